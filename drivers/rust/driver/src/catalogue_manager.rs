@@ -322,4 +322,13 @@ mod tests {
       values: hashmap!{}
     }));
   }
+
+  #[test]
+  fn matches_pattern_ignores_the_case_and_parameters_of_the_content_type() {
+    let content_type = ContentType::parse("TEXT/CSV; charset=utf-8").unwrap();
+    expect!(matches_pattern("text/csv", &content_type)).to(be_true());
+    expect!(matches_pattern("application/csv", &content_type)).to(be_false());
+    let content_type = ContentType::parse("Application/Vnd.Report+CSV").unwrap();
+    expect!(matches_pattern("application/csv", &content_type)).to(be_true());
+  }
 }
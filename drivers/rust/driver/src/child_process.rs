@@ -1,36 +1,351 @@
 //! Module for managing running child processes
 
-use std::sync::mpsc::channel;
-use std::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, TryRecvError};
+use std::time::{Duration, Instant};
 
 use anyhow::anyhow;
+use log::max_level;
+use os_info::Type;
+use semver::Version;
 use serde::{Deserialize, Serialize};
-use sysinfo::{Pid, PidExt, ProcessExt, Signal, System, SystemExt};
+use serde_json::Value;
+use sysinfo::{Pid, PidExt, Process, ProcessExt, ProcessStatus, Signal, System, SystemExt};
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Child;
+use tokio::net::TcpStream;
+#[cfg(unix)] use tokio::net::UnixStream;
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use tonic::{Code, Status};
+use tonic::codegen::InterceptedService;
+use tonic::transport::{Channel, Endpoint, Uri};
+#[cfg(unix)] use tower::service_fn;
 use tracing::{debug, error, trace, warn};
 
-use crate::plugin_models::PactPluginManifest;
+use crate::plugin_models::{PactPluginInterceptor, PactPluginManifest};
+use crate::proto::Catalogue;
+use crate::proto::pact_plugin_client::PactPluginClient;
 
+/// Key in the plugin manifest `pluginConfig` for a prefix the startup message line must start with
+pub const STARTUP_PREFIX_KEY: &str = "startupPrefix";
+
+/// Key in the plugin manifest `pluginConfig` for the time (in milliseconds) to wait for the plugin
+/// to print its startup message
+pub const STARTUP_TIMEOUT_KEY: &str = "startupTimeout";
+
+/// Environment variable with the time (in milliseconds) to wait for a plugin to print its startup
+/// message. This takes precedence over the plugin manifest, i.e. for slow CI machines.
+pub const STARTUP_TIMEOUT_ENV: &str = "PACT_PLUGIN_STARTUP_TIMEOUT";
+
+/// Time to wait for the plugin to print its startup message if it is not configured
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the startup message and the process are checked while waiting for the plugin to start
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time to wait for the rest of the output once the plugin process has exited during startup
+const EXIT_OUTPUT_WAIT: Duration = Duration::from_secs(1);
+
+/// How often the process is checked while waiting for it to exit after SIGTERM
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time to wait for the process to exit after SIGKILL
+const KILL_WAIT: Duration = Duration::from_secs(1);
+
+/// Number of the most recent lines of the plugin standard error that are kept, to report when the
+/// plugin fails to start and for `ChildPluginProcess::recent_stderr`
+pub const STDERR_LINES: usize = 100;
+
+/// Log target for the lines the plugin processes write to their standard output and error. The
+/// lines are logged with the `plugin`, `pid`, `server_key` and `stream` fields, so the output of a
+/// single plugin instance can be filtered, i.e. `pact_plugin_driver::plugin_output[{plugin=csv}]=debug`.
+pub const PLUGIN_OUTPUT_TARGET: &str = "pact_plugin_driver::plugin_output";
+
+/// Number of times to try connect to the plugin gRPC server before giving up
+const CONNECT_ATTEMPTS: u32 = 10;
+
+/// Delay before the first retry to connect to the plugin gRPC server. This is increased with each
+/// attempt.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Longest delay between the attempts to connect to the plugin port while waiting for it to be ready
+const READY_MAX_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Key in the plugin manifest `pluginConfig` for the time (in milliseconds) to wait for each ping
+/// of the health check
+pub const HEALTH_CHECK_TIMEOUT_KEY: &str = "healthCheckTimeout";
+
+/// Key in the plugin manifest `pluginConfig` for the number of pings of the health check to try
+pub const HEALTH_CHECK_ATTEMPTS_KEY: &str = "healthCheckAttempts";
+
+/// Key in the plugin manifest `pluginConfig` to ping the plugin once it has started. Without it, the
+/// plugin only has to accept connections, as it may not respond to requests until it has been initialised.
+pub const HEALTH_CHECK_PING_KEY: &str = "healthCheckPing";
+
+/// Check that a started plugin is responding to gRPC requests, by sending it a ping (an update
+/// catalogue request with an empty catalogue)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthCheck {
+  /// Time to wait for the plugin to respond to each ping
+  pub timeout: Duration,
+  /// Number of pings to try before the plugin is considered not to be responding
+  pub attempts: u32
+}
+
+impl Default for HealthCheck {
+  fn default() -> Self {
+    HealthCheck {
+      timeout: Duration::from_secs(5),
+      attempts: 3
+    }
+  }
+}
+
+impl HealthCheck {
+  /// Health check for the plugin, using the `healthCheckTimeout` (in milliseconds) and
+  /// `healthCheckAttempts` values from the plugin manifest `pluginConfig` if they are set
+  pub fn from_manifest(manifest: &PactPluginManifest) -> Self {
+    let mut health_check = HealthCheck::default();
+    if let Some(value) = manifest.plugin_config.get(HEALTH_CHECK_TIMEOUT_KEY) {
+      match config_number(value) {
+        Some(millis) if millis > 0 => health_check.timeout = Duration::from_millis(millis),
+        _ => warn!("Ignoring the plugin {} value, expected a number of milliseconds, got {}", HEALTH_CHECK_TIMEOUT_KEY, value)
+      }
+    }
+    if let Some(value) = manifest.plugin_config.get(HEALTH_CHECK_ATTEMPTS_KEY) {
+      match config_number(value).and_then(|attempts| u32::try_from(attempts).ok()) {
+        Some(attempts) if attempts > 0 => health_check.attempts = attempts,
+        _ => warn!("Ignoring the plugin {} value, expected a positive number, got {}", HEALTH_CHECK_ATTEMPTS_KEY, value)
+      }
+    }
+    health_check
+  }
+
+  /// If the plugin should be pinged once it has started, from the `healthCheckPing` value in the
+  /// plugin manifest `pluginConfig`. This is false if it is not set.
+  pub fn ping_on_startup(manifest: &PactPluginManifest) -> bool {
+    match manifest.plugin_config.get(HEALTH_CHECK_PING_KEY) {
+      Some(Value::Bool(ping)) => *ping,
+      Some(value) => {
+        warn!("Ignoring the plugin {} value, expected a boolean, got {}", HEALTH_CHECK_PING_KEY, value);
+        false
+      }
+      None => false
+    }
+  }
+
+  /// Total time to wait for the plugin to accept connections when it is not pinged, which is the
+  /// timeout for each of the attempts
+  pub fn ready_timeout(&self) -> Duration {
+    self.timeout * self.attempts
+  }
+}
+
+/// Startup message printed by a plugin process as a single line of JSON, i.e.
+/// `{"port":12345,"serverKey":"...","version":"0.1.0"}`. A plugin listening on a Unix domain socket
+/// prints the path of the socket instead of the port, i.e. `{"socket":"/tmp/plugin.sock","serverKey":"..."}`.
+/// More attributes may be added to the startup message, so use `RunningPluginInfo::new` to create
+/// this outside of the driver (i.e. in a custom handshake parser).
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
+#[non_exhaustive]
 pub struct RunningPluginInfo {
+  /// Port the plugin gRPC server is listening on. This is zero if it is listening on a socket.
+  #[serde(default)]
   pub port: u16,
-  pub server_key: String
+  /// Path of the Unix domain socket the plugin gRPC server is listening on, instead of a port
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub socket: Option<String>,
+  /// Key the driver must send with each request
+  pub server_key: String,
+  /// Version of the running plugin, which is checked against the version in the plugin manifest.
+  /// This is optional, as older plugins do not report it.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>
 }
 
-/// Running child process
+impl RunningPluginInfo {
+  /// Startup info for a plugin listening on the port, without a version
+  pub fn new(port: u16, server_key: &str) -> Self {
+    RunningPluginInfo {
+      port,
+      socket: None,
+      server_key: server_key.to_string(),
+      version: None
+    }
+  }
+
+  /// Try to parse the startup message from a line of the plugin standard output. If a prefix is
+  /// given, only lines starting with that prefix are considered. Otherwise, the line must be a JSON
+  /// object with the `serverKey` attribute and either the `port` or `socket` one. Returns `None` if
+  /// the line is not the startup message.
+  pub fn from_startup_line(line: &str, prefix: Option<&str>) -> Option<anyhow::Result<RunningPluginInfo>> {
+    let line = line.trim();
+    if let Some(prefix) = prefix {
+      line.strip_prefix(prefix)
+        .map(|message| serde_json::from_str::<RunningPluginInfo>(message.trim())
+          .map_err(|err| anyhow!("Failed to read startup info from plugin - {}", err))
+          .and_then(RunningPluginInfo::validate))
+    } else if line.starts_with('{') {
+      match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(attributes)) if (attributes.contains_key("port") || attributes.contains_key("socket"))
+          && attributes.contains_key("serverKey") => {
+          Some(serde_json::from_value::<RunningPluginInfo>(Value::Object(attributes))
+            .map_err(|err| anyhow!("Failed to read startup info from plugin - {}", err))
+            .and_then(RunningPluginInfo::validate))
+        }
+        _ => {
+          trace!("Ignoring JSON line that is not the plugin startup message");
+          None
+        }
+      }
+    } else {
+      None
+    }
+  }
+
+  fn validate(self) -> anyhow::Result<RunningPluginInfo> {
+    if self.port == 0 && self.socket.is_none() {
+      Err(anyhow!("Failed to read startup info from plugin - the startup message must have either a port or a socket"))
+    } else {
+      Ok(self)
+    }
+  }
+
+  /// Description of where the plugin is listening, for messages, i.e. `port 12345`
+  pub fn address(&self) -> String {
+    match &self.socket {
+      Some(socket) => format!("socket {}", socket),
+      None => format!("port {}", self.port)
+    }
+  }
+}
+
+/// Parser for the startup message, called with each line of the plugin standard output until it
+/// returns the startup info (or an error if the startup message is not valid)
+type HandshakeParser = Box<dyn FnMut(&str) -> Option<anyhow::Result<RunningPluginInfo>> + Send>;
+
+/// Running child process. The clones of this share the process, which is only killed on drop once
+/// all of them have been dropped.
 #[derive(Debug, Clone)]
 pub struct ChildPluginProcess {
   /// OS PID of the running process
   pub child_pid: usize,
   /// Info on the running plugin
-  pub plugin_info: RunningPluginInfo
+  pub plugin_info: RunningPluginInfo,
+  /// State of the process that is shared between the clones
+  state: Arc<ProcessState>
+}
+
+/// State of a running child process that is shared between the clones of its handle
+#[derive(Debug)]
+struct ProcessState {
+  /// OS PID of the running process
+  child_pid: usize,
+  /// Tasks polling the standard output and error of the process
+  reader_tasks: Mutex<Vec<JoinHandle<()>>>,
+  /// Most recent lines the process wrote to its standard error
+  stderr_lines: Arc<Mutex<VecDeque<String>>>,
+  /// Time the process was started (in seconds since the epoch). This is used to make sure that the
+  /// process is not killed if the PID has been reused by the operating system.
+  start_time: Option<u64>,
+  /// If the process has already been killed
+  killed: AtomicBool,
+  /// If the process should be killed when this is dropped
+  kill_on_drop: AtomicBool,
+  /// Manifest of the plugin, with the environment variables and working directory the process was
+  /// spawned with. These are used to start the process again with `restart`.
+  manifest: PactPluginManifest,
+  env: HashMap<String, String>,
+  cwd: Option<PathBuf>
+}
+
+impl ProcessState {
+  /// Kill the process if it has not already been killed, and abort the tasks polling its output
+  fn kill(&self) {
+    if self.killed.swap(true, Ordering::SeqCst) {
+      trace!("Child process with PID {} has already been killed", self.child_pid);
+      return;
+    }
+
+    self.send_signal(Signal::Term);
+    self.abort_reader_tasks();
+  }
+
+  fn abort_reader_tasks(&self) {
+    if let Ok(tasks) = self.reader_tasks.lock() {
+      for task in tasks.iter() {
+        task.abort();
+      }
+    }
+  }
+
+  /// Send the signal to the plugin process, returning true if it was sent. The signal is not sent
+  /// if the process is not found, or if the PID now belongs to a different process.
+  fn send_signal(&self, signal: Signal) -> bool {
+    let mut s = System::new();
+    s.refresh_process(Pid::from_u32(self.child_pid as u32));
+    if let Some(process) = s.process(Pid::from_u32(self.child_pid as u32)) {
+      if self.start_time.is_none() || self.start_time == Some(process.start_time()) {
+        signal_process(process, signal)
+      } else {
+        warn!("Child process with PID {} has a different start time, the PID has been reused so not killing it",
+          self.child_pid);
+        false
+      }
+    } else {
+      warn!("Child process with PID {} was not found", self.child_pid);
+      false
+    }
+  }
+
+  /// If the plugin process is still running. A process that has exited but not been reaped yet (a
+  /// zombie) is not running.
+  fn is_running(&self) -> bool {
+    let mut s = System::new();
+    s.refresh_process(Pid::from_u32(self.child_pid as u32));
+    s.process(Pid::from_u32(self.child_pid as u32))
+      .map(|process| (self.start_time.is_none() || self.start_time == Some(process.start_time()))
+        && process.status() != ProcessStatus::Zombie)
+      .unwrap_or(false)
+  }
+}
+
+impl Drop for ProcessState {
+  fn drop(&mut self) {
+    if self.kill_on_drop.load(Ordering::SeqCst) && !self.killed.load(Ordering::SeqCst) {
+      debug!("Child process handle for PID {} dropped, killing the process", self.child_pid);
+      self.kill();
+    }
+  }
 }
 
 impl ChildPluginProcess {
   /// Start the child process and try read the startup JSON message from its standard output.
-  pub async fn new(mut child: Child, manifest: &PactPluginManifest) -> anyhow::Result<Self> {
+  pub async fn new(child: Child, manifest: &PactPluginManifest) -> anyhow::Result<Self> {
+    Self::start(child, manifest, default_parser(manifest), HashMap::new(), None).await
+  }
+
+  /// Start the child process, using the given parser to find the startup message in its standard
+  /// output. The parser is called with each line until it returns the startup info. This is for
+  /// plugins that print their startup info in a different format to the default JSON message.
+  pub async fn new_with_handshake_parser<F>(child: Child, manifest: &PactPluginManifest, mut parser: F) -> anyhow::Result<Self>
+    where F: FnMut(&str) -> Option<RunningPluginInfo> + Send + 'static {
+    Self::start(child, manifest, Box::new(move |line: &str| parser(line).map(Ok)), HashMap::new(), None).await
+  }
+
+  async fn start(
+    mut child: Child,
+    manifest: &PactPluginManifest,
+    mut parser: HandshakeParser,
+    env: HashMap<String, String>,
+    cwd: Option<PathBuf>
+  ) -> anyhow::Result<Self> {
     let (tx, rx) = channel();
     let child_pid = child.id()
       .ok_or_else(|| anyhow!("Could not get the child process ID"))?;
@@ -41,48 +356,56 @@ impl ChildPluginProcess {
 
     trace!("Starting output polling tasks...");
 
+    // The server key is only known once the startup message has been read
+    let server_key = Arc::new(Mutex::new(String::default()));
     let mfso = manifest.clone();
-    tokio::task::spawn(async move {
+    let stdout_key = server_key.clone();
+    let stdout_task = tokio::task::spawn(async move {
       trace!("Starting task to poll plugin stdout");
       let mut startup_read = false;
       let reader = BufReader::new(child_out);
       let mut lines = reader.lines();
       let plugin_name = mfso.name.as_str();
-      while let Ok(line) = lines.next_line().await {
-        if let Some(line) = line {
-          debug!("Plugin({}, {}, STDOUT) || {}", plugin_name, child_pid, line);
-          if !startup_read {
-            let line = line.trim();
-            if line.starts_with("{") {
-              startup_read = true;
-              match serde_json::from_str::<RunningPluginInfo>(line) {
-                Ok(plugin_info) => {
-                  tx.send(Ok(ChildPluginProcess {
-                    child_pid: child_pid as usize,
-                    plugin_info
-                  })).unwrap_or_default()
-                }
-                Err(err) => {
-                  error!("Failed to read startup info from plugin - {}", err);
-                  tx.send(Err(anyhow!("Failed to read startup info from plugin - {}", err)))
-                    .unwrap_or_default()
-                }
-              }
+      while let Ok(Some(line)) = lines.next_line().await {
+        let startup_result = if startup_read { None } else { parser(&line) };
+        if let Some(Ok(plugin_info)) = &startup_result {
+          if let Ok(mut key) = stdout_key.lock() {
+            *key = plugin_info.server_key.clone();
+          }
+        }
+        let key = current_server_key(&stdout_key);
+        debug!(target: PLUGIN_OUTPUT_TARGET, plugin = plugin_name, pid = child_pid, server_key = key.as_str(),
+          stream = "stdout", "Plugin({}, {}, {}, STDOUT) || {}", plugin_name, child_pid, key, line);
+        if let Some(result) = startup_result {
+          startup_read = true;
+          match result {
+            Ok(plugin_info) => tx.send(Ok(plugin_info)).unwrap_or_default(),
+            Err(err) => {
+              error!("{}", err);
+              tx.send(Err(err)).unwrap_or_default()
             }
           }
         }
       }
-      trace!("Task to poll plugin stderr done");
+      trace!("Task to poll plugin stdout done");
     });
 
     let plugin_name = manifest.name.clone();
-    tokio::task::spawn(async move {
+    let stderr_lines = Arc::new(Mutex::new(VecDeque::new()));
+    let stderr_buffer = stderr_lines.clone();
+    let stderr_task = tokio::task::spawn(async move {
       trace!("Starting task to poll plugin stderr");
       let reader = BufReader::new(child_err);
       let mut lines = reader.lines();
-      while let Ok(line) = lines.next_line().await {
-        if let Some(line) = line {
-          debug!("Plugin({}, {}, STDERR) || {}", plugin_name, child_pid, line);
+      while let Ok(Some(line)) = lines.next_line().await {
+        let key = current_server_key(&server_key);
+        debug!(target: PLUGIN_OUTPUT_TARGET, plugin = plugin_name.as_str(), pid = child_pid, server_key = key.as_str(),
+          stream = "stderr", "Plugin({}, {}, {}, STDERR) || {}", plugin_name, child_pid, key, line);
+        if let Ok(mut buffer) = stderr_buffer.lock() {
+          if buffer.len() == STDERR_LINES {
+            buffer.pop_front();
+          }
+          buffer.push_back(line);
         }
       }
       trace!("Task to poll plugin stderr done");
@@ -90,30 +413,1003 @@ impl ChildPluginProcess {
 
     trace!("Starting output polling tasks... DONE");
 
-    // TODO: This timeout needs to be configurable
-    // TODO: Timeout is not working on Alpine, waits indefinitely if the plugin does not start properly
-    match rx.recv_timeout(Duration::from_secs(60)) {
-      Ok(value) => value,
-      Err(err) => {
-        error!("Timeout waiting to get plugin startup info: {}", err);
-        Err(anyhow!("Plugin process did not output the correct startup message in 60 seconds: {}", err))
+    // The startup message is waited for without blocking the polling tasks, and the process is
+    // checked while waiting so that a plugin that fails to start is reported straight away
+    let reader_tasks = vec![stdout_task, stderr_task];
+    let timeout = startup_timeout(manifest);
+    let deadline = Instant::now() + timeout;
+    let result = loop {
+      match rx.try_recv() {
+        Ok(result) => break Some(result),
+        Err(TryRecvError::Disconnected) => break None,
+        Err(TryRecvError::Empty) => {}
+      }
+      if !matches!(child.try_wait(), Ok(None)) {
+        // The process may have printed the startup message just before it exited, so wait for the
+        // polling tasks to read the rest of its output
+        wait_for_tasks(&reader_tasks, EXIT_OUTPUT_WAIT).await;
+        break rx.try_recv().ok();
+      }
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining.is_zero() {
+        error!("Timeout waiting to get plugin startup info after {:?}", timeout);
+        reader_tasks.iter().for_each(|task| task.abort());
+        return Err(anyhow!("Plugin process did not output the correct startup message in {:?} (this can be increased \
+          with the {} environment variable or the {} plugin config value)", timeout, STARTUP_TIMEOUT_ENV,
+          STARTUP_TIMEOUT_KEY));
+      }
+      tokio::time::sleep(remaining.min(STARTUP_POLL_INTERVAL)).await;
+    };
+
+    let result = result.map(|result| result.and_then(|plugin_info| {
+      check_plugin_version(manifest, &plugin_info).map(|_| plugin_info)
+    }));
+    match result {
+      Some(Ok(plugin_info)) => Ok(ChildPluginProcess {
+        child_pid: child_pid as usize,
+        plugin_info,
+        state: Arc::new(ProcessState {
+          child_pid: child_pid as usize,
+          reader_tasks: Mutex::new(reader_tasks),
+          stderr_lines,
+          start_time: process_start_time(child_pid),
+          killed: AtomicBool::new(false),
+          kill_on_drop: AtomicBool::new(true),
+          manifest: manifest.clone(),
+          env,
+          cwd
+        })
+      }),
+      Some(Err(err)) => {
+        reader_tasks.iter().for_each(|task| task.abort());
+        Err(err)
+      }
+      None => {
+        // The standard output has been closed without the startup message, which is normally because
+        // the process has exited. The standard error is read until it is closed as well.
+        wait_for_tasks(&reader_tasks, EXIT_OUTPUT_WAIT).await;
+        let status = child.try_wait().ok().flatten();
+        reader_tasks.iter().for_each(|task| task.abort());
+        let stderr = stderr_lines.lock()
+          .map(|lines| lines.iter().cloned().collect::<Vec<_>>())
+          .unwrap_or_default();
+        let stderr = if stderr.is_empty() {
+          String::default()
+        } else {
+          format!(", standard error:\n{}", stderr.join("\n"))
+        };
+        let message = match status {
+          Some(status) => match status.code() {
+            Some(code) => format!("Plugin process exited with code {} before printing the startup message{}", code, stderr),
+            None => format!("Plugin process exited ({}) before printing the startup message{}", status, stderr)
+          },
+          None => format!("Plugin process closed its standard output without printing the startup message{}", stderr)
+        };
+        error!("{}", message);
+        Err(anyhow!(message))
       }
     }
   }
 
-  /// Port the plugin is running on
+  /// Abort the tasks polling the standard output and error of the plugin process
+  pub fn abort_reader_tasks(&self) {
+    self.state.abort_reader_tasks();
+  }
+
+  /// Wait for the tasks polling the standard output and error of the plugin process to finish.
+  /// These will finish once the plugin process has exited and closed its output streams.
+  pub async fn wait_for_reader_tasks(&self) {
+    let tasks = match self.state.reader_tasks.lock() {
+      Ok(mut tasks) => tasks.drain(..).collect::<Vec<_>>(),
+      Err(_) => vec![]
+    };
+    for task in tasks {
+      if let Err(err) = task.await {
+        if !err.is_cancelled() {
+          warn!("Plugin output polling task failed - {}", err);
+        }
+      }
+    }
+  }
+
+  /// The most recent lines (up to 100) the plugin process wrote to its standard error, oldest first.
+  /// This is to help diagnose a misbehaving plugin, i.e. when a verification fails.
+  pub fn recent_stderr(&self) -> Vec<String> {
+    self.state.stderr_lines.lock()
+      .map(|lines| lines.iter().cloned().collect())
+      .unwrap_or_default()
+  }
+
+  /// Port the plugin is running on. This is zero if the plugin is listening on a socket.
   pub fn port(&self) -> u16 {
     self.plugin_info.port
   }
 
-  /// Kill the running plugin process
+  /// Path of the Unix domain socket the plugin is listening on, if it is not listening on a port
+  pub fn socket(&self) -> Option<&str> {
+    self.plugin_info.socket.as_deref()
+  }
+
+  /// Key the plugin printed in its startup message, which must be sent with each request to its
+  /// gRPC server
+  pub fn server_key(&self) -> &str {
+    self.plugin_info.server_key.as_str()
+  }
+
+  /// Connect to the gRPC server of the plugin, returning a client that sends the server key with
+  /// each request. The plugin may print its startup message before its server is accepting
+  /// connections, so the connection is retried until the server accepts it (up to 10 attempts).
+  pub async fn connect(&self) -> anyhow::Result<PactPluginClient<InterceptedService<Channel, PactPluginInterceptor>>> {
+    let interceptor = PactPluginInterceptor::new(self.server_key())?;
+    let mut attempt = 1;
+    loop {
+      if self.state.killed.load(Ordering::SeqCst) {
+        return Err(anyhow!("Can not connect to the plugin with PID {}, the process has been killed", self.child_pid));
+      }
+      match connect_channel(&self.plugin_info).await {
+        Ok(channel) => return Ok(PactPluginClient::with_interceptor(channel, interceptor)),
+        Err(err) if attempt < CONNECT_ATTEMPTS => {
+          debug!("Connection attempt {} to the plugin on {} failed, will retry - {}", attempt, self.plugin_info.address(), err);
+          tokio::time::sleep(CONNECT_RETRY_DELAY * attempt).await;
+          attempt += 1;
+        }
+        Err(err) => return Err(anyhow!("Failed to connect to the plugin on {} after {} attempts - {}",
+          self.plugin_info.address(), attempt, err))
+      }
+    }
+  }
+
+  /// Wait up to the timeout for the plugin gRPC server to accept connections on its port (or socket).
+  /// The plugin may print its startup message before its server is listening, so connections to the
+  /// port are tried (on the IP6 and then the IP4 address) with an increasing delay between the attempts.
+  /// This fails straight away if the process is no longer running. Use `ping` to also check that the
+  /// server is responding to requests.
+  pub async fn wait_until_ready(&self, timeout: Duration) -> anyhow::Result<()> {
+    let deadline = Instant::now() + timeout;
+    let mut delay = CONNECT_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+      if !self.process_alive() {
+        return Err(anyhow!("Can not wait for the plugin with PID {} to be ready, the process is not running", self.child_pid));
+      }
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      let result = tokio::time::timeout(remaining, connect_stream(&self.plugin_info)).await
+        .unwrap_or_else(|_| Err(anyhow!("no connection in {:?}", remaining)));
+      let err = match result {
+        Ok(()) => {
+          debug!("Plugin on {} is accepting connections after {} attempts", self.plugin_info.address(), attempt);
+          return Ok(());
+        }
+        Err(err) => err
+      };
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining.is_zero() {
+        return Err(anyhow!("The plugin on {} was not accepting connections after {:?} ({} attempts) - {}",
+          self.plugin_info.address(), timeout, attempt, err));
+      }
+      trace!("Connection attempt {} to the plugin on {} failed, will retry - {}", attempt, self.plugin_info.address(), err);
+      tokio::time::sleep(delay.min(remaining)).await;
+      delay = (delay * 2).min(READY_MAX_RETRY_DELAY);
+      attempt += 1;
+    }
+  }
+
+  /// If the plugin process is still running (and has not been killed). With a health check, the
+  /// plugin must also respond to a ping.
+  pub async fn is_alive(&self, health_check: Option<&HealthCheck>) -> bool {
+    match health_check {
+      Some(health_check) => self.ping(health_check).await.is_ok(),
+      None => self.process_alive()
+    }
+  }
+
+  /// Send a ping (an update catalogue request with an empty catalogue) to the plugin, to check that
+  /// its gRPC server is accepting connections and responding to requests. Each attempt waits up to
+  /// the health check timeout, and the ping fails straight away if the process is no longer running.
+  /// A plugin that responds with an unimplemented status is responding, so the ping succeeds.
+  pub async fn ping(&self, health_check: &HealthCheck) -> anyhow::Result<()> {
+    let interceptor = PactPluginInterceptor::new(self.server_key())?;
+    let mut attempt = 1;
+    loop {
+      if !self.process_alive() {
+        return Err(anyhow!("Can not ping the plugin with PID {}, the process is not running", self.child_pid));
+      }
+      let ping = async {
+        let channel = connect_channel(&self.plugin_info).await?;
+        let mut client = PactPluginClient::with_interceptor(channel, interceptor.clone());
+        ping_result(client.update_catalogue(tonic::Request::new(Catalogue { catalogue: vec![] })).await)
+      };
+      let result = tokio::time::timeout(health_check.timeout, ping).await
+        .unwrap_or_else(|_| Err(anyhow!("no response in {:?}", health_check.timeout)));
+      match result {
+        Ok(()) => return Ok(()),
+        Err(err) if attempt < health_check.attempts => {
+          debug!("Ping {} to the plugin on {} failed, will retry - {}", attempt, self.plugin_info.address(), err);
+          tokio::time::sleep(CONNECT_RETRY_DELAY * attempt).await;
+          attempt += 1;
+        }
+        Err(err) => return Err(anyhow!("The plugin on {} did not respond to a ping after {} attempts - {}",
+          self.plugin_info.address(), attempt, err))
+      }
+    }
+  }
+
+  fn process_alive(&self) -> bool {
+    !self.state.killed.load(Ordering::SeqCst) && self.is_running()
+  }
+
+  /// Kill the running plugin process. This will also abort the tasks polling the output of the
+  /// process. The process will not be killed if it has already been killed, or if the PID now
+  /// belongs to a different process.
   pub fn kill(&self) {
-    let mut s = System::new();
-    s.refresh_processes();
-    if let Some(process) = s.process(Pid::from_u32(self.child_pid as u32)) {
-      process.kill_with(Signal::Term);
-    } else {
-      warn!("Child process with PID {} was not found", self.child_pid);
+    self.state.kill();
+  }
+
+  /// Shut down the running plugin process. This sends SIGTERM to the process and waits up to the
+  /// grace period for it to exit, then sends SIGKILL if it is still running (i.e. the plugin ignores
+  /// SIGTERM). As with `kill`, the tasks polling the output of the process are aborted, and the
+  /// process is not signalled if it has already been killed or the PID now belongs to a different process.
+  pub async fn shutdown(&self, grace: Duration) {
+    if self.state.killed.swap(true, Ordering::SeqCst) {
+      trace!("Child process with PID {} has already been killed", self.child_pid);
+      return;
+    }
+
+    if self.state.send_signal(Signal::Term) {
+      if self.wait_for_exit(grace).await {
+        debug!("Child process with PID {} exited after SIGTERM", self.child_pid);
+      } else if self.state.send_signal(Signal::Kill) {
+        warn!("Child process with PID {} was still running {:?} after SIGTERM, killing it with SIGKILL",
+          self.child_pid, grace);
+        if !self.wait_for_exit(KILL_WAIT).await {
+          warn!("Child process with PID {} is still running after SIGKILL", self.child_pid);
+        }
+      }
+    }
+    self.abort_reader_tasks();
+  }
+
+  /// Wait up to the timeout for the plugin process to exit, returning true if it has exited
+  async fn wait_for_exit(&self, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while self.is_running() {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining.is_zero() {
+        return false;
+      }
+      tokio::time::sleep(remaining.min(SHUTDOWN_POLL_INTERVAL)).await;
+    }
+    true
+  }
+
+  fn is_running(&self) -> bool {
+    self.state.is_running()
+  }
+
+  /// Start the plugin process again, i.e. after it has crashed and `is_alive` returns false. A new
+  /// process is spawned from the plugin manifest with the same environment variables and working
+  /// directory, and once it has printed its startup message (which is read with the default parser),
+  /// the old process is killed if it is still running and its PID, port and server key are replaced
+  /// together. If the new process fails to start, the old details are kept.
+  ///
+  /// Any interactions the plugin was working on (i.e. mock servers it started) are lost, and need to
+  /// be set up again with the restarted plugin. Use `PactPlugin::restart` for a plugin that has been
+  /// loaded, as that is shared between all the handles to the plugin.
+  pub async fn restart(&mut self) -> anyhow::Result<()> {
+    let process = self.respawn().await?;
+    self.kill();
+    let old = std::mem::replace(self, process);
+    debug!("Plugin {} restarted with PID {} (was {})", self.state.manifest.name, self.child_pid, old.child_pid);
+    Ok(())
+  }
+
+  /// Spawn a new process for the plugin, with the same manifest, environment variables, working
+  /// directory and kill on drop setting that this one was spawned with. This process is left as is.
+  pub async fn respawn(&self) -> anyhow::Result<ChildPluginProcess> {
+    let state = &self.state;
+    warn!("Restarting the plugin {} with PID {}", state.manifest.name, self.child_pid);
+    let process = spawn(&state.manifest, state.env.clone(), state.cwd.clone()).await?;
+    process.set_kill_on_drop(state.kill_on_drop.load(Ordering::SeqCst));
+    Ok(process)
+  }
+
+  /// Set if the plugin process should be killed when the last clone of this handle is dropped (the
+  /// default). Set this to false if the plugin process needs to outlive all the handles. This applies
+  /// to all the clones.
+  pub fn set_kill_on_drop(&self, kill_on_drop: bool) {
+    self.state.kill_on_drop.store(kill_on_drop, Ordering::SeqCst);
+  }
+}
+
+/// Parser for the default JSON startup message, using the `startupPrefix` from the plugin manifest
+fn default_parser(manifest: &PactPluginManifest) -> HandshakeParser {
+  let startup_prefix = manifest.plugin_config.get(STARTUP_PREFIX_KEY)
+    .and_then(|prefix| prefix.as_str())
+    .map(|prefix| prefix.to_string());
+  Box::new(move |line: &str| RunningPluginInfo::from_startup_line(line, startup_prefix.as_deref()))
+}
+
+/// Server key of the plugin for the logged output, or an empty string before the startup message
+/// has been read
+fn current_server_key(server_key: &Mutex<String>) -> String {
+  server_key.lock().map(|key| key.clone()).unwrap_or_default()
+}
+
+/// Connect a channel to the gRPC server of the plugin. This is either on the Unix domain socket, or
+/// on the local port, trying the IP6 address before the IP4 one.
+pub(crate) async fn connect_channel(plugin_info: &RunningPluginInfo) -> anyhow::Result<Channel> {
+  if let Some(socket) = &plugin_info.socket {
+    return connect_socket_channel(socket).await;
+  }
+  let port = plugin_info.port;
+  match Channel::from_shared(format!("http://[::1]:{}", port))?.connect().await {
+    Ok(channel) => Ok(channel),
+    Err(err) => {
+      debug!("IP6 connection failed, will try IP4 address - {err}");
+      Channel::from_shared(format!("http://127.0.0.1:{}", port))?.connect().await
+        .map_err(|err| anyhow!(err))
+    }
+  }
+}
+
+/// Path to the executable of the plugin: the entry point for the current OS if the manifest has one,
+/// otherwise the default entry point. Relative paths are in the plugin directory.
+pub fn plugin_entry_point(manifest: &PactPluginManifest) -> PathBuf {
+  let os_info = os_info::get();
+  debug!("Detected OS: {}", os_info);
+  let path = if let Some(entry_point) = manifest.entry_points.get(&os_info.to_string()) {
+    PathBuf::from(entry_point)
+  } else if os_info.os_type() == Type::Windows && manifest.entry_points.contains_key("windows") {
+    PathBuf::from(manifest.entry_points.get("windows").unwrap())
+  } else {
+    PathBuf::from(&manifest.entry_point)
+  };
+
+  if !path.is_absolute() || !path.exists() {
+    PathBuf::from(manifest.plugin_dir.clone()).join(path)
+  } else {
+    path
+  }
+}
+
+/// Start the plugin process from the entry point in the manifest, and read its startup message.
+/// The process gets the `LOG_LEVEL` and `RUST_LOG` environment variables set to the current log
+/// level, together with the given environment variables (which take precedence), and runs in the
+/// given working directory (or the plugin directory if there is none). If the startup message can
+/// not be read, the process is killed.
+pub async fn spawn(
+  manifest: &PactPluginManifest,
+  env: HashMap<String, String>,
+  cwd: Option<PathBuf>
+) -> anyhow::Result<ChildPluginProcess> {
+  let path = plugin_entry_point(manifest);
+  debug!("Starting plugin using {:?}", &path);
+
+  let log_level = max_level();
+  let mut child_command = Command::new(path.clone());
+  child_command
+    .env("LOG_LEVEL", log_level.to_string())
+    .env("RUST_LOG", log_level.to_string())
+    .envs(env.clone())
+    .current_dir(cwd.clone().unwrap_or_else(|| PathBuf::from(manifest.plugin_dir.clone())));
+  if let Some(args) = &manifest.args {
+    child_command.args(args);
+  }
+
+  let child = child_command
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|err| anyhow!("Was not able to start plugin process for '{}' - {}",
+      path.to_string_lossy(), err))?;
+  let child_pid = child.id().unwrap_or_default();
+  debug!("Plugin {} started with PID {}", manifest.name, child_pid);
+
+  match ChildPluginProcess::start(child, manifest, default_parser(manifest), env, cwd).await {
+    Ok(child) => Ok(child),
+    Err(err) => {
+      let mut s = System::new();
+      s.refresh_process(Pid::from_u32(child_pid));
+      if let Some(process) = s.process(Pid::from_u32(child_pid)) {
+        signal_process(process, Signal::Term);
+      } else {
+        warn!("Child process with PID {} was not found", child_pid);
+      }
+      Err(err)
     }
   }
 }
+
+/// Time to wait for the plugin to print its startup message. This is taken from the
+/// `PACT_PLUGIN_STARTUP_TIMEOUT` environment variable, then the `startupTimeout` value in the plugin
+/// manifest `pluginConfig` (both in milliseconds), and defaults to 60 seconds.
+pub fn startup_timeout(manifest: &PactPluginManifest) -> Duration {
+  let env_value = env::var(STARTUP_TIMEOUT_ENV).ok().map(Value::String);
+  let config_value = manifest.plugin_config.get(STARTUP_TIMEOUT_KEY).cloned();
+  for (source, value) in [(STARTUP_TIMEOUT_ENV, env_value), (STARTUP_TIMEOUT_KEY, config_value)] {
+    if let Some(value) = value {
+      match config_number(&value) {
+        Some(millis) if millis > 0 => return Duration::from_millis(millis),
+        _ => warn!("Ignoring the plugin startup timeout from {}, expected a number of milliseconds, got {}", source, value)
+      }
+    }
+  }
+  DEFAULT_STARTUP_TIMEOUT
+}
+
+/// Checks the version the plugin reported in its startup message against the version of the plugin
+/// manifest, using semantic version precedence (so build metadata is ignored). A plugin that is older
+/// than the manifest is an error, as it is most likely an old plugin executable that has been started
+/// with a newer manifest. A newer plugin is only logged as a warning. Versions that can not be parsed
+/// are not checked.
+pub fn check_plugin_version(manifest: &PactPluginManifest, plugin_info: &RunningPluginInfo) -> anyhow::Result<()> {
+  let reported = match &plugin_info.version {
+    Some(version) if !manifest.version.is_empty() => version,
+    _ => {
+      trace!("Plugin {} did not report its version, not checking it", manifest.name);
+      return Ok(());
+    }
+  };
+  match (Version::parse(reported), Version::parse(&manifest.version)) {
+    (Ok(version), Ok(expected)) => match (version.major, version.minor, version.patch, version.pre)
+      .cmp(&(expected.major, expected.minor, expected.patch, expected.pre)) {
+      std::cmp::Ordering::Equal => Ok(()),
+      std::cmp::Ordering::Less => Err(anyhow!("Plugin {} reported version {}, which is older than the version in its manifest ({}). \
+        The plugin executable is probably from an older version of the plugin", manifest.name, reported, manifest.version)),
+      std::cmp::Ordering::Greater => {
+        warn!("Plugin {} reported version {}, which is newer than the version in its manifest ({})", manifest.name,
+          reported, manifest.version);
+        Ok(())
+      }
+    },
+    _ => {
+      warn!("Could not compare the version reported by plugin {} ({}) with the version in its manifest ({})",
+        manifest.name, reported, manifest.version);
+      Ok(())
+    }
+  }
+}
+
+/// Positive number from a plugin config value, which can be a number or a string
+// A plugin that does not implement the update catalogue request is still responding to requests
+fn ping_result(result: Result<tonic::Response<()>, Status>) -> anyhow::Result<()> {
+  match result {
+    Ok(_) => Ok(()),
+    Err(status) if status.code() == Code::Unimplemented => {
+      debug!("Plugin responded to the ping with an unimplemented status, treating it as responding");
+      Ok(())
+    }
+    Err(status) => Err(status.into())
+  }
+}
+
+fn config_number(value: &Value) -> Option<u64> {
+  match value {
+    Value::Number(number) => number.as_u64(),
+    Value::String(s) => s.trim().parse::<u64>().ok(),
+    _ => None
+  }
+}
+
+/// Send the signal to the process. Windows does not have signals, so there the process is terminated
+/// (with `TerminateProcess`) for any signal, i.e. SIGTERM.
+#[cfg(windows)]
+fn signal_process(process: &Process, signal: Signal) -> bool {
+  trace!("Terminating process {} instead of sending it {:?}", process.pid(), signal);
+  process.kill()
+}
+
+/// Send the signal to the process
+#[cfg(not(windows))]
+fn signal_process(process: &Process, signal: Signal) -> bool {
+  process.kill_with(signal).unwrap_or(false)
+}
+
+/// Waits (up to the timeout) for the tasks to finish, without consuming them
+async fn wait_for_tasks(tasks: &[JoinHandle<()>], timeout: Duration) {
+  let deadline = Instant::now() + timeout;
+  while Instant::now() < deadline && !tasks.iter().all(|task| task.is_finished()) {
+    tokio::time::sleep(Duration::from_millis(10)).await;
+  }
+}
+
+fn process_start_time(pid: u32) -> Option<u64> {
+  let mut s = System::new();
+  s.refresh_process(Pid::from_u32(pid));
+  s.process(Pid::from_u32(pid)).map(|process| process.start_time())
+}
+#[cfg(unix)]
+async fn connect_socket_channel(socket: &str) -> anyhow::Result<Channel> {
+  let socket = socket.to_string();
+  // The URI is required, but is not used to connect
+  Endpoint::from_static("http://[::]:50051")
+    .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect(socket.clone())))
+    .await
+    .map_err(|err| anyhow!(err))
+}
+
+#[cfg(not(unix))]
+async fn connect_socket_channel(socket: &str) -> anyhow::Result<Channel> {
+  Err(anyhow!("Can not connect to the socket {}, Unix domain sockets are not supported on this platform", socket))
+}
+
+/// Open a connection to the Unix domain socket or the local port of the plugin, trying the IP6
+/// address before the IP4 one
+async fn connect_stream(plugin_info: &RunningPluginInfo) -> anyhow::Result<()> {
+  if let Some(socket) = &plugin_info.socket {
+    #[cfg(unix)]
+    return UnixStream::connect(socket).await.map(|_| ()).map_err(|err| anyhow!(err));
+    #[cfg(not(unix))]
+    return Err(anyhow!("Can not connect to the socket {}, Unix domain sockets are not supported on this platform", socket));
+  }
+  let port = plugin_info.port;
+  match TcpStream::connect(("::1", port)).await {
+    Ok(_) => Ok(()),
+    Err(err) => {
+      trace!("IP6 connection failed, will try IP4 address - {err}");
+      TcpStream::connect(("127.0.0.1", port)).await
+        .map(|_| ())
+        .map_err(|err| anyhow!(err))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::process::Stdio;
+  use std::time::{Duration, Instant};
+
+  use expectest::prelude::*;
+  use maplit::hashmap;
+  use serde_json::json;
+  use sysinfo::{Pid, PidExt, ProcessExt, ProcessStatus, System, SystemExt};
+  use tokio::net::TcpListener;
+  #[cfg(unix)] use tokio::net::UnixListener;
+  use tokio::process::Command;
+
+  use crate::plugin_models::PactPluginManifest;
+
+  use super::{
+    check_plugin_version,
+    ChildPluginProcess,
+    DEFAULT_STARTUP_TIMEOUT,
+    HealthCheck,
+    ping_result,
+    RunningPluginInfo,
+    STDERR_LINES,
+    spawn,
+    startup_timeout
+  };
+
+  #[test]
+  fn from_startup_line_test() {
+    let info = RunningPluginInfo { port: 1234, socket: None, server_key: "abc".to_string(), version: None };
+    expect!(RunningPluginInfo::from_startup_line("{\"port\":1234, \"serverKey\":\"abc\"}", None).unwrap().unwrap())
+      .to(be_equal_to(info.clone()));
+    expect!(RunningPluginInfo::from_startup_line("starting up", None)).to(be_none());
+    expect!(RunningPluginInfo::from_startup_line("{\"level\":\"INFO\", \"msg\":\"starting\"}", None)).to(be_none());
+    expect!(RunningPluginInfo::from_startup_line("{\"port\":\"abc\", \"serverKey\":\"abc\"}", None).unwrap()).to(be_err());
+
+    expect!(RunningPluginInfo::from_startup_line("{\"port\":1234, \"serverKey\":\"abc\"}", Some("PACT:"))).to(be_none());
+    expect!(RunningPluginInfo::from_startup_line("PACT: {\"port\":1234, \"serverKey\":\"abc\"}", Some("PACT:")).unwrap().unwrap())
+      .to(be_equal_to(info));
+    expect!(RunningPluginInfo::from_startup_line("PACT: {\"port\":1234}", Some("PACT:")).unwrap()).to(be_err());
+    expect!(RunningPluginInfo::from_startup_line("PACT: {\"serverKey\":\"abc\"}", Some("PACT:")).unwrap()).to(be_err());
+
+    let info = RunningPluginInfo { port: 0, socket: Some("/tmp/plugin.sock".to_string()), server_key: "abc".to_string(), version: None };
+    expect!(RunningPluginInfo::from_startup_line("{\"socket\":\"/tmp/plugin.sock\", \"serverKey\":\"abc\"}", None).unwrap().unwrap())
+      .to(be_equal_to(info.clone()));
+    expect!(info.address()).to(be_equal_to("socket /tmp/plugin.sock"));
+
+    expect!(RunningPluginInfo::from_startup_line("{\"port\":1234, \"serverKey\":\"abc\", \"version\":\"1.2.3\"}", None).unwrap().unwrap())
+      .to(be_equal_to(RunningPluginInfo { port: 1234, socket: None, server_key: "abc".to_string(), version: Some("1.2.3".to_string()) }));
+  }
+
+  #[test]
+  fn check_plugin_version_test() {
+    let manifest = PactPluginManifest { name: "test".to_string(), version: "1.2.3".to_string(), .. PactPluginManifest::default() };
+    let info = |version: Option<&str>| RunningPluginInfo {
+      port: 1234,
+      socket: None,
+      server_key: "abc".to_string(),
+      version: version.map(|version| version.to_string())
+    };
+    expect!(check_plugin_version(&manifest, &info(None))).to(be_ok());
+    expect!(check_plugin_version(&manifest, &info(Some("1.2.3")))).to(be_ok());
+    expect!(check_plugin_version(&manifest, &info(Some("1.2.3+build.5")))).to(be_ok());
+    expect!(check_plugin_version(&manifest, &info(Some("1.3.0")))).to(be_ok());
+    expect!(check_plugin_version(&manifest, &info(Some("not a version")))).to(be_ok());
+    expect!(check_plugin_version(&manifest, &info(Some("1.2.2")))).to(be_err());
+    expect!(check_plugin_version(&manifest, &info(Some("1.2.3-beta.1")))).to(be_err());
+  }
+
+  #[test]
+  fn startup_timeout_test() {
+    let manifest = |value| PactPluginManifest {
+      plugin_config: hashmap! { "startupTimeout".to_string() => value },
+      .. PactPluginManifest::default()
+    };
+    expect!(startup_timeout(&PactPluginManifest::default())).to(be_equal_to(DEFAULT_STARTUP_TIMEOUT));
+    expect!(startup_timeout(&manifest(json!(2500)))).to(be_equal_to(Duration::from_millis(2500)));
+    expect!(startup_timeout(&manifest(json!("750")))).to(be_equal_to(Duration::from_millis(750)));
+    expect!(startup_timeout(&manifest(json!(0)))).to(be_equal_to(DEFAULT_STARTUP_TIMEOUT));
+    expect!(startup_timeout(&manifest(json!("soon")))).to(be_equal_to(DEFAULT_STARTUP_TIMEOUT));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn startup_fails_after_the_configured_timeout() {
+    let child = Command::new("sh")
+      .args(["-c", "sleep 5; echo '{\"port\":1234, \"serverKey\":\"abc\"}'"])
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let manifest = PactPluginManifest {
+      plugin_config: hashmap! { "startupTimeout".to_string() => json!(300) },
+      .. PactPluginManifest::default()
+    };
+    let result = ChildPluginProcess::new(child, &manifest).await;
+    expect!(result.unwrap_err().to_string().contains("did not output the correct startup message in 300ms")).to(be_true());
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn startup_fails_when_the_process_exits() {
+    let child = Command::new("sh")
+      .args(["-c", "echo 'starting'; echo 'error while loading shared libraries' >&2; exit 3"])
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let manifest = PactPluginManifest {
+      plugin_config: hashmap! { "startupTimeout".to_string() => json!(30000) },
+      .. PactPluginManifest::default()
+    };
+    let result = tokio::time::timeout(Duration::from_secs(10), ChildPluginProcess::new(child, &manifest)).await;
+    expect!(result.unwrap().unwrap_err().to_string()).to(be_equal_to(
+      "Plugin process exited with code 3 before printing the startup message, standard error:\n\
+      error while loading shared libraries".to_string()));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn reader_tasks_finish_when_the_process_exits() {
+    let child = Command::new("sh")
+      .args(["-c", "echo '{\"port\":1234, \"serverKey\":\"abc\"}'; echo 'error' >&2"])
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let process = ChildPluginProcess::new(child, &PactPluginManifest::default()).await.unwrap();
+    expect!(process.port()).to(be_equal_to(1234));
+
+    let result = tokio::time::timeout(Duration::from_secs(5), process.wait_for_reader_tasks()).await;
+    expect!(result).to(be_ok());
+    expect!(process.recent_stderr()).to(be_equal_to(vec!["error".to_string()]));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn recent_stderr_keeps_the_last_lines() {
+    let child = Command::new("sh")
+      .args(["-c", "echo '{\"port\":1234, \"serverKey\":\"abc\"}'; i=1; while [ $i -le 150 ]; do echo \"line $i\" >&2; i=$((i+1)); done"])
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let process = ChildPluginProcess::new(child, &PactPluginManifest::default()).await.unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(5), process.wait_for_reader_tasks()).await;
+    expect!(result).to(be_ok());
+    let stderr = process.recent_stderr();
+    expect!(stderr.len()).to(be_equal_to(STDERR_LINES));
+    expect!(stderr.first().cloned()).to(be_some().value("line 51".to_string()));
+    expect!(stderr.last().cloned()).to(be_some().value("line 150".to_string()));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn custom_handshake_parser() {
+    let child = Command::new("sh")
+      .args(["-c", "echo 'starting'; echo 'LISTENING port=4321 key=xyz'"])
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let parser = |line: &str| {
+      let mut attributes = line.strip_prefix("LISTENING ")?.split(' ')
+        .filter_map(|attribute| attribute.split_once('='));
+      let port = attributes.next()?.1.parse().ok()?;
+      let server_key = attributes.next()?.1;
+      Some(RunningPluginInfo::new(port, server_key))
+    };
+    let process = ChildPluginProcess::new_with_handshake_parser(child, &PactPluginManifest::default(), parser)
+      .await.unwrap();
+    expect!(process.plugin_info.clone()).to(be_equal_to(RunningPluginInfo { port: 4321, socket: None, server_key: "xyz".to_string(), version: None }));
+    expect!(process.port()).to(be_equal_to(4321));
+    expect!(process.server_key()).to(be_equal_to("xyz"));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn connect_retries_until_the_server_accepts() {
+    // Find a free port, and only start listening on it after the process has started
+    let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+    let child = Command::new("sh")
+      .args(["-c", format!("echo '{{\"port\":{}, \"serverKey\":\"abc\"}}'; sleep 5", port).as_str()])
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let process = ChildPluginProcess::new(child, &PactPluginManifest::default()).await.unwrap();
+    let server = tokio::spawn(async move {
+      tokio::time::sleep(Duration::from_millis(300)).await;
+      let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+      let _ = listener.accept().await;
+    });
+
+    let result = tokio::time::timeout(Duration::from_secs(10), process.connect()).await;
+    expect!(result.map(|client| client.is_ok())).to(be_ok().value(true));
+    server.abort();
+    process.kill();
+    expect!(process.connect().await).to(be_err());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn wait_until_ready_waits_for_the_port_to_accept_connections() {
+    // Find a free port, and only start listening on it after the process has started
+    let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+    let process = start_long_running_process(port).await;
+    expect!(process.wait_until_ready(Duration::from_millis(300)).await.unwrap_err().to_string()
+      .starts_with(format!("The plugin on port {} was not accepting connections after 300ms", port).as_str())).to(be_true());
+
+    let server = tokio::spawn(async move {
+      let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+      loop {
+        let _ = listener.accept().await;
+      }
+    });
+    expect!(process.wait_until_ready(Duration::from_secs(5)).await).to(be_ok());
+    server.abort();
+
+    process.kill();
+    expect!(process.wait_until_ready(Duration::from_secs(5)).await.unwrap_err().to_string()
+      .contains("the process is not running")).to(be_true());
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn connect_to_a_plugin_on_a_socket() {
+    let socket = std::env::temp_dir().join(format!("pact-driver-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&socket);
+    let path = socket.to_string_lossy().to_string();
+    let child = long_running_command().spawn().unwrap();
+    let parser = move |line: &str| (line.trim() == "STARTED")
+      .then(|| RunningPluginInfo { port: 0, socket: Some(path.clone()), server_key: "abc".to_string(), version: None });
+    let process = ChildPluginProcess::new_with_handshake_parser(child, &PactPluginManifest::default(), parser).await.unwrap();
+    expect!(process.wait_until_ready(Duration::from_millis(300)).await.unwrap_err().to_string()
+      .starts_with(format!("The plugin on socket {} was not accepting connections", socket.display()).as_str())).to(be_true());
+
+    let listener = UnixListener::bind(&socket).unwrap();
+    let server = tokio::spawn(async move {
+      loop {
+        let _ = listener.accept().await;
+      }
+    });
+    expect!(process.wait_until_ready(Duration::from_secs(5)).await).to(be_ok());
+    expect!(process.connect().await.map(|_| ())).to(be_ok());
+    server.abort();
+    process.kill();
+    std::fs::remove_file(&socket).unwrap();
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn shutdown_stops_the_process_with_sigterm() {
+    let child = Command::new("sh")
+      .args(["-c", "echo '{\"port\":1234, \"serverKey\":\"abc\"}'; exec sleep 30"])
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let process = ChildPluginProcess::new(child, &PactPluginManifest::default()).await.unwrap();
+    expect!(process.is_running()).to(be_true());
+    let start = Instant::now();
+    process.shutdown(Duration::from_secs(10)).await;
+    expect!(process.is_running()).to(be_false());
+    expect!(start.elapsed() < Duration::from_secs(5)).to(be_true());
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn shutdown_kills_the_process_if_it_ignores_sigterm() {
+    let child = Command::new("sh")
+      .args(["-c", "trap '' TERM; echo '{\"port\":1234, \"serverKey\":\"abc\"}'; exec sleep 30"])
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    let process = ChildPluginProcess::new(child, &PactPluginManifest::default()).await.unwrap();
+    process.shutdown(Duration::from_millis(300)).await;
+    expect!(process.is_running()).to(be_false());
+  }
+
+  // Command for a process that prints `STARTED` and then keeps running
+  fn long_running_command() -> Command {
+    #[cfg(windows)]
+    let mut command = {
+      let mut command = Command::new("powershell");
+      command.args(["-NoProfile", "-Command", "Write-Output STARTED; Start-Sleep -Seconds 30"]);
+      command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+      let mut command = Command::new("sh");
+      command.args(["-c", "echo STARTED; exec sleep 30"]);
+      command
+    };
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    command
+  }
+
+  async fn start_long_running_process(port: u16) -> ChildPluginProcess {
+    let child = long_running_command().spawn().unwrap();
+    let parser = move |line: &str| (line.trim() == "STARTED")
+      .then(|| RunningPluginInfo { port, socket: None, server_key: "abc".to_string(), version: None });
+    ChildPluginProcess::new_with_handshake_parser(child, &PactPluginManifest::default(), parser).await.unwrap()
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn kill_stops_the_process() {
+    let process = start_long_running_process(1234).await;
+    expect!(process.is_running()).to(be_true());
+    process.kill();
+    expect!(process.wait_for_exit(Duration::from_secs(5)).await).to(be_true());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn process_is_only_killed_once_all_the_clones_are_dropped() {
+    let running = |pid: usize| {
+      let mut s = System::new();
+      s.refresh_process(Pid::from_u32(pid as u32));
+      s.process(Pid::from_u32(pid as u32)).map(|process| process.status() != ProcessStatus::Zombie).unwrap_or(false)
+    };
+    let process = start_long_running_process(1234).await;
+    let pid = process.child_pid;
+    let clone = process.clone();
+    drop(process);
+    expect!(clone.is_running()).to(be_true());
+
+    drop(clone);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while running(pid) && Instant::now() < deadline {
+      tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    expect!(running(pid)).to(be_false());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn shutdown_stops_the_process() {
+    let process = start_long_running_process(1234).await;
+    process.shutdown(Duration::from_secs(5)).await;
+    expect!(process.is_running()).to(be_false());
+  }
+
+  #[test]
+  fn health_check_from_manifest() {
+    expect!(HealthCheck::from_manifest(&PactPluginManifest::default())).to(be_equal_to(HealthCheck::default()));
+    let manifest = PactPluginManifest {
+      plugin_config: hashmap! {
+        "healthCheckTimeout".to_string() => json!(250),
+        "healthCheckAttempts".to_string() => json!("5")
+      },
+      .. PactPluginManifest::default()
+    };
+    expect!(HealthCheck::from_manifest(&manifest)).to(be_equal_to(HealthCheck { timeout: Duration::from_millis(250), attempts: 5 }));
+    let manifest = PactPluginManifest {
+      plugin_config: hashmap! { "healthCheckAttempts".to_string() => json!(0) },
+      .. PactPluginManifest::default()
+    };
+    expect!(HealthCheck::from_manifest(&manifest)).to(be_equal_to(HealthCheck::default()));
+  }
+
+  #[test]
+  fn ping_on_startup_from_manifest() {
+    expect!(HealthCheck::ping_on_startup(&PactPluginManifest::default())).to(be_false());
+    let manifest = |value: serde_json::Value| PactPluginManifest {
+      plugin_config: hashmap! { "healthCheckPing".to_string() => value },
+      .. PactPluginManifest::default()
+    };
+    expect!(HealthCheck::ping_on_startup(&manifest(json!(true)))).to(be_true());
+    expect!(HealthCheck::ping_on_startup(&manifest(json!(false)))).to(be_false());
+    expect!(HealthCheck::ping_on_startup(&manifest(json!("yes")))).to(be_false());
+    expect!(HealthCheck::default().ready_timeout()).to(be_equal_to(Duration::from_secs(15)));
+  }
+
+  #[test]
+  fn ping_result_treats_unimplemented_as_responding() {
+    expect!(ping_result(Ok(tonic::Response::new(())))).to(be_ok());
+    expect!(ping_result(Err(tonic::Status::unimplemented("not implemented")))).to(be_ok());
+    expect!(ping_result(Err(tonic::Status::unavailable("not ready")))).to(be_err());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn is_alive_checks_the_process_and_the_server() {
+    // The process is running, but nothing is listening on the port from its startup message
+    let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+    let process = start_long_running_process(port).await;
+    let health_check = HealthCheck { timeout: Duration::from_millis(200), attempts: 2 };
+    expect!(process.is_alive(None).await).to(be_true());
+    expect!(process.is_alive(Some(&health_check)).await).to(be_false());
+    expect!(process.ping(&health_check).await.unwrap_err().to_string()
+      .contains("did not respond to a ping after 2 attempts")).to(be_true());
+
+    process.kill();
+    expect!(process.is_alive(None).await).to(be_false());
+    expect!(process.ping(&health_check).await.unwrap_err().to_string()
+      .contains("the process is not running")).to(be_true());
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn spawn_sets_the_environment_and_working_directory() {
+    let dir = std::env::temp_dir().canonicalize().unwrap();
+    let manifest = PactPluginManifest {
+      name: "test".to_string(),
+      entry_point: "/bin/sh".to_string(),
+      args: Some(vec!["-c".to_string(), "echo '{\"port\":1234, \"serverKey\":\"abc\"}'; echo \"$DATA_LEVEL $(pwd)\" >&2".to_string()]),
+      .. PactPluginManifest::default()
+    };
+    let env = hashmap! { "DATA_LEVEL".to_string() => "verbose".to_string() };
+    let process = spawn(&manifest, env, Some(dir.clone())).await.unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(5), process.wait_for_reader_tasks()).await;
+    expect!(result).to(be_ok());
+    expect!(process.recent_stderr()).to(be_equal_to(vec![format!("verbose {}", dir.display())]));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn restart_starts_a_new_process() {
+    let manifest = PactPluginManifest {
+      name: "test".to_string(),
+      entry_point: "/bin/sh".to_string(),
+      args: Some(vec!["-c".to_string(), r#"echo "{\"port\":1234, \"serverKey\":\"$SERVER_KEY\"}"; exec sleep 30"#.to_string()]),
+      .. PactPluginManifest::default()
+    };
+    let env = hashmap! { "SERVER_KEY".to_string() => "abc".to_string() };
+    let mut process = spawn(&manifest, env, Some(std::env::temp_dir())).await.unwrap();
+    let pid = process.child_pid;
+    process.kill();
+    expect!(process.wait_for_exit(Duration::from_secs(5)).await).to(be_true());
+    expect!(process.is_alive(None).await).to(be_false());
+
+    process.restart().await.unwrap();
+    expect!(process.child_pid).to_not(be_equal_to(pid));
+    expect!(process.plugin_info.clone()).to(be_equal_to(RunningPluginInfo { port: 1234, socket: None, server_key: "abc".to_string(), version: None }));
+    expect!(process.is_alive(None).await).to(be_true());
+    process.kill();
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn restart_keeps_the_old_process_if_the_new_one_fails_to_start() {
+    let marker = std::env::temp_dir().join(format!("restart-marker-{}", std::process::id()));
+    let manifest = PactPluginManifest {
+      name: "test".to_string(),
+      entry_point: "/bin/sh".to_string(),
+      args: Some(vec!["-c".to_string(), r#"[ -e "$MARKER" ] && exit 1; echo '{"port":1234, "serverKey":"abc"}'; exec sleep 30"#.to_string()]),
+      .. PactPluginManifest::default()
+    };
+    let env = hashmap! { "MARKER".to_string() => marker.to_string_lossy().to_string() };
+    let mut process = spawn(&manifest, env, Some(std::env::temp_dir())).await.unwrap();
+    let pid = process.child_pid;
+    std::fs::write(&marker, "").unwrap();
+
+    let result = process.restart().await;
+    std::fs::remove_file(&marker).unwrap();
+    expect!(result).to(be_err());
+    expect!(process.child_pid).to(be_equal_to(pid));
+    expect!(process.is_alive(None).await).to(be_true());
+    process.kill();
+  }
+}
@@ -12,3 +12,6 @@ pub mod mock_server;
 pub mod verification;
 pub mod repository;
 pub mod download;
+
+/// Startup message that plugins need to print to standard output once they are running
+pub use child_process::RunningPluginInfo;
@@ -5,7 +5,6 @@ use std::fs;
 use std::fs::File;
 use std::io::{BufReader, Write};
 use std::path::PathBuf;
-use std::process::Stdio;
 use std::str::from_utf8;
 use std::str::FromStr;
 use std::sync::Mutex;
@@ -15,9 +14,7 @@ use anyhow::{anyhow, bail, Context};
 use bytes::Bytes;
 use itertools::Either;
 use lazy_static::lazy_static;
-use log::max_level;
 use maplit::hashmap;
-use os_info::Type;
 use pact_models::bodies::OptionalBody;
 use pact_models::json_utils::json_to_string;
 use pact_models::PactSpecification;
@@ -27,12 +24,10 @@ use pact_models::v4::interaction::V4Interaction;
 use reqwest::Client;
 use semver::Version;
 use serde_json::Value;
-use sysinfo::{Pid, PidExt, ProcessExt, Signal, System, SystemExt};
-use tokio::process::Command;
 use tracing::{debug, info, trace, warn};
 
 use crate::catalogue_manager::{all_entries, CatalogueEntry, register_plugin_entries, remove_plugin_entries};
-use crate::child_process::ChildPluginProcess;
+use crate::child_process::{HealthCheck, spawn};
 use crate::content::ContentMismatch;
 use crate::download::{download_json_from_github, download_plugin_executable, fetch_json_from_url};
 use crate::metrics::send_metrics;
@@ -243,52 +238,20 @@ pub async fn init_handshake(manifest: &PactPluginManifest, plugin: &mut (dyn Pac
 
 async fn start_plugin_process(manifest: &PactPluginManifest) -> anyhow::Result<PactPlugin> {
   debug!("Starting plugin with manifest {:?}", manifest);
+  let child = spawn(manifest, HashMap::new(), None).await?;
 
-  let os_info = os_info::get();
-  debug!("Detected OS: {}", os_info);
-  let mut path = if let Some(entry_point) = manifest.entry_points.get(&os_info.to_string()) {
-    PathBuf::from(entry_point)
-  } else if os_info.os_type() == Type::Windows && manifest.entry_points.contains_key("windows") {
-    PathBuf::from(manifest.entry_points.get("windows").unwrap())
+  // The plugin is checked to be accepting connections (or responding to requests if the manifest
+  // asks for a ping), so that a plugin that never accepts connections fails straight away
+  let health_check = HealthCheck::from_manifest(manifest);
+  let result = if HealthCheck::ping_on_startup(manifest) {
+    child.ping(&health_check).await
   } else {
-    PathBuf::from(&manifest.entry_point)
+    child.wait_until_ready(health_check.ready_timeout()).await
   };
-
-  if !path.is_absolute() || !path.exists() {
-    path = PathBuf::from(manifest.plugin_dir.clone()).join(path);
-  }
-  debug!("Starting plugin using {:?}", &path);
-
-  let log_level = max_level();
-  let mut child_command = Command::new(path.clone());
-  let mut child_command = child_command
-    .env("LOG_LEVEL", log_level.to_string())
-    .env("RUST_LOG", log_level.to_string())
-    .current_dir(manifest.plugin_dir.clone());
-
-  if let Some(args) = &manifest.args {
-    child_command = child_command.args(args);
-  }
-
-  let child = child_command
-    .stdout(Stdio::piped())
-    .stderr(Stdio::piped())
-    .spawn()
-    .map_err(|err| anyhow!("Was not able to start plugin process for '{}' - {}",
-      path.to_string_lossy(), err))?;
-  let child_pid = child.id().unwrap_or_default();
-  debug!("Plugin {} started with PID {}", manifest.name, child_pid);
-
-  match ChildPluginProcess::new(child, manifest).await {
-    Ok(child) => Ok(PactPlugin::new(manifest, child)),
+  match result {
+    Ok(()) => Ok(PactPlugin::new(manifest, child)),
     Err(err) => {
-      let mut s = System::new();
-      s.refresh_processes();
-      if let Some(process) = s.process(Pid::from_u32(child_pid)) {
-        process.kill_with(Signal::Term);
-      } else {
-        warn!("Child process with PID {} was not found", child_pid);
-      }
+      child.kill();
       Err(err)
     }
   }
@@ -2,10 +2,9 @@
 
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError, RwLock};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use anyhow::anyhow;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -14,9 +13,9 @@ use tonic::{Request, Status};
 use tonic::codegen::InterceptedService;
 use tonic::service::Interceptor;
 use tonic::transport::Channel;
-use tracing::{debug, trace};
+use tracing::trace;
 
-use crate::child_process::ChildPluginProcess;
+use crate::child_process::{ChildPluginProcess, connect_channel};
 use crate::proto::*;
 use crate::proto::pact_plugin_client::PactPluginClient;
 
@@ -172,8 +171,9 @@ pub struct PactPlugin {
   /// Manifest for this plugin
   pub manifest: PactPluginManifest,
 
-  /// Running child process
-  pub child: Arc<ChildPluginProcess>,
+  /// Running child process. This is shared between all the clones of the plugin, so that when the
+  /// plugin is restarted they all use the new process.
+  child: Arc<RwLock<Arc<ChildPluginProcess>>>,
 
   /// Count of access to the plugin. If this is ever zero, the plugin process will be shutdown
   access_count: Arc<AtomicUsize>
@@ -251,19 +251,35 @@ impl PactPlugin {
   pub fn new(manifest: &PactPluginManifest, child: ChildPluginProcess) -> Self {
     PactPlugin {
       manifest: manifest.clone(),
-      child: Arc::new(child),
+      child: Arc::new(RwLock::new(Arc::new(child))),
       access_count: Arc::new(AtomicUsize::new(1))
     }
   }
 
+  /// Running child process
+  pub fn child(&self) -> Arc<ChildPluginProcess> {
+    self.child.read().unwrap_or_else(PoisonError::into_inner).clone()
+  }
+
   /// Port the plugin is running on
   pub fn port(&self) -> u16 {
-    self.child.port()
+    self.child().port()
   }
 
   /// Kill the running plugin process
   pub fn kill(&self) {
-    self.child.kill();
+    self.child().kill();
+  }
+
+  /// Start the plugin process again, i.e. after it has crashed. The new process replaces the old one
+  /// for all the clones of this plugin, and the old one is killed if it is still running. If the new
+  /// process fails to start, the old one is kept. See `ChildPluginProcess::restart`.
+  pub async fn restart(&self) -> anyhow::Result<()> {
+    let old = self.child();
+    let process = old.respawn().await?;
+    old.kill();
+    *self.child.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(process);
+    Ok(())
   }
 
   /// Update the access of the plugin
@@ -293,34 +309,24 @@ impl PactPlugin {
     count
   }
 
-  async fn connect_channel(&self) -> anyhow::Result<Channel> {
-    let port = self.child.port();
-    match Channel::from_shared(format!("http://[::1]:{}", port))?.connect().await {
-      Ok(channel) => Ok(channel),
-      Err(err) => {
-        debug!("IP6 connection failed, will try IP4 address - {err}");
-        Channel::from_shared(format!("http://127.0.0.1:{}", port))?.connect().await
-          .map_err(|err| anyhow!(err))
-      }
-    }
-  }
-
   async fn get_plugin_client(&self) -> anyhow::Result<PactPluginClient<InterceptedService<Channel, PactPluginInterceptor>>> {
-    let channel = self.connect_channel().await?;
-    let interceptor = PactPluginInterceptor::new(self.child.plugin_info.server_key.as_str())?;
+    let child = self.child();
+    let channel = connect_channel(&child.plugin_info).await?;
+    let interceptor = PactPluginInterceptor::new(child.server_key())?;
     Ok(PactPluginClient::with_interceptor(channel, interceptor))
   }
 }
 
 /// Interceptor to inject the server key as an authorisation header
 #[derive(Clone, Debug)]
-struct PactPluginInterceptor {
+pub struct PactPluginInterceptor {
   /// Server key to inject
   server_key: MetadataValue<Ascii>
 }
 
 impl PactPluginInterceptor {
-  fn new(server_key: &str) -> anyhow::Result<Self> {
+  /// Create an interceptor for the server key. This will fail if the key is not a valid header value.
+  pub fn new(server_key: &str) -> anyhow::Result<Self> {
     let token = MetadataValue::try_from(server_key)?;
     Ok(PactPluginInterceptor {
       server_key: token
@@ -343,3 +349,37 @@ pub struct PluginInteractionConfig {
   /// Interaction plugin config
   pub interaction_configuration: HashMap<String, Value>
 }
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use maplit::hashmap;
+
+  use crate::child_process::spawn;
+
+  use super::{PactPlugin, PactPluginManifest};
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn restart_replaces_the_process_for_all_clones_of_the_plugin() {
+    let manifest = PactPluginManifest {
+      name: "test".to_string(),
+      entry_point: "/bin/sh".to_string(),
+      args: Some(vec!["-c".to_string(), r#"echo "{\"port\":1234, \"serverKey\":\"$SERVER_KEY\"}"; exec sleep 30"#.to_string()]),
+      .. PactPluginManifest::default()
+    };
+    let env = hashmap! { "SERVER_KEY".to_string() => "abc".to_string() };
+    let process = spawn(&manifest, env, Some(std::env::temp_dir())).await.unwrap();
+    let plugin = PactPlugin::new(&manifest, process);
+    let clone = plugin.clone();
+    let old = plugin.child();
+
+    plugin.restart().await.unwrap();
+    expect!(clone.child().child_pid).to_not(be_equal_to(old.child_pid));
+    expect!(clone.child().child_pid).to(be_equal_to(plugin.child().child_pid));
+    expect!(old.is_alive(None).await).to(be_false());
+    expect!(clone.child().is_alive(None).await).to(be_true());
+    expect!(clone.port()).to(be_equal_to(1234));
+    plugin.kill();
+  }
+}
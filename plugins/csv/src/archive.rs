@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+
+use anyhow::anyhow;
+use zip::ZipArchive;
+
+/// Maximum total size of the CSV members once they have been unpacked from an archive (256 MiB)
+pub const MAX_UNPACKED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Unpacks the CSV members (files ending in `.csv`) from a zip archive, keyed by their name within
+/// the archive. Other members are ignored. Fails if the members are larger than `MAX_UNPACKED_SIZE`
+/// once unpacked.
+pub fn read_csv_members(data: &[u8]) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+  read_csv_members_with_limit(data, MAX_UNPACKED_SIZE)
+}
+
+fn read_csv_members_with_limit(data: &[u8], limit: u64) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+  let mut archive = ZipArchive::new(Cursor::new(data))?;
+  let mut members = BTreeMap::new();
+  let mut total = 0;
+
+  for index in 0..archive.len() {
+    let mut file = archive.by_index(index)?;
+    if !file.is_file() || !file.name().to_lowercase().ends_with(".csv") {
+      continue;
+    }
+    let name = file.name().to_string();
+    // The sizes in the archive can not be trusted, so the limit is applied while unpacking
+    let mut content = vec![];
+    (&mut file).take(limit - total + 1).read_to_end(&mut content)?;
+    total += content.len() as u64;
+    if total > limit {
+      return Err(anyhow!("The CSV members of the archive are larger than the maximum of {} bytes", limit));
+    }
+    members.insert(name, content);
+  }
+
+  Ok(members)
+}
+
+#[cfg(test)]
+pub(crate) fn zip_archive(members: &[(&str, &str)]) -> Vec<u8> {
+  use std::io::Write;
+
+  use zip::write::FileOptions;
+  use zip::ZipWriter;
+
+  let mut writer = ZipWriter::new(Cursor::new(vec![]));
+  for (name, content) in members {
+    writer.start_file(*name, FileOptions::default()).unwrap();
+    writer.write_all(content.as_bytes()).unwrap();
+  }
+  writer.finish().unwrap().into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+
+  use super::{read_csv_members, read_csv_members_with_limit, zip_archive};
+
+  #[test]
+  fn read_csv_members_test() {
+    let archive = zip_archive(&[("orders.csv", "id\n1\n"), ("README.txt", "not csv"), ("data/ITEMS.CSV", "id\n2\n")]);
+    let members = read_csv_members(&archive).unwrap();
+    expect!(members.keys().cloned().collect::<Vec<_>>())
+      .to(be_equal_to(vec!["data/ITEMS.CSV".to_string(), "orders.csv".to_string()]));
+    expect!(members.get("orders.csv").cloned()).to(be_some().value(b"id\n1\n".to_vec()));
+
+    expect!(read_csv_members_with_limit(&archive, 10)).to(be_ok());
+    expect!(read_csv_members_with_limit(&archive, 9)).to(be_err());
+    expect!(read_csv_members(b"id\n1\n")).to(be_err());
+  }
+}
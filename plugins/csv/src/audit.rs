@@ -0,0 +1,199 @@
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use serde_json::{json, Value};
+
+use crate::options::CsvOptions;
+use crate::proto;
+
+/// Environment variable with the path to write the result of each comparison to, as JSON. The
+/// mismatch log is only written if it is set, and the `mismatchLog` option can only change the name
+/// of the file within its directory.
+pub const MISMATCH_LOG_ENV: &str = "PACT_CSV_MISMATCH_LOG";
+
+/// Writes the compare contents response to the mismatch log file, if there is one. This is a record
+/// of the comparison, so a failure to write it is logged and does not fail the comparison.
+pub fn write_mismatch_log(options: &CsvOptions, response: &proto::CompareContentsResponse) {
+  write_mismatch_log_with(env::var(MISMATCH_LOG_ENV).ok(), options, response)
+}
+
+fn write_mismatch_log_with(env_template: Option<String>, options: &CsvOptions, response: &proto::CompareContentsResponse) {
+  let template = match env_template {
+    Some(template) if !template.is_empty() => template,
+    _ => {
+      if let Some(file_name) = &options.mismatch_log {
+        warn!("Ignoring the mismatchLog option '{}', as the {} environment variable is not set", file_name, MISMATCH_LOG_ENV);
+      }
+      return
+    }
+  };
+  let template = match mismatch_log_template(&template, options.mismatch_log.as_deref()) {
+    Ok(template) => template,
+    Err(err) => {
+      warn!("Not writing the comparison result: {}", err);
+      return
+    }
+  };
+  let path = mismatch_log_path(&template, Utc::now(), std::process::id());
+  match write_response(&path, response) {
+    Ok(()) => debug!("Wrote the comparison result to {}", path.display()),
+    Err(err) => warn!("Failed to write the comparison result to {}: {:#}", path.display(), err)
+  }
+}
+
+// The options come from the pact file, so the directory of the mismatch log is always the one from
+// the environment variable. The file name from the `mismatchLog` option must be relative to it, and
+// can not refer to files outside of it.
+fn mismatch_log_template(env_template: &str, file_name: Option<&str>) -> anyhow::Result<String> {
+  match file_name {
+    Some(file_name) => {
+      let relative = Path::new(file_name);
+      if file_name.is_empty() || !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(anyhow!("'{}' is not a valid mismatch log, it must be a relative path within the directory of {}",
+          file_name, MISMATCH_LOG_ENV));
+      }
+      let dir = Path::new(env_template).parent().unwrap_or_else(|| Path::new(""));
+      Ok(dir.join(relative).to_string_lossy().to_string())
+    }
+    None => Ok(env_template.to_string())
+  }
+}
+
+/// Path of the mismatch log file, with `{timestamp}` replaced with the time of the comparison (UTC,
+/// to the microsecond) and `{pid}` with the process ID of the plugin
+fn mismatch_log_path(template: &str, now: DateTime<Utc>, pid: u32) -> PathBuf {
+  PathBuf::from(template
+    .replace("{timestamp}", &now.format("%Y%m%dT%H%M%S%.6fZ").to_string())
+    .replace("{pid}", &pid.to_string()))
+}
+
+fn write_response(path: &Path, response: &proto::CompareContentsResponse) -> anyhow::Result<()> {
+  if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create the directory {}", dir.display()))?;
+  }
+  let json = serde_json::to_string_pretty(&response_to_json(response))?;
+  fs::write(path, json)?;
+  Ok(())
+}
+
+/// Converts the compare contents response to JSON. The expected and actual values are bytes in the
+/// response, and are written as text.
+fn response_to_json(response: &proto::CompareContentsResponse) -> Value {
+  let bytes_to_json = |bytes: &Option<Vec<u8>>| match bytes {
+    Some(bytes) => json!(String::from_utf8_lossy(bytes)),
+    None => Value::Null
+  };
+  let results = response.results.iter()
+    .map(|(key, mismatches)| {
+      let mismatches = mismatches.mismatches.iter()
+        .map(|mismatch| json!({
+          "expected": bytes_to_json(&mismatch.expected),
+          "actual": bytes_to_json(&mismatch.actual),
+          "mismatch": mismatch.mismatch,
+          "path": mismatch.path,
+          "diff": mismatch.diff
+        }))
+        .collect::<Vec<_>>();
+      (key.clone(), Value::Array(mismatches))
+    })
+    .collect::<serde_json::Map<_, _>>();
+  json!({
+    "error": response.error,
+    "typeMismatch": response.type_mismatch.as_ref().map(|mismatch| json!({
+      "expected": mismatch.expected,
+      "actual": mismatch.actual
+    })),
+    "results": results
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use chrono::{TimeZone, Utc};
+  use expectest::prelude::*;
+  use maplit::hashmap;
+  use serde_json::json;
+
+  use crate::options::CsvOptions;
+  use crate::proto;
+
+  use super::{mismatch_log_path, mismatch_log_template, response_to_json, write_mismatch_log_with};
+
+  fn response() -> proto::CompareContentsResponse {
+    proto::CompareContentsResponse {
+      error: String::default(),
+      type_mismatch: None,
+      results: hashmap! {
+        String::default() => proto::ContentMismatches {
+          mismatches: vec![proto::ContentMismatch {
+            expected: Some("1".as_bytes().to_vec()),
+            actual: Some("2".as_bytes().to_vec()),
+            mismatch: "Expected column 0 value to equal '1', but got '2'".to_string(),
+            path: "row:    2, column: 0".to_string(),
+            diff: "- 1\n+ 2".to_string()
+          }]
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn mismatch_log_path_test() {
+    let now = Utc.with_ymd_and_hms(2023, 5, 6, 7, 8, 9).unwrap();
+    expect!(mismatch_log_path("logs/csv-{timestamp}-{pid}.json", now, 42).to_str().unwrap().to_string())
+      .to(be_equal_to("logs/csv-20230506T070809.000000Z-42.json".to_string()));
+    expect!(mismatch_log_path("result.json", now, 42).to_str().unwrap().to_string())
+      .to(be_equal_to("result.json".to_string()));
+  }
+
+  #[test]
+  fn mismatch_log_template_test() {
+    expect!(mismatch_log_template("logs/csv-{pid}.json", None).unwrap()).to(be_equal_to("logs/csv-{pid}.json"));
+    expect!(mismatch_log_template("/var/log/csv.json", Some("orders-{timestamp}.json")).unwrap())
+      .to(be_equal_to("/var/log/orders-{timestamp}.json"));
+    expect!(mismatch_log_template("csv.json", Some("orders/result.json")).unwrap()).to(be_equal_to("orders/result.json"));
+    expect!(mismatch_log_template("logs/csv.json", Some("../result.json"))).to(be_err());
+    expect!(mismatch_log_template("logs/csv.json", Some("orders/../../result.json"))).to(be_err());
+    expect!(mismatch_log_template("logs/csv.json", Some("/tmp/result.json"))).to(be_err());
+    expect!(mismatch_log_template("logs/csv.json", Some(""))).to(be_err());
+  }
+
+  #[test]
+  fn response_to_json_test() {
+    expect!(response_to_json(&response())).to(be_equal_to(json!({
+      "error": "",
+      "typeMismatch": null,
+      "results": {
+        "": [{
+          "expected": "1",
+          "actual": "2",
+          "mismatch": "Expected column 0 value to equal '1', but got '2'",
+          "path": "row:    2, column: 0",
+          "diff": "- 1\n+ 2"
+        }]
+      }
+    })));
+  }
+
+  #[test]
+  fn write_mismatch_log_creates_the_file() {
+    let dir = std::env::temp_dir().join(format!("pact-csv-mismatch-log-{}", std::process::id()));
+    let options = CsvOptions { mismatch_log: Some("nested/result-{pid}.json".to_string()), .. CsvOptions::default() };
+
+    // Without the environment variable, the option does not write anything
+    write_mismatch_log_with(None, &options, &response());
+    expect!(dir.exists()).to(be_false());
+
+    write_mismatch_log_with(Some(dir.join("csv.json").to_string_lossy().to_string()), &options, &response());
+    let written = dir.join("nested").join(format!("result-{}.json", std::process::id()));
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&written).unwrap()).unwrap();
+    expect!(json["results"][""][0]["actual"].clone()).to(be_equal_to(json!("2")));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,81 @@
+use std::env;
+
+use log::warn;
+use tonic::{Request, Status};
+use tonic::service::Interceptor;
+
+/// Environment variable to disable the server key check (set it to `false`)
+pub const REQUIRE_SERVER_KEY_ENV: &str = "PACT_CSV_REQUIRE_SERVER_KEY";
+
+/// Metadata keys the server key can be sent with. The plugin drivers send it as the
+/// `authorization` metadata.
+const SERVER_KEY_METADATA: [&str; 2] = ["server-key", "authorization"];
+
+/// Interceptor that rejects requests that do not have the server key the plugin printed in its
+/// startup message, so that only the driver that started the plugin can use it.
+#[derive(Debug, Clone)]
+pub struct ServerKeyInterceptor {
+  server_key: Option<String>
+}
+
+impl ServerKeyInterceptor {
+  /// Create an interceptor that requires the server key, unless the check has been disabled with
+  /// the `PACT_CSV_REQUIRE_SERVER_KEY` environment variable
+  pub fn new(server_key: &str) -> Self {
+    let required = env::var(REQUIRE_SERVER_KEY_ENV)
+      .map(|value| value.to_lowercase() != "false")
+      .unwrap_or(true);
+    if required {
+      ServerKeyInterceptor { server_key: Some(server_key.to_string()) }
+    } else {
+      warn!("{} is set to false, requests will not be checked for the server key", REQUIRE_SERVER_KEY_ENV);
+      ServerKeyInterceptor { server_key: None }
+    }
+  }
+}
+
+impl Interceptor for ServerKeyInterceptor {
+  fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+    match &self.server_key {
+      Some(server_key) => {
+        let metadata = request.metadata();
+        let key = SERVER_KEY_METADATA.iter()
+          .find_map(|key| metadata.get(*key))
+          .and_then(|value| value.to_str().ok());
+        match key {
+          Some(key) if key == server_key => Ok(request),
+          Some(_) => Err(Status::unauthenticated("The server key for the request is not valid")),
+          None => Err(Status::unauthenticated("The request does not have a server key"))
+        }
+      }
+      None => Ok(request)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use tonic::{Code, Request};
+  use tonic::service::Interceptor;
+
+  use super::ServerKeyInterceptor;
+
+  fn request_with(key: &'static str, value: &str) -> Request<()> {
+    let mut request = Request::new(());
+    request.metadata_mut().insert(key, value.parse().unwrap());
+    request
+  }
+
+  #[test]
+  fn server_key_interceptor_test() {
+    let mut interceptor = ServerKeyInterceptor { server_key: Some("abc".to_string()) };
+    expect!(interceptor.call(request_with("server-key", "abc"))).to(be_ok());
+    expect!(interceptor.call(request_with("authorization", "abc"))).to(be_ok());
+    expect!(interceptor.call(request_with("server-key", "xyz")).unwrap_err().code()).to(be_equal_to(Code::Unauthenticated));
+    expect!(interceptor.call(Request::new(())).unwrap_err().code()).to(be_equal_to(Code::Unauthenticated));
+
+    let mut interceptor = ServerKeyInterceptor { server_key: None };
+    expect!(interceptor.call(Request::new(()))).to(be_ok());
+  }
+}
@@ -0,0 +1,93 @@
+use pact_models::matchingrules::MatchingRule;
+
+use crate::matchers::CsvRule;
+use crate::proto;
+
+/// Category of a mismatch, so that tooling can filter or group the mismatches from a comparison
+/// (i.e. treat format mismatches as warnings and value mismatches as failures)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MismatchCategory {
+  /// The shape of the contents is different, i.e. missing or unexpected columns or rows
+  Structural,
+  /// A value does not match the expected value, or a rule that checks the value
+  Value,
+  /// A value or the contents are not in the expected format, i.e. a regex or date format
+  Format,
+  /// The values of a column do not match when taken together, i.e. a multiset column
+  Aggregate,
+  /// A value does not match a rule that applies because of the value of another column
+  CrossField
+}
+
+impl MismatchCategory {
+  /// Name of the category, as used in the prefix of the mismatch description
+  pub fn name(&self) -> &'static str {
+    match self {
+      MismatchCategory::Structural => "structural",
+      MismatchCategory::Value => "value",
+      MismatchCategory::Format => "format",
+      MismatchCategory::Aggregate => "aggregate",
+      MismatchCategory::CrossField => "crossField"
+    }
+  }
+
+  /// Category of the mismatches from a Pact matching rule
+  pub fn for_rule(rule: &MatchingRule) -> MismatchCategory {
+    match rule {
+      MatchingRule::Regex(_) | MatchingRule::Type | MatchingRule::Number | MatchingRule::Integer
+        | MatchingRule::Decimal | MatchingRule::Date(_) | MatchingRule::Time(_) | MatchingRule::Timestamp(_)
+        | MatchingRule::Boolean | MatchingRule::Semver | MatchingRule::ContentType(_) => MismatchCategory::Format,
+      _ => MismatchCategory::Value
+    }
+  }
+
+  /// Category of the mismatches from a matching rule provided by this plugin
+  pub fn for_csv_rule(rule: &CsvRule) -> MismatchCategory {
+    match rule {
+      CsvRule::DecimalPrecision { .. } | CsvRule::DecimalPlaces { .. } | CsvRule::List { .. } | CsvRule::Luhn
+        | CsvRule::Iban => MismatchCategory::Format,
+      CsvRule::DateTimeTolerance { .. } | CsvRule::Rounded { .. } | CsvRule::OneOf { .. } | CsvRule::NotEmpty
+        | CsvRule::Empty => MismatchCategory::Value
+    }
+  }
+
+  /// Prefixes the mismatch description with the category, i.e. `[value] Expected ...`
+  pub fn tag(self, mismatch: proto::ContentMismatch) -> proto::ContentMismatch {
+    proto::ContentMismatch {
+      mismatch: format!("[{}] {}", self.name(), mismatch.mismatch),
+      .. mismatch
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use pact_models::matchingrules::MatchingRule;
+
+  use crate::matchers::CsvRule;
+  use crate::proto;
+
+  use super::MismatchCategory;
+
+  #[test]
+  fn tag_test() {
+    let mismatch = proto::ContentMismatch {
+      mismatch: "Expected 'a', but got 'b'".to_string(),
+      path: "row:    2, column: 1".to_string(),
+      .. proto::ContentMismatch::default()
+    };
+    let tagged = MismatchCategory::CrossField.tag(mismatch.clone());
+    expect!(tagged.mismatch).to(be_equal_to("[crossField] Expected 'a', but got 'b'"));
+    expect!(tagged.path).to(be_equal_to(mismatch.path));
+  }
+
+  #[test]
+  fn rule_category_test() {
+    expect!(MismatchCategory::for_rule(&MatchingRule::Regex("\\d+".to_string()))).to(be_equal_to(MismatchCategory::Format));
+    expect!(MismatchCategory::for_rule(&MatchingRule::Equality)).to(be_equal_to(MismatchCategory::Value));
+    expect!(MismatchCategory::for_csv_rule(&CsvRule::Rounded { scale: 2 })).to(be_equal_to(MismatchCategory::Value));
+    expect!(MismatchCategory::for_csv_rule(&CsvRule::DecimalPrecision { precision: None, scale: Some(2) }))
+      .to(be_equal_to(MismatchCategory::Format));
+  }
+}
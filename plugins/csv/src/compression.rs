@@ -0,0 +1,69 @@
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+use anyhow::anyhow;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use log::debug;
+
+/// Maximum size of gzip compressed contents once they have been decompressed (256 MiB)
+pub const MAX_DECOMPRESSED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Magic bytes at the start of gzip compressed data
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// If the data is gzip compressed. This is detected from the magic bytes, as the second byte is not
+/// valid at the start of UTF-8 text.
+pub fn is_gzip(data: &[u8]) -> bool {
+  data.starts_with(&GZIP_MAGIC)
+}
+
+/// Decompresses the data if it is gzip compressed, otherwise returns it as is. Fails if it is larger
+/// than `MAX_DECOMPRESSED_SIZE` once decompressed.
+pub fn decompress(data: &[u8]) -> anyhow::Result<Cow<'_, [u8]>> {
+  decompress_with_limit(data, MAX_DECOMPRESSED_SIZE)
+}
+
+fn decompress_with_limit(data: &[u8], limit: u64) -> anyhow::Result<Cow<'_, [u8]>> {
+  if !is_gzip(data) {
+    return Ok(Cow::Borrowed(data));
+  }
+
+  // The size in the gzip trailer can not be trusted, so the limit is applied while decompressing
+  let mut content = vec![];
+  MultiGzDecoder::new(data).take(limit + 1).read_to_end(&mut content)
+    .map_err(|err| anyhow!("Failed to decompress the gzip contents: {}", err))?;
+  if content.len() as u64 > limit {
+    return Err(anyhow!("The gzip contents are larger than the maximum of {} bytes once decompressed", limit));
+  }
+  debug!("Decompressed {} bytes of gzip contents to {} bytes", data.len(), content.len());
+  Ok(Cow::Owned(content))
+}
+
+/// Compresses the data with gzip
+pub fn compress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+  let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+  encoder.write_all(data)?;
+  Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+
+  use super::{compress, decompress, decompress_with_limit, is_gzip};
+
+  #[test]
+  fn decompress_test() {
+    let compressed = compress(b"id,name\n1,Bob\n").unwrap();
+    expect!(is_gzip(&compressed)).to(be_true());
+    expect!(decompress(&compressed).unwrap().to_vec()).to(be_equal_to(b"id,name\n1,Bob\n".to_vec()));
+
+    expect!(is_gzip(b"id,name\n")).to(be_false());
+    expect!(decompress(b"id,name\n").unwrap().to_vec()).to(be_equal_to(b"id,name\n".to_vec()));
+    expect!(decompress(&compressed[..10])).to(be_err());
+
+    expect!(decompress_with_limit(&compressed, 14)).to(be_ok());
+    expect!(decompress_with_limit(&compressed, 13)).to(be_err());
+  }
+}
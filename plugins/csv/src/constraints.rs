@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::anyhow;
+use csv::StringRecord;
+use either::Either;
+use itertools::Itertools;
+use serde_json::{json, Value};
+
+use crate::categories::MismatchCategory;
+use crate::options::CsvOptions;
+use crate::parser::parse_field;
+use crate::proto;
+
+/// Constraint between the values of a column and the values of another column
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnConstraint {
+  /// The values of the two columns must be different in each row (i.e. `sourceId != targetId`)
+  DistinctFrom(Either<usize, String>),
+  /// No value of the column can occur anywhere in the other column, in any of the rows
+  DisjointFrom(Either<usize, String>)
+}
+
+impl ColumnConstraint {
+  /// Name of the constraint, as used in the column definitions
+  pub fn name(&self) -> &'static str {
+    match self {
+      ColumnConstraint::DistinctFrom(_) => "distinctFrom",
+      ColumnConstraint::DisjointFrom(_) => "disjointFrom"
+    }
+  }
+
+  /// The other column of the constraint
+  pub fn column(&self) -> &Either<usize, String> {
+    match self {
+      ColumnConstraint::DistinctFrom(column) | ColumnConstraint::DisjointFrom(column) => column
+    }
+  }
+
+  /// Builds the constraint from its JSON form, i.e. `{"type": "distinctFrom", "column": "column:targetId"}`
+  pub fn from_json(json: &Value) -> anyhow::Result<ColumnConstraint> {
+    let column = json.get("column").and_then(Value::as_str)
+      .ok_or_else(|| anyhow!("Column constraint {} must have a column", json))?;
+    let column = parse_field(column)?;
+    match json.get("type").and_then(Value::as_str) {
+      Some("distinctFrom") => Ok(ColumnConstraint::DistinctFrom(column)),
+      Some("disjointFrom") => Ok(ColumnConstraint::DisjointFrom(column)),
+      _ => Err(anyhow!("Column constraint {} is not valid, expected a type of distinctFrom or disjointFrom", json))
+    }
+  }
+
+  /// Converts the constraint into its JSON form
+  pub fn to_json(&self) -> Value {
+    let column = match self.column() {
+      Either::Left(index) => format!("column:{}", index),
+      Either::Right(header) => format!("column:{}", header)
+    };
+    json!({ "type": self.name(), "column": column })
+  }
+}
+
+/// Values collected from the actual rows to check a column constraint, once all the rows have
+/// been read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintCheck {
+  constraint: ColumnConstraint,
+  /// Index and header of the expected column with the constraint
+  pub index: usize,
+  pub header: String,
+  /// Index and header of the expected column the constraint refers to
+  pub other_index: usize,
+  pub other_header: String,
+  /// Rows (by number) where the values of the two columns are the same
+  same_values: Vec<(u64, String)>,
+  /// Lines each value occurs on, for each of the two columns
+  values: BTreeMap<String, BTreeSet<u64>>,
+  other_values: BTreeMap<String, BTreeSet<u64>>
+}
+
+impl ConstraintCheck {
+  /// Creates the checks for the constraints of each of the expected columns. Fails if a constraint
+  /// refers to a column that is not in the expected contents.
+  pub fn for_columns(options: &CsvOptions, expected_headers: &StringRecord, columns: usize) -> anyhow::Result<Vec<ConstraintCheck>> {
+    let mut checks = vec![];
+    for index in 0..columns {
+      let header = expected_headers.get(index).unwrap_or_default();
+      let constraints = options.column(index + 1, header)
+        .map(|column| column.constraints.as_slice())
+        .unwrap_or_default();
+      for constraint in constraints {
+        let other_index = match constraint.column() {
+          Either::Left(other) if *other <= columns => Some(other - 1),
+          Either::Left(_) => None,
+          Either::Right(other) => expected_headers.iter().position(|header| header == other)
+        }.ok_or_else(|| anyhow!("The {} constraint on column {} refers to column {}, which is not in the expected contents",
+          constraint.name(), display_name(index, header), constraint.column().clone().either(|index| index.to_string(), |header| header)))?;
+        checks.push(ConstraintCheck {
+          constraint: constraint.clone(),
+          index,
+          header: header.to_string(),
+          other_index,
+          other_header: expected_headers.get(other_index).unwrap_or_default().to_string(),
+          same_values: vec![],
+          values: BTreeMap::new(),
+          other_values: BTreeMap::new()
+        });
+      }
+    }
+    Ok(checks)
+  }
+
+  /// Adds the values of the two columns from an actual row. Columns that are missing from the row
+  /// are not checked.
+  pub fn add_row(&mut self, row: u64, value: Option<&str>, other_value: Option<&str>) {
+    match self.constraint {
+      ColumnConstraint::DistinctFrom(_) => if let (Some(value), Some(other_value)) = (value, other_value) {
+        if value == other_value {
+          self.same_values.push((row, value.to_string()));
+        }
+      },
+      ColumnConstraint::DisjointFrom(_) => {
+        if let Some(value) = value {
+          self.values.entry(value.to_string()).or_default().insert(row);
+        }
+        if let Some(other_value) = other_value {
+          self.other_values.entry(other_value.to_string()).or_default().insert(row);
+        }
+      }
+    }
+  }
+
+  /// Reports the rows that violate the constraint
+  pub fn compare(&self, options: &CsvOptions, results: &mut Vec<proto::ContentMismatch>) {
+    let column = display_name(self.index, &self.header);
+    let other_column = display_name(self.other_index, &self.other_header);
+    match self.constraint {
+      ColumnConstraint::DistinctFrom(_) => for (row, value) in &self.same_values {
+        results.push(options.categorise(MismatchCategory::CrossField, proto::ContentMismatch {
+          expected: Some(format!("not '{}'", value).as_bytes().to_vec()),
+          actual: Some(value.as_bytes().to_vec()),
+          mismatch: format!("Expected column {} to be distinct from column {}, but both were '{}' in row {}",
+            column, other_column, value, row),
+          path: format!("row:{:5}, column:{:2}", row, self.index),
+          diff: String::default()
+        }));
+      },
+      ColumnConstraint::DisjointFrom(_) => for (value, rows) in &self.values {
+        if let Some(other_rows) = self.other_values.get(value) {
+          results.push(options.categorise(MismatchCategory::CrossField, proto::ContentMismatch {
+            expected: None,
+            actual: Some(value.as_bytes().to_vec()),
+            mismatch: format!("Expected column {} to not share any values with column {}, but '{}' occurs in both (rows {} and rows {})",
+              column, other_column, value, rows.iter().join(", "), other_rows.iter().join(", ")),
+            path: format!("column:{:2}", self.index),
+            diff: String::default()
+          }));
+        }
+      }
+    }
+  }
+}
+
+pub(crate) fn display_name(index: usize, header: &str) -> String {
+  if header.is_empty() { (index + 1).to_string() } else { header.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+  use csv::StringRecord;
+  use either::Either;
+  use expectest::prelude::*;
+  use maplit::btreemap;
+  use serde_json::json;
+
+  use crate::options::{ColumnOptions, CsvOptions};
+
+  use super::{ColumnConstraint, ConstraintCheck};
+
+  #[test]
+  fn constraint_json_test() {
+    let constraint = ColumnConstraint::from_json(&json!({ "type": "distinctFrom", "column": "column:targetId" })).unwrap();
+    expect!(constraint.clone()).to(be_equal_to(ColumnConstraint::DistinctFrom(Either::Right("targetId".to_string()))));
+    expect!(ColumnConstraint::from_json(&constraint.to_json()).unwrap()).to(be_equal_to(constraint));
+    expect!(ColumnConstraint::from_json(&json!({ "type": "disjointFrom", "column": "column:2" })).unwrap())
+      .to(be_equal_to(ColumnConstraint::DisjointFrom(Either::Left(2))));
+    expect!(ColumnConstraint::from_json(&json!({ "type": "disjointFrom" }))).to(be_err());
+    expect!(ColumnConstraint::from_json(&json!({ "type": "other", "column": "column:2" }))).to(be_err());
+  }
+
+  #[test]
+  fn for_columns_fails_if_the_other_column_is_not_expected() {
+    let options = CsvOptions {
+      columns: btreemap!{
+        "column:a".to_string() => ColumnOptions {
+          constraints: vec![ColumnConstraint::DistinctFrom(Either::Right("c".to_string()))],
+          .. ColumnOptions::default()
+        }
+      },
+      .. CsvOptions::default()
+    };
+    let headers = StringRecord::from(vec!["a", "b"]);
+    expect!(ConstraintCheck::for_columns(&options, &headers, 2)).to(be_err());
+    let options = CsvOptions {
+      columns: btreemap!{
+        "column:a".to_string() => ColumnOptions {
+          constraints: vec![ColumnConstraint::DistinctFrom(Either::Left(2))],
+          .. ColumnOptions::default()
+        }
+      },
+      .. CsvOptions::default()
+    };
+    let checks = ConstraintCheck::for_columns(&options, &headers, 2).unwrap();
+    expect!(checks.iter().map(|check| (check.index, check.other_header.clone())).collect::<Vec<_>>())
+      .to(be_equal_to(vec![(0, "b".to_string())]));
+  }
+}
@@ -1,51 +1,104 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
 use anyhow::anyhow;
 use bytes::Bytes;
-use csv::{ReaderBuilder, Writer};
+use csv::{ReaderBuilder, WriterBuilder};
 use either::Either;
 use either::Either::{Left, Right};
 use itertools::Itertools;
-use log::debug;
+use log::{debug, info, warn};
 use maplit::hashmap;
 use pact_models::bodies::OptionalBody;
-use pact_models::generators::{GenerateValue, Generator, NoopVariantMatcher, VariantMatcher};
+use pact_models::generators::Generator;
 use pact_models::prelude::ContentType;
-use serde_json::{json, Value};
+use prost_types::value::Kind;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use tonic::{Request, Response};
 
-use pact_plugin_driver::utils::{to_proto_struct, proto_struct_to_map};
+use pact_plugin_driver::utils::to_proto_struct;
 
-use crate::parser::{parse_field, parse_value};
+use crate::compression::{compress, decompress};
+use crate::faults::{FAULT_INJECTION_ENV, fault_injection_enabled, inject_faults};
+use crate::fixed_width::{csv_to_fixed_width, fixed_width_to_csv};
+use crate::generators::{check_generator_params, generate_pact_value};
+use crate::limits::check_field_sizes;
+use crate::options::{body_content_type, ColumnOptions, Compression, CsvFormat, CsvOptions};
+use crate::parser::{ColumnDefinition, ConfigError, parse_field, parse_value, validate_config};
 use crate::proto;
-use crate::utils::{from_value, to_boolean, to_value};
+use crate::rows::RowOverride;
+use crate::utils::{from_value, to_value};
+
+/// UTF-8 byte order mark, which some tools (i.e. on Windows) write at the start of the CSV files
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
 
 pub fn setup_csv_contents(
   request: &Request<proto::ConfigureInteractionRequest>
 ) -> anyhow::Result<Response<proto::ConfigureInteractionResponse>> {
   match &request.get_ref().contents_config {
     Some(config) => {
-      let mut columns = vec![];
-      let has_headers = config.fields.get("csvHeaders").map(|val| to_boolean(val)).unwrap_or(true);
+      let mut column_definitions = vec![];
+      let mut options = CsvOptions::from_contents_config(&config.fields)?
+        .with_content_type(&request.get_ref().content_type)?;
+      let has_headers = options.has_headers;
+
+      // Report all the invalid column and row definitions together, instead of only the first one
+      let definitions = config.fields.iter()
+        .filter_map(|(key, value)| match &value.kind {
+          Some(Kind::StringValue(s)) => Some((key.clone(), s.clone())),
+          _ => None
+        })
+        .collect();
+      let mut errors = validate_config(&definitions);
+      // Definitions that are not strings are not valid either
+      errors.extend(config.fields.iter()
+        .filter(|(key, value)| (key.starts_with("column:") || key.starts_with("row:"))
+          && !matches!(value.kind, Some(Kind::StringValue(_))))
+        .filter_map(|(key, value)| parse_value(value).err()
+          .map(|err| ConfigError { key: key.clone(), message: err.to_string() })));
+      errors.sort_by(|a, b| a.key.cmp(&b.key));
+      if !errors.is_empty() {
+        return Err(anyhow!("The contents config is not valid: {}", errors.iter().join("; ")));
+      }
 
       for (key, value) in &config.fields {
-        if key.starts_with("column:") {
-          let column = parse_field(&key)?;
-          let result = parse_value(&value)?;
-          debug!("Parsed column definition: {}, {:?}", column, result);
-          match column {
-            Either::Left(i) => {
-              if i > columns.len() {
-                columns.resize(i, None)
-              }
-              columns[i - 1] = Some((result, i.to_string()));
-            }
-            Either::Right(s) => {
-              columns.push(Some((result, s.clone())))
+        if key.starts_with("row:") {
+          let (selector, column) = RowOverride::parse_key(key)?;
+          let result = parse_value(value)?;
+          debug!("Parsed row column definition: {}, {}, {:?}", selector, column, result);
+          let matching_rules = result.definition.rules.iter()
+            .map(|rule| match rule {
+              Either::Left(rule) => Ok(rule.clone()),
+              Either::Right(reference) => Err(anyhow!("Expected a matching rule definition for '{}', but got an un-resolved reference {:?}", key, reference))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+          options.rows.push(RowOverride {
+            selector,
+            column,
+            value: result.definition.value,
+            matching_rules,
+            options: ColumnOptions {
+              rules: result.rules,
+              transforms: result.transforms,
+              text: result.text,
+              .. ColumnOptions::default()
             }
-          }
+          });
+        } else if key.starts_with("column:") {
+          let column = parse_field(key)?;
+          let result = parse_value(value)?;
+          debug!("Parsed column definition: {}, {:?}", column, result);
+          column_definitions.push((column, result));
         }
       }
+      let columns = layout_columns(column_definitions);
 
-      let mut wtr = Writer::from_writer(vec![]);
+      // The example content is written with a comma if the delimiter is detected
+      let mut wtr = WriterBuilder::new()
+        .delimiter(options.writer_delimiter().unwrap_or(b','))
+        .quote_style(options.quote_style.writer_style())
+        .from_writer(vec![]);
       let mut csv_markup = String::new();
 
       csv_markup.push_str("# Data\n\n");
@@ -76,7 +129,7 @@ pub fn setup_csv_contents(
 
       let column_values = columns.iter().map(|v| {
         if let Some((md, _)) = v {
-          md.value.as_str()
+          md.definition.value.as_str()
         } else {
           ""
         }
@@ -91,7 +144,32 @@ pub fn setup_csv_contents(
       let mut generators = hashmap!{};
       for vals in columns {
         if let Some((md, name)) = vals {
-          for rule in md.rules {
+          // The generated values are checked against the Pact matching rules when the generation
+          // is retried, but the rules are not provided with the generate request
+          let generated = md.generator.is_some() || md.definition.generator.is_some();
+          let matching_rules = if options.retry_generation && generated {
+            md.definition.rules.iter().filter_map(|rule| rule.clone().left()).collect()
+          } else {
+            vec![]
+          };
+          if !matching_rules.is_empty() || !md.rules.is_empty() || !md.transforms.is_empty() || md.generator.is_some() || md.optional || md.text || md.multiset
+            || md.homogeneous || md.each_value || !md.constraints.is_empty() {
+            let column = options.columns.entry(format!("column:{}", name)).or_default();
+            column.rules.extend(md.rules);
+            column.transforms.extend(md.transforms);
+            if md.generator.is_some() {
+              column.generator = md.generator;
+            }
+            column.optional = column.optional || md.optional;
+            column.text = column.text || md.text;
+            column.multiset = column.multiset || md.multiset;
+            column.homogeneous = column.homogeneous || md.homogeneous;
+            column.each_value = column.each_value || md.each_value;
+            column.constraints.extend(md.constraints);
+            column.matching_rules.extend(matching_rules);
+          }
+
+          for rule in md.definition.rules {
             if let Either::Left(rule) = rule {
               debug!("rule.values()={:?}", rule.values());
               rules.insert(format!("column:{}", name), proto::MatchingRules {
@@ -112,7 +190,7 @@ pub fn setup_csv_contents(
             }
           }
 
-          if let Some(gen) = md.generator {
+          if let Some(gen) = md.definition.generator {
             generators.insert(format!("column:{}", name), proto::Generator {
               r#type: gen.name(),
               values: Some(prost_types::Struct {
@@ -129,17 +207,15 @@ pub fn setup_csv_contents(
       Ok(Response::new(proto::ConfigureInteractionResponse {
         interaction: vec![proto::InteractionResponse {
           contents: Some(proto::Body {
-            content_type: "text/csv;charset=UTF-8".to_string(),
-            content: Some(wtr.into_inner()?),
+            content_type: body_content_type(&request.get_ref().content_type).to_string(),
+            content: Some(encode_content(wtr.into_inner()?, &options)?),
             content_type_hint: 0
           }),
           rules,
           generators,
           message_metadata: None,
           plugin_configuration: Some(proto::PluginConfiguration {
-            interaction_configuration: Some(to_proto_struct(&hashmap!{
-            "csvHeaders".to_string() => json!(has_headers)
-          })),
+            interaction_configuration: Some(to_proto_struct(&options.to_config())),
             pact_configuration: None
           }),
           interaction_markup: csv_markup,
@@ -153,75 +229,611 @@ pub fn setup_csv_contents(
   }
 }
 
+// Places the column definitions in a deterministic order, regardless of the order of the config
+// entries. Columns with an index are placed at that index, and columns with a name follow the last
+// indexed column, ordered by name.
+fn layout_columns(definitions: Vec<(Either<usize, String>, ColumnDefinition)>) -> Vec<Option<(ColumnDefinition, String)>> {
+  let (mut indexed, mut named): (Vec<_>, Vec<_>) = definitions.into_iter()
+    .partition(|(column, _)| column.is_left());
+  indexed.sort_by_key(|(column, _)| column.clone().left());
+  named.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+  let mut columns = vec![];
+  for (column, definition) in indexed.into_iter().chain(named) {
+    match column {
+      Either::Left(i) => {
+        if i > columns.len() {
+          columns.resize(i, None)
+        }
+        columns[i - 1] = Some((definition, i.to_string()));
+      }
+      Either::Right(name) => columns.push(Some((definition, name)))
+    }
+  }
+  columns
+}
+
 pub fn generate_csv_content(
   request: &Request<proto::GenerateContentRequest>
 ) -> anyhow::Result<OptionalBody> {
   let request = request.get_ref();
-  let has_headers = has_headers(&request.plugin_configuration);
+  let content_type = request.contents.as_ref().map(|contents| contents.content_type.as_str()).unwrap_or_default();
+  let options = CsvOptions::from_plugin_config(&request.plugin_configuration)?.with_content_type(content_type)?;
+  if !options.fault_injection.is_empty() && !fault_injection_enabled() {
+    return Err(anyhow!("The faultInjection option is set, but fault injection has not been enabled (set {} to true)",
+      FAULT_INJECTION_ENV));
+  }
 
   let mut generators = hashmap! {};
   for (key, gen) in &request.generators {
     let column = parse_field(&key)?;
-    let values = gen.values.as_ref().ok_or(anyhow!("Generator values were expected"))?.fields.iter().map(|(k, v)| {
-      (k.clone(), from_value(v))
-    }).collect();
+    let values = gen.values.as_ref()
+      .map(|values| values.fields.iter().map(|(k, v)| (k.clone(), from_value(v))).collect())
+      .unwrap_or_default();
+    check_generator_params(&gen.r#type, &values)?;
     let generator = Generator::from_map(&gen.r#type, &values)
       .ok_or(anyhow!("Failed to build generator of type {}", gen.r#type))?;
     generators.insert(column, generator);
   };
 
-  let context = hashmap! {};
-  let variant_matcher = NoopVariantMatcher.boxed();
-  let mut wtr = Writer::from_writer(vec![]);
+  let template = request.contents.as_ref()
+    .and_then(|contents| contents.content.as_ref())
+    .filter(|content| !content.is_empty())
+    .map(|template| decompress(template))
+    .transpose()
+    .map_err(|err| anyhow!("Could not read the template for the generated contents: {}", err))?;
+  let generated = generate_contents(&options, template.as_deref(), &generators)?;
+  debug!("Generated contents has {} bytes", generated.len());
+  match options.compression {
+    Compression::None => Ok(OptionalBody::Present(Bytes::from(generated),
+      Some(ContentType::from(body_content_type(content_type))), None)),
+    Compression::Gzip => {
+      let compressed = compress(&generated)?;
+      debug!("Compressed the generated contents to {} bytes", compressed.len());
+      Ok(OptionalBody::Present(Bytes::from(compressed),
+        Some(ContentType::from(format!("{};encoding=gzip", body_content_type(content_type)))), None))
+    }
+  }
+}
+
+/// Generates the contents from the template, applying the plugin generators from the options and
+/// the Pact generators for the columns. If there is no template, the contents are generated from
+/// the generator spec in the options.
+pub(crate) fn generate_contents(
+  options: &CsvOptions,
+  template: Option<&[u8]>,
+  generators: &HashMap<Either<usize, String>, Generator>
+) -> anyhow::Result<Vec<u8>> {
+  let has_headers = options.has_headers;
+  let delimiter = options.writer_delimiter()?;
+  let mut wtr = WriterBuilder::new().delimiter(delimiter).quote(options.actual_quote())
+    .quote_style(options.quote_style.writer_style()).from_writer(vec![]);
+  let mut rng = match options.seed {
+    Some(seed) => StdRng::seed_from_u64(seed),
+    None => StdRng::from_entropy()
+  };
 
-  let csv_data = request.contents.as_ref().unwrap().content.as_ref().unwrap();
-  let mut rdr = ReaderBuilder::new().has_headers(has_headers).from_reader(csv_data.as_slice());
+  let template = match (template, &options.generator_spec) {
+    (Some(template), _) => template,
+    (None, Some(spec)) => {
+      debug!("No template provided, generating the contents from the generator spec");
+      let (header, rows) = spec.generate(&mut rng)?;
+      if has_headers {
+        wtr.write_record(&header)?;
+      }
+      for row in rows {
+        wtr.write_record(&row)?;
+      }
+      return finish_contents(wtr.into_inner()?, options, &mut rng);
+    }
+    (None, None) => return Err(anyhow!("No template or generator spec was provided to generate the CSV contents"))
+  };
+
+  let csv_data = decode_content(template, options)?;
+  check_field_sizes(csv_data.as_ref(), delimiter, b'"', options.max_field_size)
+    .map_err(|err| anyhow!("Could not read the template for the generated contents: {}", err))?;
+  let mut rdr = ReaderBuilder::new().has_headers(has_headers).delimiter(delimiter).from_reader(csv_data.as_ref());
   let headers = rdr.headers()?.clone();
 
   if has_headers {
     wtr.write_record(&headers)?;
   }
 
-  for result in rdr.records() {
-    let record = result?;
+  // With a row count, the template rows are repeated until there are that many rows, with new
+  // values from the generators for each row
+  let records = rdr.records().collect::<Result<Vec<_>, _>>()?;
+  let row_count = options.row_count.unwrap_or(records.len());
+  if records.is_empty() && row_count > 0 {
+    return Err(anyhow!("The template has no data rows to generate {} rows from", row_count));
+  }
+  for row in 0..row_count {
+    let record = &records[row % records.len()];
     for (col, field) in record.iter().enumerate() {
       debug!("got column:{} = '{}'", col, field);
-      if has_headers {
-        if let Some(generator) = generators.get(&Right(headers.get(col).unwrap_or_default().to_string())) {
-          let value = generator.generate_value(&field.to_string(), &context, &variant_matcher)?;
-          wtr.write_field(value)?;
-        } else if let Some(generator) = generators.get(&Left(col)) {
-          let value = generator.generate_value(&field.to_string(), &context, &variant_matcher)?;
-          wtr.write_field(value)?;
-        } else {
-          wtr.write_field(field)?;
-        }
+      let header = if has_headers { headers.get(col).unwrap_or_default() } else { "" };
+      let column = options.column(col + 1, header);
+      let plugin_generator = column.and_then(|column| column.generator.as_ref());
+      let column_generator = if has_headers {
+        generators.get(&Right(header.to_string())).or_else(|| generators.get(&Left(col)))
       } else {
-        if let Some(generator) = generators.get(&Left(col)) {
-          let value = generator.generate_value(&field.to_string(), &context, &variant_matcher)?;
-          wtr.write_field(value)?;
-        } else {
-          wtr.write_field(field)?;
-        }
+        generators.get(&Left(col))
+      };
+      // The default generator only applies to columns without a generator of their own
+      let default_generator = options.default_generator.as_ref()
+        .filter(|_| plugin_generator.is_none() && column_generator.is_none())
+        .and_then(|default_generator| default_generator.generator_for(field));
+      let generator = column_generator.or(default_generator.as_ref());
+      if plugin_generator.is_none() && generator.is_none() {
+        wtr.write_field(field)?;
+        continue;
       }
+
+      let mut attempt = 1;
+      let value = loop {
+        let value = match (plugin_generator, generator) {
+          (Some(plugin_generator), _) => plugin_generator.generate_value(row, &mut rng)?,
+          (None, Some(generator)) => generate_pact_value(generator, field, &mut rng)?,
+          (None, None) => unreachable!()
+        };
+        let result = match column {
+          Some(column) if options.retry_generation => column.check_generated(field, &value, options.rounding_mode),
+          _ => Ok(())
+        };
+        match result {
+          Ok(()) => break value,
+          Err(err) if attempt < options.max_generation_attempts => {
+            debug!("Generated value '{}' for column {} does not match its rules, generating it again - {}",
+              value, col + 1, err);
+            attempt += 1;
+          }
+          Err(err) => return Err(anyhow!("Could not generate a value for column {} that matches its rules in {} attempts - {}",
+            if header.is_empty() { (col + 1).to_string() } else { header.to_string() }, attempt, err))
+        }
+      };
+      wtr.write_field(value)?;
     }
     wtr.write_record(None::<&[u8]>)?;
   }
-  let generated = wtr.into_inner()?;
-  debug!("Generated contents has {} bytes", generated.len());
-  let bytes = Bytes::from(generated);
-  Ok(OptionalBody::Present(bytes, Some(ContentType::from("text/csv;charset=UTF-8")), None))
+  finish_contents(wtr.into_inner()?, options, &mut rng)
+}
+
+// Injects any faults into the generated CSV data, and then converts it into the configured format
+fn finish_contents(data: Vec<u8>, options: &CsvOptions, rng: &mut StdRng) -> anyhow::Result<Vec<u8>> {
+  if options.fault_injection.is_empty() {
+    return encode_content(data, options);
+  }
+  let (data, injected) = inject_faults(&data, options, rng)?;
+  for fault in injected {
+    info!("Injected a fault into the generated contents: {}", fault);
+  }
+  encode_content(data, options)
+}
+
+/// Returns the content as CSV data, converting it from the configured format if required. A UTF-8
+/// byte order mark at the start is removed first, as it is not part of the first value. The CSV
+/// reader also skips it, but the other formats and the checks on the raw data would not.
+pub fn decode_content<'a>(data: &'a [u8], options: &CsvOptions) -> anyhow::Result<Cow<'a, [u8]>> {
+  let data = strip_bom(data);
+  let data = if options.lossy_utf8 { replace_invalid_utf8(data) } else { Cow::Borrowed(data) };
+  match &options.format {
+    CsvFormat::Delimited => Ok(data),
+    CsvFormat::FixedWidth(widths) => fixed_width_to_csv(&data, widths).map(Cow::Owned),
+    CsvFormat::Zip => Err(anyhow!("Zip archives can only be compared, the members are decoded when comparing them"))
+  }
+}
+
+/// Removes the UTF-8 byte order mark from the start of the data, if it has one
+pub fn strip_bom(data: &[u8]) -> &[u8] {
+  data.strip_prefix(UTF8_BOM).unwrap_or(data)
+}
+
+/// Replaces the invalid UTF-8 sequences with the replacement character, logging a warning with the
+/// lines they were on
+fn replace_invalid_utf8(data: &[u8]) -> Cow<'_, [u8]> {
+  let lines = invalid_utf8_lines(data);
+  if lines.is_empty() {
+    Cow::Borrowed(data)
+  } else {
+    warn!("Replaced invalid UTF-8 bytes with the replacement character on line(s) {}", lines.iter().join(", "));
+    Cow::Owned(String::from_utf8_lossy(data).into_owned().into_bytes())
+  }
+}
+
+/// Lines (starting at 1) that have invalid UTF-8 sequences
+fn invalid_utf8_lines(data: &[u8]) -> Vec<usize> {
+  let mut lines = vec![];
+  let mut line = 1;
+  let mut offset = 0;
+  while let Err(err) = std::str::from_utf8(&data[offset..]) {
+    let position = offset + err.valid_up_to();
+    line += data[offset..position].iter().filter(|byte| **byte == b'\n').count();
+    if lines.last() != Some(&line) {
+      lines.push(line);
+    }
+    match err.error_len() {
+      Some(len) => offset = position + len,
+      None => break
+    }
+  }
+  lines
 }
 
-pub fn has_headers(plugin_config: &Option<proto::PluginConfiguration>) -> bool {
-  match &plugin_config {
-    Some(config) => match &config.interaction_configuration {
-      Some(i_config) => proto_struct_to_map(&i_config).get("csvHeaders").map(|val| match val {
-        Value::Bool(b) => *b,
-        _ => true
-      }).unwrap_or(true),
-      None => true
+/// Converts the CSV data into the configured format
+pub fn encode_content(data: Vec<u8>, options: &CsvOptions) -> anyhow::Result<Vec<u8>> {
+  match &options.format {
+    CsvFormat::Delimited => Ok(data),
+    CsvFormat::FixedWidth(widths) => csv_to_fixed_width(&data, widths),
+    CsvFormat::Zip => Err(anyhow!("Contents can not be generated as a zip archive"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use either::Either;
+  use expectest::prelude::*;
+  use maplit::{btreemap, hashmap};
+  use pact_models::generators::Generator;
+  use pact_models::matchingrules::MatchingRule;
+  use prost_types::value::Kind;
+  use tonic::Request;
+
+  use crate::compression::{compress, decompress};
+  use crate::generators::{CsvGenerator, DefaultGenerator};
+  use crate::matchers::CsvRule;
+  use crate::options::{ColumnOptions, CsvFormat, CsvOptions};
+  use crate::proto;
+
+  use super::{decode_content, generate_contents, generate_csv_content, invalid_utf8_lines, setup_csv_contents};
+
+  fn column_values(contents: &[u8], column: usize) -> Vec<String> {
+    String::from_utf8_lossy(contents).lines()
+      .map(|line| line.split(',').nth(column).unwrap_or_default().to_string())
+      .collect()
+  }
+
+  #[test]
+  fn setup_csv_contents_orders_the_columns_by_index_then_name() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(Kind::StringValue(value.to_string())) };
+    let request = Request::new(proto::ConfigureInteractionRequest {
+      content_type: "text/csv".to_string(),
+      contents_config: Some(prost_types::Struct {
+        fields: btreemap! {
+          "column:total".to_string() => string_value("matching(number, 100)"),
+          "column:#ref".to_string() => string_value("matching(type, 'R1')"),
+          "column:2".to_string() => string_value("matching(type, 'b')"),
+          "column:1".to_string() => string_value("matching(type, 'a')"),
+          "column:email".to_string() => string_value("matching(type, 'x@y.z')")
+        }
+      })
+    });
+    for _ in 0..3 {
+      let response = setup_csv_contents(&request).unwrap();
+      let contents = response.get_ref().interaction[0].contents.as_ref().unwrap().content.clone().unwrap();
+      expect!(String::from_utf8(contents).unwrap()).to(be_equal_to("1,2,#ref,email,total\na,b,R1,x@y.z,100\n"));
     }
-    None => true
+  }
+
+  #[test]
+  fn setup_csv_contents_reports_all_the_invalid_definitions() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(Kind::StringValue(value.to_string())) };
+    let request = Request::new(proto::ConfigureInteractionRequest {
+      content_type: "text/csv".to_string(),
+      contents_config: Some(prost_types::Struct {
+        fields: btreemap! {
+          "column:1".to_string() => string_value("matching(integer, 100)"),
+          "column:2".to_string() => prost_types::Value { kind: Some(Kind::NumberValue(100.0)) },
+          "column:name".to_string() => string_value("matching(other, 'a')"),
+          "row:first:column:1".to_string() => string_value("matching(integer, 1)")
+        }
+      })
+    });
+    let err = setup_csv_contents(&request).unwrap_err().to_string();
+    expect!(err.starts_with("The contents config is not valid: column:2: Number is not a valid value definition value; \
+      column:name: ")).to(be_true());
+    expect!(err.contains("; row:first:column:1: ")).to(be_true());
+  }
+
+  #[test]
+  fn setup_csv_contents_with_a_delimiter_in_the_content_type() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(Kind::StringValue(value.to_string())) };
+    let setup = |content_type: &str, fields: BTreeMap<String, prost_types::Value>| {
+      let request = Request::new(proto::ConfigureInteractionRequest {
+        content_type: content_type.to_string(),
+        contents_config: Some(prost_types::Struct { fields })
+      });
+      setup_csv_contents(&request).map(|response| {
+        let contents = response.get_ref().interaction[0].contents.as_ref().unwrap().content.clone().unwrap();
+        String::from_utf8(contents).unwrap()
+      })
+    };
+    let columns = || btreemap! {
+      "column:1".to_string() => string_value("matching(type, 'a')"),
+      "column:2".to_string() => string_value("matching(type, 'b')")
+    };
+    expect!(setup("text/csv;delimiter=;", columns()).unwrap()).to(be_equal_to("1;2\na;b\n"));
+    expect!(setup("text/csv; delimiter=;; charset=UTF-8", columns()).unwrap()).to(be_equal_to("1;2\na;b\n"));
+    expect!(setup("text/csv; charset=UTF-8; delimiter=\"\t\"", columns()).unwrap()).to(be_equal_to("1\t2\na\tb\n"));
+    expect!(setup("text/csv; delimiter=tab", columns()).unwrap()).to(be_equal_to("1\t2\na\tb\n"));
+    expect!(setup("text/csv", columns()).unwrap()).to(be_equal_to("1,2\na,b\n"));
+    expect!(setup("text/csv;delimiter=||", columns())).to(be_err());
+
+    let mut fields = columns();
+    fields.insert("delimiter".to_string(), string_value("|"));
+    expect!(setup("text/csv;delimiter=;", fields).unwrap()).to(be_equal_to("1|2\na|b\n"));
+  }
+
+  #[test]
+  fn generate_csv_content_with_gzip_compression() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(Kind::StringValue(value.to_string())) };
+    let generate = |content_type: &str, template: Vec<u8>, compression: Option<&str>| {
+      let fields = compression.map(|compression| btreemap! { "compression".to_string() => string_value(compression) })
+        .unwrap_or_default();
+      let request = Request::new(proto::GenerateContentRequest {
+        contents: Some(proto::Body {
+          content_type: content_type.to_string(),
+          content: Some(template),
+          content_type_hint: 0
+        }),
+        generators: hashmap! {},
+        plugin_configuration: Some(proto::PluginConfiguration {
+          interaction_configuration: Some(prost_types::Struct { fields }),
+          pact_configuration: None
+        })
+      });
+      generate_csv_content(&request).map(|body| {
+        (body.content_type().unwrap().to_string(), body.value().unwrap().to_vec())
+      })
+    };
+
+    // A compressed template is decompressed, and the contents are only compressed if asked for
+    let template = b"id,name\n1,a\n".to_vec();
+    let (content_type, contents) = generate("text/csv", compress(&template).unwrap(), None).unwrap();
+    expect!(content_type).to(be_equal_to("text/csv;charset=utf-8"));
+    expect!(contents).to(be_equal_to(template.clone()));
+
+    let (content_type, contents) = generate("text/csv", template.clone(), Some("gzip")).unwrap();
+    expect!(content_type).to(be_equal_to("text/csv;charset=utf-8;encoding=gzip"));
+    expect!(decompress(&contents).unwrap().to_vec()).to(be_equal_to(template.clone()));
+
+    let (content_type, contents) = generate("text/csv;encoding=gzip", template.clone(), None).unwrap();
+    expect!(content_type).to(be_equal_to("text/csv;charset=utf-8;encoding=gzip"));
+    expect!(decompress(&contents).unwrap().to_vec()).to(be_equal_to(template.clone()));
+
+    expect!(generate("text/csv", template.clone(), Some("zip"))).to(be_err());
+    expect!(generate("text/csv", vec![0x1f, 0x8b, 0], None)).to(be_err());
+  }
+
+  #[test]
+  fn setup_and_generate_contents_with_a_quote_style() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(Kind::StringValue(value.to_string())) };
+    let setup = |style: &str| {
+      let request = Request::new(proto::ConfigureInteractionRequest {
+        content_type: "text/csv".to_string(),
+        contents_config: Some(prost_types::Struct {
+          fields: btreemap! {
+            "column:1".to_string() => string_value("matching(integer, 1)"),
+            "column:2".to_string() => string_value("matching(type, 'a b')"),
+            "quoteStyle".to_string() => string_value(style)
+          }
+        })
+      });
+      setup_csv_contents(&request).map(|response| {
+        let interaction = &response.get_ref().interaction[0];
+        let contents = interaction.contents.as_ref().unwrap().content.clone().unwrap();
+        let options = CsvOptions::from_plugin_config(&interaction.plugin_configuration).unwrap();
+        (String::from_utf8(contents).unwrap(), options)
+      })
+    };
+    expect!(setup("necessary").unwrap().0).to(be_equal_to("1,2\n1,a b\n"));
+    expect!(setup("always").unwrap().0).to(be_equal_to("\"1\",\"2\"\n\"1\",\"a b\"\n"));
+    expect!(setup("never").unwrap().0).to(be_equal_to("1,2\n1,a b\n"));
+    expect!(setup("sometimes")).to(be_err());
+
+    // The quote style is kept with the interaction, so the generated contents are quoted the same way
+    let (template, options) = setup("nonNumeric").unwrap();
+    expect!(template.clone()).to(be_equal_to("1,2\n1,\"a b\"\n"));
+    let generated = generate_contents(&options, Some(template.as_bytes()), &hashmap!{}).unwrap();
+    expect!(String::from_utf8(generated).unwrap()).to(be_equal_to(template));
+  }
+
+  #[test]
+  fn invalid_utf8_lines_test() {
+    expect!(invalid_utf8_lines(b"a,b\nc,d\n").iter()).to(be_empty());
+    expect!(invalid_utf8_lines(b"a,b\n\xffc,d\xfe\ne,f\n\xe2\x82")).to(be_equal_to(vec![2, 4]));
+    let options = CsvOptions { lossy_utf8: true, .. CsvOptions::default() };
+    expect!(decode_content(b"id\n1\xff\n", &options).unwrap().to_vec()).to(be_equal_to("id\n1\u{FFFD}\n".as_bytes().to_vec()));
+  }
+
+  #[test]
+  fn decode_content_strips_the_byte_order_mark() {
+    let options = CsvOptions::default();
+    expect!(decode_content(b"\xEF\xBB\xBFid\n1\n", &options).unwrap().to_vec()).to(be_equal_to(b"id\n1\n".to_vec()));
+    expect!(decode_content(b"id\n\xEF\xBB\xBF1\n", &options).unwrap().to_vec())
+      .to(be_equal_to(b"id\n\xEF\xBB\xBF1\n".to_vec()));
+    let options = CsvOptions { format: CsvFormat::FixedWidth(vec![2, 4]), .. CsvOptions::default() };
+    expect!(decode_content("\u{feff}idname\n1 Bob \n".as_bytes(), &options).unwrap().to_vec())
+      .to(be_equal_to(b"id,name\n1,Bob\n".to_vec()));
+
+    let options = CsvOptions { columns: btreemap! {
+      "column:id".to_string() => ColumnOptions { generator: Some(CsvGenerator::Sequence { start: 5, step: 1 }), .. ColumnOptions::default() }
+    }, .. CsvOptions::default() };
+    expect!(String::from_utf8(generate_contents(&options, Some(b"\xEF\xBB\xBFid,name\n1,a\n"), &hashmap!{}).unwrap()).unwrap())
+      .to(be_equal_to("id,name\n5,a\n".to_string()));
+  }
+
+  #[test]
+  fn generate_contents_is_repeatable_with_deterministic_generators() {
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:id".to_string() => ColumnOptions {
+          generator: Some(CsvGenerator::Sequence { start: 100, step: 10 }),
+          .. ColumnOptions::default()
+        }
+      },
+      .. CsvOptions::default()
+    };
+    let template = "id,name\n1,a\n1,b\n1,c\n".as_bytes();
+    let first = generate_contents(&options, Some(template), &hashmap!{}).unwrap();
+    let second = generate_contents(&options, Some(template), &hashmap!{}).unwrap();
+    expect!(String::from_utf8(first.clone()).unwrap()).to(be_equal_to("id,name\n100,a\n110,b\n120,c\n"));
+    expect!(first).to(be_equal_to(second));
+  }
+
+  #[test]
+  fn generate_contents_with_a_row_count() {
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:id".to_string() => ColumnOptions {
+          generator: Some(CsvGenerator::Sequence { start: 1, step: 1 }),
+          .. ColumnOptions::default()
+        }
+      },
+      row_count: Some(5),
+      .. CsvOptions::default()
+    };
+    let generators = hashmap! { Either::Right("amount".to_string()) => Generator::RandomInt(100, 999) };
+    let generated = generate_contents(&options, Some("id,name,amount\n1,a,100\n".as_bytes()), &generators).unwrap();
+    let generated = String::from_utf8(generated).unwrap();
+    let rows = generated.lines().skip(1).map(|line| line.split(',').collect::<Vec<_>>()).collect::<Vec<_>>();
+    expect!(rows.iter().map(|row| format!("{},{}", row[0], row[1])).collect::<Vec<_>>())
+      .to(be_equal_to(vec!["1,a", "2,a", "3,a", "4,a", "5,a"]));
+    expect!(rows.iter().all(|row| row[2].len() == 3)).to(be_true());
+
+    let options = CsvOptions { row_count: Some(3), .. CsvOptions::default() };
+    let generated = generate_contents(&options, Some("id\n1\n2\n".as_bytes()), &hashmap!{}).unwrap();
+    expect!(String::from_utf8(generated).unwrap()).to(be_equal_to("id\n1\n2\n1\n"));
+    expect!(generate_contents(&options, Some("id\n".as_bytes()), &hashmap!{})).to(be_err());
+  }
+
+  #[test]
+  fn generate_contents_retries_values_that_do_not_match_the_rules() {
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:code".to_string() => ColumnOptions {
+          matching_rules: vec![MatchingRule::Regex("^[0-9]$".to_string())],
+          .. ColumnOptions::default()
+        }
+      },
+      retry_generation: true,
+      max_generation_attempts: 200,
+      .. CsvOptions::default()
+    };
+    let generators = hashmap! { Either::Right("code".to_string()) => Generator::RandomInt(0, 99) };
+    let generated = generate_contents(&options, Some("code\n1\n2\n3\n".as_bytes()), &generators).unwrap();
+    expect!(column_values(&generated, 0).iter().skip(1).all(|value| value.len() == 1)).to(be_true());
+
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:id".to_string() => ColumnOptions {
+          rules: vec![CsvRule::Luhn],
+          generator: Some(CsvGenerator::Sequence { start: 1, step: 1 }),
+          .. ColumnOptions::default()
+        }
+      },
+      retry_generation: true,
+      max_generation_attempts: 3,
+      .. CsvOptions::default()
+    };
+    let result = generate_contents(&options, Some("id\n1\n".as_bytes()), &hashmap!{});
+    expect!(result.unwrap_err().to_string()).to(be_equal_to(
+      "Could not generate a value for column id that matches its rules in 3 attempts - Expected '1' to be a number with a Luhn check digit"));
+
+    let options = CsvOptions { retry_generation: false, .. options };
+    expect!(generate_contents(&options, Some("id\n1\n".as_bytes()), &hashmap!{})).to(be_ok());
+  }
+
+  #[test]
+  fn generate_contents_samples_weighted_values_with_the_seed() {
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:grade".to_string() => ColumnOptions {
+          generator: Some(CsvGenerator::Weighted { choices: vec![("A".to_string(), 70), ("B".to_string(), 30)] }),
+          .. ColumnOptions::default()
+        }
+      },
+      seed: Some(42),
+      .. CsvOptions::default()
+    };
+    let template = format!("id,grade\n{}", "1,A\n".repeat(1000));
+    let first = generate_contents(&options, Some(template.as_bytes()), &hashmap!{}).unwrap();
+    let second = generate_contents(&options, Some(template.as_bytes()), &hashmap!{}).unwrap();
+    expect!(first.clone()).to(be_equal_to(second));
+
+    let grades = column_values(&first, 1);
+    let a_count = grades.iter().filter(|grade| *grade == "A").count();
+    let b_count = grades.iter().filter(|grade| *grade == "B").count();
+    expect!(a_count + b_count).to(be_equal_to(1000));
+    expect!(a_count > 600 && a_count < 800).to(be_true());
+  }
+
+  #[test]
+  fn generate_contents_is_repeatable_with_the_seed_for_the_random_generators() {
+    let generators = hashmap! {
+      Either::Right("int".to_string()) => Generator::RandomInt(1, 1000000),
+      Either::Right("decimal".to_string()) => Generator::RandomDecimal(6),
+      Either::Right("string".to_string()) => Generator::RandomString(10),
+      Either::Right("bool".to_string()) => Generator::RandomBoolean,
+      Either::Right("uuid".to_string()) => Generator::Uuid(None)
+    };
+    let template = format!("int,decimal,string,bool,uuid\n{}", "1,1.5,abc,true,x\n".repeat(10));
+    let generate = |seed: u64| {
+      let options = CsvOptions { seed: Some(seed), .. CsvOptions::default() };
+      generate_contents(&options, Some(template.as_bytes()), &generators).unwrap()
+    };
+
+    let first = generate(42);
+    expect!(first.clone()).to(be_equal_to(generate(42)));
+    expect!(first).to_not(be_equal_to(generate(43)));
+  }
+
+  #[test]
+  fn generate_contents_with_a_default_generator() {
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:id".to_string() => ColumnOptions {
+          generator: Some(CsvGenerator::Sequence { start: 1, step: 1 }),
+          .. ColumnOptions::default()
+        }
+      },
+      default_generator: Some(DefaultGenerator::ByType),
+      .. CsvOptions::default()
+    };
+    let generators = hashmap! {
+      Either::Right("code".to_string()) => Generator::Regex("[A-C]{2}".to_string())
+    };
+    let template = format!("id,code,count,name,note\n{}", "1,XY,123,abcde,\n".repeat(20));
+    let generated = generate_contents(&options, Some(template.as_bytes()), &generators).unwrap();
+
+    expect!(column_values(&generated, 0)[1..4].to_vec()).to(be_equal_to(vec!["1".to_string(), "2".to_string(), "3".to_string()]));
+    expect!(column_values(&generated, 1)[1..].iter().all(|code| code.len() == 2 && code.chars().all(|ch| ('A'..='C').contains(&ch))))
+      .to(be_true());
+    let counts = column_values(&generated, 2)[1..].to_vec();
+    expect!(counts.iter().all(|count| count.parse::<i32>().map(|n| (100..=999).contains(&n)).unwrap_or(false))).to(be_true());
+    expect!(counts.iter().any(|count| count != "123")).to(be_true());
+    let names = column_values(&generated, 3)[1..].to_vec();
+    expect!(names.iter().all(|name| name.chars().count() == 5)).to(be_true());
+    expect!(names.iter().any(|name| name != "abcde")).to(be_true());
+    expect!(column_values(&generated, 4)[1..].iter().all(|note| note.is_empty())).to(be_true());
+  }
+
+  #[test]
+  fn generate_contents_only_changes_the_generated_columns() {
+    let options = CsvOptions::default();
+    let generators = hashmap! {
+      Either::Right("code".to_string()) => Generator::RandomString(20)
+    };
+    let template = "code,name\nabc,a\nabc,b\n".as_bytes();
+    let first = generate_contents(&options, Some(template), &generators).unwrap();
+    let second = generate_contents(&options, Some(template), &generators).unwrap();
+    expect!(column_values(&first, 1)).to(be_equal_to(vec!["name".to_string(), "a".to_string(), "b".to_string()]));
+    expect!(column_values(&first, 1)).to(be_equal_to(column_values(&second, 1)));
+    expect!(column_values(&first, 0)[1..].to_vec()).to_not(be_equal_to(column_values(&second, 0)[1..].to_vec()));
+  }
+
+  #[test]
+  fn generate_contents_requires_a_template_or_spec() {
+    expect!(generate_contents(&CsvOptions::default(), None, &hashmap!{})).to(be_err());
   }
 }
@@ -0,0 +1,224 @@
+use std::env;
+
+use anyhow::anyhow;
+use csv::{ReaderBuilder, WriterBuilder};
+use either::Either;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde_json::{json, Value};
+
+use crate::limits::check_field_sizes;
+use crate::options::CsvOptions;
+use crate::parser::parse_field;
+
+/// Environment variable that must be set to `true` for faults to be injected into generated contents
+pub const FAULT_INJECTION_ENV: &str = "PACT_CSV_FAULT_INJECTION";
+
+/// Defect to inject into generated contents, to check that a verification catches it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+  /// Change the value of the column in a data row
+  ChangeValue { column: Either<usize, String>, row: Option<usize> },
+  /// Remove a data row
+  DropRow { row: Option<usize> },
+  /// Repeat a data row
+  DuplicateRow { row: Option<usize> }
+}
+
+impl Fault {
+  /// Builds the fault from its JSON form, i.e. `{"type": "changeValue", "column": "column:name", "row": 2}`.
+  /// Rows are numbered from 1, and if a row is not given one is chosen at random.
+  pub fn from_json(json: &Value) -> anyhow::Result<Fault> {
+    let row = match json.get("row") {
+      Some(value) => match value.as_u64() {
+        Some(row) if row > 0 => Some(row as usize),
+        _ => return Err(anyhow!("'{}' is not a valid row for fault {}, expected a row number starting at 1", value, json))
+      },
+      None => None
+    };
+    match json.get("type").and_then(Value::as_str) {
+      Some("changeValue") => {
+        let column = json.get("column").and_then(Value::as_str)
+          .ok_or_else(|| anyhow!("Fault {} must have a column", json))?;
+        Ok(Fault::ChangeValue { column: parse_field(column)?, row })
+      }
+      Some("dropRow") => Ok(Fault::DropRow { row }),
+      Some("duplicateRow") => Ok(Fault::DuplicateRow { row }),
+      _ => Err(anyhow!("Fault {} is not valid, expected a type of changeValue, dropRow or duplicateRow", json))
+    }
+  }
+
+  /// Converts the fault into its JSON form
+  pub fn to_json(&self) -> Value {
+    let (mut json, row) = match self {
+      Fault::ChangeValue { column, row } => {
+        let column = match column {
+          Either::Left(index) => format!("column:{}", index),
+          Either::Right(header) => format!("column:{}", header)
+        };
+        (json!({ "type": "changeValue", "column": column }), row)
+      }
+      Fault::DropRow { row } => (json!({ "type": "dropRow" }), row),
+      Fault::DuplicateRow { row } => (json!({ "type": "duplicateRow" }), row)
+    };
+    if let Some(row) = row {
+      json["row"] = json!(row);
+    }
+    json
+  }
+}
+
+/// If fault injection has been enabled with the `PACT_CSV_FAULT_INJECTION` environment variable
+pub fn fault_injection_enabled() -> bool {
+  env::var(FAULT_INJECTION_ENV).map(|value| value == "true").unwrap_or(false)
+}
+
+/// Injects the faults from the options into the CSV data, in the order they are defined. Rows that
+/// are not given are chosen with the random number generator, so the same faults are injected
+/// when the `seed` option is set. Returns the data and a description of each injected fault.
+pub fn inject_faults(data: &[u8], options: &CsvOptions, rng: &mut StdRng) -> anyhow::Result<(Vec<u8>, Vec<String>)> {
+  // The data was generated with the quote from the options, so it has to be read back with it
+  let delimiter = options.writer_delimiter()?;
+  let quote = options.actual_quote();
+  check_field_sizes(data, delimiter, quote, options.max_field_size)?;
+  let mut rdr = ReaderBuilder::new().has_headers(options.has_headers).delimiter(delimiter).quote(quote).from_reader(data);
+  let headers = if options.has_headers { Some(rdr.headers()?.clone()) } else { None };
+  let mut rows = rdr.records().collect::<Result<Vec<_>, _>>()?
+    .iter()
+    .map(|record| record.iter().map(|field| field.to_string()).collect::<Vec<_>>())
+    .collect::<Vec<_>>();
+
+  let mut injected = vec![];
+  for fault in &options.fault_injection {
+    let selected_row = match fault {
+      Fault::ChangeValue { row, .. } | Fault::DropRow { row } | Fault::DuplicateRow { row } => *row
+    };
+    let row = match selected_row {
+      Some(row) if row > rows.len() => return Err(anyhow!("Can not inject fault {} as the contents only have {} rows",
+        fault.to_json(), rows.len())),
+      Some(row) => row,
+      None if rows.is_empty() => return Err(anyhow!("Can not inject fault {} as the contents have no rows", fault.to_json())),
+      None => rng.gen_range(1..=rows.len())
+    };
+    match fault {
+      Fault::ChangeValue { column, .. } => {
+        let index = match column {
+          Either::Left(index) => index - 1,
+          Either::Right(header) => headers.as_ref()
+            .and_then(|headers| headers.iter().position(|h| h == header))
+            .ok_or_else(|| anyhow!("Can not inject fault {} as there is no column '{}'", fault.to_json(), header))?
+        };
+        let value = rows[row - 1].get_mut(index)
+          .ok_or_else(|| anyhow!("Can not inject fault {} as row {} has no column {}", fault.to_json(), row, index + 1))?;
+        let changed = changed_value(value);
+        injected.push(format!("changed the value of column {} in row {} from '{}' to '{}'",
+          column.clone().either(|index| index.to_string(), |header| header), row, value, changed));
+        *value = changed;
+      }
+      Fault::DropRow { .. } => {
+        rows.remove(row - 1);
+        injected.push(format!("dropped row {}", row));
+      }
+      Fault::DuplicateRow { .. } => {
+        let duplicate = rows[row - 1].clone();
+        rows.insert(row, duplicate);
+        injected.push(format!("duplicated row {}", row));
+      }
+    }
+  }
+
+  let mut wtr = WriterBuilder::new().delimiter(delimiter).quote(quote)
+    .quote_style(options.quote_style.writer_style()).flexible(true).from_writer(vec![]);
+  if let Some(headers) = &headers {
+    wtr.write_record(headers)?;
+  }
+  for row in &rows {
+    wtr.write_record(row)?;
+  }
+  Ok((wtr.into_inner()?, injected))
+}
+
+// Integers are incremented and booleans are negated, so the value keeps its type. Other values
+// have an X appended to them.
+fn changed_value(value: &str) -> String {
+  if let Ok(number) = value.parse::<i64>() {
+    number.wrapping_add(1).to_string()
+  } else if value == "true" {
+    "false".to_string()
+  } else if value == "false" {
+    "true".to_string()
+  } else {
+    format!("{}X", value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use either::Either;
+  use expectest::prelude::*;
+  use rand::SeedableRng;
+  use rand::rngs::StdRng;
+  use serde_json::json;
+
+  use crate::options::CsvOptions;
+
+  use super::{Fault, inject_faults};
+
+  #[test]
+  fn fault_json_test() {
+    let fault = Fault::from_json(&json!({ "type": "changeValue", "column": "column:name", "row": 2 })).unwrap();
+    expect!(fault.clone()).to(be_equal_to(Fault::ChangeValue { column: Either::Right("name".to_string()), row: Some(2) }));
+    expect!(Fault::from_json(&fault.to_json()).unwrap()).to(be_equal_to(fault));
+    expect!(Fault::from_json(&json!({ "type": "dropRow" })).unwrap()).to(be_equal_to(Fault::DropRow { row: None }));
+    expect!(Fault::from_json(&json!({ "type": "dropRow", "row": 0 }))).to(be_err());
+    expect!(Fault::from_json(&json!({ "type": "changeValue" }))).to(be_err());
+    expect!(Fault::from_json(&json!({ "type": "other" }))).to(be_err());
+  }
+
+  #[test]
+  fn inject_faults_test() {
+    let options = CsvOptions {
+      fault_injection: vec![
+        Fault::ChangeValue { column: Either::Right("id".to_string()), row: Some(1) },
+        Fault::ChangeValue { column: Either::Left(2), row: Some(2) },
+        Fault::DuplicateRow { row: Some(3) },
+        Fault::DropRow { row: Some(2) }
+      ],
+      .. CsvOptions::default()
+    };
+    let data = "id,name\n1,a\n2,b\n3,c\n".as_bytes();
+    let (result, injected) = inject_faults(data, &options, &mut StdRng::seed_from_u64(1)).unwrap();
+    expect!(String::from_utf8(result).unwrap()).to(be_equal_to("id,name\n2,a\n3,c\n3,c\n"));
+    expect!(injected).to(be_equal_to(vec![
+      "changed the value of column id in row 1 from '1' to '2'".to_string(),
+      "changed the value of column 2 in row 2 from 'b' to 'bX'".to_string(),
+      "duplicated row 3".to_string(),
+      "dropped row 2".to_string()
+    ]));
+
+    let options = CsvOptions { fault_injection: vec![Fault::DropRow { row: Some(4) }], .. CsvOptions::default() };
+    expect!(inject_faults(data, &options, &mut StdRng::seed_from_u64(1))).to(be_err());
+  }
+
+  #[test]
+  fn inject_faults_reads_the_data_with_the_quote_from_the_options() {
+    let options = CsvOptions {
+      quote: b'\'',
+      fault_injection: vec![Fault::ChangeValue { column: Either::Left(1), row: Some(1) }],
+      .. CsvOptions::default()
+    };
+    let data = "id,name\n1,'Smith, John'\n".as_bytes();
+    let (result, _) = inject_faults(data, &options, &mut StdRng::seed_from_u64(1)).unwrap();
+    expect!(String::from_utf8(result).unwrap()).to(be_equal_to("id,name\n2,'Smith, John'\n"));
+  }
+
+  #[test]
+  fn inject_faults_chooses_the_same_rows_with_the_seed() {
+    let options = CsvOptions { fault_injection: vec![Fault::DropRow { row: None }], .. CsvOptions::default() };
+    let data = "id\n1\n2\n3\n4\n5\n".as_bytes();
+    let first = inject_faults(data, &options, &mut StdRng::seed_from_u64(7)).unwrap();
+    let second = inject_faults(data, &options, &mut StdRng::seed_from_u64(7)).unwrap();
+    expect!(first.0.len()).to(be_equal_to(data.len() - 2));
+    expect!(first).to(be_equal_to(second));
+  }
+}
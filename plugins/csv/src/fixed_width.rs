@@ -0,0 +1,83 @@
+use anyhow::anyhow;
+use csv::{ReaderBuilder, Writer};
+
+/// Splits each line of fixed-width content into fields at the given column widths, and returns
+/// the fields encoded as CSV so the content can be processed with the normal CSV reader. Padding
+/// at the end of each field is removed. The widths are in characters, not bytes, so content with
+/// multi-byte UTF-8 characters is split correctly.
+pub fn fixed_width_to_csv(data: &[u8], widths: &[usize]) -> anyhow::Result<Vec<u8>> {
+  let content = std::str::from_utf8(data)?;
+  let total_width: usize = widths.iter().sum();
+  let mut wtr = Writer::from_writer(vec![]);
+
+  for (index, line) in content.lines().enumerate() {
+    // Byte offset of each character, with the length of the line at the end
+    let offsets = line.char_indices().map(|(offset, _)| offset)
+      .chain(std::iter::once(line.len()))
+      .collect::<Vec<_>>();
+    let char_count = offsets.len() - 1;
+    let byte_offset = |chars: usize| offsets[chars.min(char_count)];
+
+    if !line[byte_offset(total_width)..].trim().is_empty() {
+      return Err(anyhow!("Line {} is longer than the total width of the columns ({})", index + 1, total_width));
+    }
+
+    let mut start = 0;
+    let mut fields = vec![];
+    for width in widths {
+      let end = start + width;
+      fields.push(line[byte_offset(start)..byte_offset(end)].trim_end());
+      start = end;
+    }
+    wtr.write_record(&fields)?;
+  }
+
+  Ok(wtr.into_inner()?)
+}
+
+/// Writes each record of the CSV content as a fixed-width line, padding or truncating the fields
+/// to the given column widths.
+pub fn csv_to_fixed_width(data: &[u8], widths: &[usize]) -> anyhow::Result<Vec<u8>> {
+  let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(data);
+  let mut output = String::new();
+
+  for record in rdr.records() {
+    let record = record?;
+    for (col, width) in widths.iter().enumerate() {
+      let field = record.get(col).unwrap_or_default();
+      let field = match field.char_indices().nth(*width) {
+        Some((offset, _)) => &field[..offset],
+        None => field
+      };
+      output.push_str(format!("{:width$}", field, width = width).as_str());
+    }
+    output.push('\n');
+  }
+
+  Ok(output.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+
+  use super::{csv_to_fixed_width, fixed_width_to_csv};
+
+  #[test]
+  fn fixed_width_to_csv_test() {
+    let data = "1   José      Zürich\n22  Bob       Paris \n";
+    expect!(String::from_utf8(fixed_width_to_csv(data.as_bytes(), &[4, 10, 6]).unwrap()).unwrap())
+      .to(be_equal_to("1,José,Zürich\n22,Bob,Paris\n"));
+    expect!(String::from_utf8(fixed_width_to_csv("ñ\n".as_bytes(), &[4, 2]).unwrap()).unwrap())
+      .to(be_equal_to("ñ,\n"));
+    expect!(fixed_width_to_csv("1   Françoise Zürich\n".as_bytes(), &[4, 8, 6])).to(be_err());
+  }
+
+  #[test]
+  fn csv_to_fixed_width_test() {
+    expect!(String::from_utf8(csv_to_fixed_width("1,José,Zürich\n".as_bytes(), &[4, 10, 6]).unwrap()).unwrap())
+      .to(be_equal_to("1   José      Zürich\n"));
+    expect!(String::from_utf8(csv_to_fixed_width("1,Françoise,Zürich\n".as_bytes(), &[2, 4, 3]).unwrap()).unwrap())
+      .to(be_equal_to("1 FranZür\n"));
+  }
+}
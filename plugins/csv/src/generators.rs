@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use pact_models::generators::{GenerateValue, Generator, NoopVariantMatcher, UuidFormat, VariantMatcher};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde_json::{json, Value};
+use uuid::{Builder, Uuid};
+
+/// Types of the generators provided by this plugin, as used in the generator spec and in the
+/// `generate(...)` column definitions
+pub const CSV_GENERATOR_TYPES: [&str; 3] = ["sequence", "rowIndex", "weighted"];
+
+/// Generators provided by this plugin, in addition to the Pact generators. As with the plugin
+/// matching rules, these are stored in the interaction plugin configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvGenerator {
+  /// Incrementing integer, starting at `start` for the first row and increasing by `step` for
+  /// each row after that
+  Sequence { start: i64, step: i64 },
+  /// Random choice from the values, where each value is chosen in proportion to its weight
+  Weighted { choices: Vec<(String, u32)> }
+}
+
+impl CsvGenerator {
+  /// Name of the generator
+  pub fn name(&self) -> &'static str {
+    match self {
+      CsvGenerator::Sequence { .. } => "sequence",
+      CsvGenerator::Weighted { .. } => "weighted"
+    }
+  }
+
+  /// Builds the generator from its stored JSON form
+  pub fn from_json(json: &Value) -> anyhow::Result<CsvGenerator> {
+    match json.get("type").and_then(Value::as_str) {
+      Some("sequence") => Ok(CsvGenerator::Sequence {
+        start: json.get("start").and_then(Value::as_i64).unwrap_or(1),
+        step: json.get("step").and_then(Value::as_i64).unwrap_or(1)
+      }),
+      Some("rowIndex") => CsvGenerator::row_index(json.get("base").and_then(Value::as_i64).unwrap_or(1)),
+      Some("weighted") => {
+        let choices = match json.get("weights") {
+          Some(Value::Array(weights)) => weights.iter().map(|choice| {
+            let value = choice.get("value").and_then(Value::as_str)
+              .ok_or_else(|| anyhow!("Weighted generator choice {} must have a value", choice))?;
+            let weight = choice.get("weight").and_then(Value::as_u64)
+              .ok_or_else(|| anyhow!("Weighted generator choice {} must have a weight", choice))?;
+            Ok((value.to_string(), u32::try_from(weight)?))
+          }).collect::<anyhow::Result<Vec<_>>>()?,
+          _ => return Err(anyhow!("Weighted generator JSON {} must have a list of weights", json))
+        };
+        CsvGenerator::weighted(choices)
+      }
+      Some(name) => Err(anyhow!("'{}' is not a known CSV generator", name)),
+      None => Err(anyhow!("CSV generator JSON {} is missing the 'type' attribute", json))
+    }
+  }
+
+  /// Converts the generator to the JSON form stored in the plugin configuration
+  pub fn to_json(&self) -> Value {
+    match self {
+      CsvGenerator::Sequence { start, step } => json!({ "type": self.name(), "start": start, "step": step }),
+      CsvGenerator::Weighted { choices } => json!({
+        "type": self.name(),
+        "weights": choices.iter()
+          .map(|(value, weight)| json!({ "value": value, "weight": weight }))
+          .collect::<Vec<_>>()
+      })
+    }
+  }
+
+  /// Creates the generator for the index of the row, which is a sequence starting at the base (0 or
+  /// 1) for the first data row
+  pub fn row_index(base: i64) -> anyhow::Result<CsvGenerator> {
+    if base == 0 || base == 1 {
+      Ok(CsvGenerator::Sequence { start: base, step: 1 })
+    } else {
+      Err(anyhow!("The row index generator base must be 0 or 1, got {}", base))
+    }
+  }
+
+  /// Creates a weighted generator, checking that there is at least one choice and that the
+  /// weights are not all zero
+  pub fn weighted(choices: Vec<(String, u32)>) -> anyhow::Result<CsvGenerator> {
+    if choices.is_empty() {
+      Err(anyhow!("The weighted generator requires at least one value with a weight"))
+    } else if choices.iter().map(|(_, weight)| *weight as u64).sum::<u64>() == 0 {
+      Err(anyhow!("The weighted generator requires at least one value with a weight greater than zero"))
+    } else {
+      Ok(CsvGenerator::Weighted { choices })
+    }
+  }
+
+  /// Generates the value for the data row with the given index (starting at 0). Rows are
+  /// numbered in the order they are written to the generated content. Random values are taken
+  /// from the random number generator, which is seeded if the `seed` option is set. Fails if
+  /// the value of a sequence is too large for a 64 bit integer.
+  pub fn generate_value(&self, row: usize, rng: &mut StdRng) -> anyhow::Result<String> {
+    match self {
+      CsvGenerator::Sequence { start, step } => i64::try_from(row).ok()
+        .and_then(|row| step.checked_mul(row))
+        .and_then(|offset| start.checked_add(offset))
+        .map(|value| value.to_string())
+        .ok_or_else(|| anyhow!("The sequence generator (start: {}, step: {}) overflows at data row {}", start, step, row + 1)),
+      CsvGenerator::Weighted { choices } => {
+        let total = choices.iter().map(|(_, weight)| *weight as u64).sum::<u64>();
+        let mut remaining = rng.gen_range(0..total);
+        for (value, weight) in choices {
+          if remaining < *weight as u64 {
+            return Ok(value.clone());
+          }
+          remaining -= *weight as u64;
+        }
+        Ok(choices.last().map(|(value, _)| value.clone()).unwrap_or_default())
+      }
+    }
+  }
+}
+
+/// Generator for a column of a generation spec, either one of the Pact generators or one provided
+/// by this plugin
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpecGenerator {
+  Pact(Generator),
+  Plugin(CsvGenerator)
+}
+
+/// Column of a generation spec
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSpec {
+  /// Name of the column, used for the header row
+  pub name: String,
+  /// Generator for the values of the column
+  pub generator: SpecGenerator
+}
+
+/// Specification used to generate the contents without a template, i.e.
+/// `{"count": 10, "columns": [{"name": "id", "type": "sequence"}, {"name": "code", "type": "RandomString", "size": 5}]}`.
+/// The other attributes of a column are the parameters for the generator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationSpec {
+  /// Columns to generate
+  pub columns: Vec<ColumnSpec>,
+  /// Number of rows to generate
+  pub count: usize
+}
+
+impl GenerationSpec {
+  /// Builds the spec from its JSON form
+  pub fn from_json(json: &Value) -> anyhow::Result<GenerationSpec> {
+    let count = json.get("count").and_then(Value::as_u64)
+      .ok_or_else(|| anyhow!("Generation spec {} must have a count of the rows to generate", json))? as usize;
+    let columns = match json.get("columns") {
+      Some(Value::Array(columns)) if !columns.is_empty() => columns.iter().map(|column| {
+        let name = column.get("name").and_then(Value::as_str)
+          .ok_or_else(|| anyhow!("Generation spec column {} must have a name", column))?;
+        let generator_type = column.get("type").and_then(Value::as_str)
+          .ok_or_else(|| anyhow!("Generation spec column '{}' must have a generator type", name))?;
+        let generator = if CSV_GENERATOR_TYPES.contains(&generator_type) {
+          SpecGenerator::Plugin(CsvGenerator::from_json(column)?)
+        } else {
+          let params = column.as_object().cloned().unwrap_or_default().into_iter()
+            .filter(|(key, _)| key != "name" && key != "type")
+            .collect();
+          check_generator_params(generator_type, &params)?;
+          SpecGenerator::Pact(Generator::from_map(generator_type, &params)
+            .ok_or_else(|| anyhow!("Generation spec column '{}' has an unknown generator type '{}'", name, generator_type))?)
+        };
+        Ok(ColumnSpec { name: name.to_string(), generator })
+      }).collect::<anyhow::Result<Vec<_>>>()?,
+      _ => return Err(anyhow!("Generation spec {} must have a list of columns", json))
+    };
+    Ok(GenerationSpec { columns, count })
+  }
+
+  /// Converts the spec to the JSON form stored in the plugin configuration
+  pub fn to_json(&self) -> Value {
+    json!({
+      "count": self.count,
+      "columns": self.columns.iter().map(|column| {
+        let mut json = match &column.generator {
+          SpecGenerator::Pact(generator) => generator.to_json().unwrap_or_else(|| json!({ "type": generator.name() })),
+          SpecGenerator::Plugin(generator) => generator.to_json()
+        };
+        json["name"] = json!(column.name);
+        json
+      }).collect::<Vec<_>>()
+    })
+  }
+
+  /// Generates the header and rows from the spec
+  pub fn generate(&self, rng: &mut StdRng) -> anyhow::Result<(Vec<String>, Vec<Vec<String>>)> {
+    let header = self.columns.iter().map(|column| column.name.clone()).collect();
+    let rows = (0..self.count).map(|row| {
+      self.columns.iter().map(|column| match &column.generator {
+        SpecGenerator::Pact(generator) => generate_pact_value(generator, "", rng),
+        SpecGenerator::Plugin(generator) => generator.generate_value(row, rng)
+      }).collect::<anyhow::Result<Vec<_>>>()
+    }).collect::<anyhow::Result<Vec<_>>>()?;
+    Ok((header, rows))
+  }
+}
+
+/// Generates a value with the Pact generator. The random generators (`RandomInt`, `RandomDecimal`,
+/// `RandomHexadecimal`, `RandomString`, `RandomBoolean` and `Uuid`) use the random number generator,
+/// so they generate the same values when the `seed` option is set. The other generators are not
+/// random, and are generated by Pact.
+pub fn generate_pact_value(generator: &Generator, value: &str, rng: &mut StdRng) -> anyhow::Result<String> {
+  match generator {
+    Generator::RandomInt(min, max) => Ok(rng.gen_range(*min..max.saturating_add(1)).to_string()),
+    Generator::RandomDecimal(digits) => Ok(random_decimal(*digits as usize, rng)),
+    Generator::RandomHexadecimal(digits) => Ok((0..*digits)
+      .map(|_| HEX_DIGITS[rng.gen_range(0..HEX_DIGITS.len())] as char)
+      .collect()),
+    Generator::RandomString(size) => Ok(rng.sample_iter(&Alphanumeric).take(*size as usize).map(char::from).collect()),
+    Generator::RandomBoolean => Ok(rng.gen::<bool>().to_string()),
+    Generator::Uuid(format) => {
+      let uuid = Builder::from_random_bytes(rng.gen()).into_uuid();
+      Ok(match format.unwrap_or_default() {
+        UuidFormat::Simple => uuid.as_simple().to_string(),
+        UuidFormat::LowerCaseHyphenated => uuid.as_hyphenated().to_string(),
+        UuidFormat::UpperCaseHyphenated => uuid.as_hyphenated().to_string().to_uppercase(),
+        UuidFormat::Urn => uuid.as_urn().to_string()
+      })
+    }
+    _ => generator.generate_value(&value.to_string(), &HashMap::new(), &NoopVariantMatcher.boxed())
+  }
+}
+
+const HEX_DIGITS: &[u8] = b"0123456789ABCDEF";
+
+// Decimal number with the number of digits, where the integer part does not start with a zero
+fn random_decimal(digits: usize, rng: &mut StdRng) -> String {
+  match digits {
+    0 => String::default(),
+    1 => rng.gen_range(0..10).to_string(),
+    _ => {
+      let point = rng.gen_range(1..digits);
+      let mut value = String::with_capacity(digits + 1);
+      for index in 0..digits {
+        if index == point {
+          value.push('.');
+        }
+        let digit = if index == 0 && point > 1 { rng.gen_range(1..10) } else { rng.gen_range(0..10) };
+        value.push(char::from_digit(digit, 10).unwrap_or('0'));
+      }
+      value
+    }
+  }
+}
+
+/// Parameters that must be provided for each of the Pact generator types. Generators not listed
+/// here can be created without any parameters.
+pub fn required_generator_params(generator_type: &str) -> &'static [&'static str] {
+  match generator_type {
+    "Regex" => &["regex"],
+    "ProviderState" | "ProviderStateGenerator" => &["expression"],
+    "MockServerURL" => &["example", "regex"],
+    "ArrayContains" => &["variants"],
+    _ => &[]
+  }
+}
+
+/// Generator for the columns of a template that do not have a generator of their own
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefaultGenerator {
+  /// Generate a random value of the same type as the value in the template
+  ByType,
+  /// Use the Pact generator for all the columns
+  Pact(Generator)
+}
+
+impl DefaultGenerator {
+  /// Builds the default generator from its JSON form, which is either `"byType"`, the name of a
+  /// Pact generator or a Pact generator with its parameters (i.e. `{"type": "RandomString", "size": 5}`)
+  pub fn from_json(json: &Value) -> anyhow::Result<DefaultGenerator> {
+    let (generator_type, params) = match json {
+      Value::String(s) if s == "byType" => return Ok(DefaultGenerator::ByType),
+      Value::String(s) => (s.as_str(), serde_json::Map::new()),
+      Value::Object(map) => match map.get("type").and_then(Value::as_str) {
+        Some(generator_type) => (generator_type, map.iter()
+          .filter(|(key, _)| *key != "type")
+          .map(|(key, value)| (key.clone(), value.clone()))
+          .collect()),
+        None => return Err(anyhow!("Default generator {} must have a generator type", json))
+      },
+      _ => return Err(anyhow!("'{}' is not a valid default generator, expected byType or a generator", json))
+    };
+    check_generator_params(generator_type, &params)?;
+    Generator::from_map(generator_type, &params)
+      .map(DefaultGenerator::Pact)
+      .ok_or_else(|| anyhow!("'{}' is not a valid default generator type", generator_type))
+  }
+
+  /// Converts the default generator into its JSON form
+  pub fn to_json(&self) -> Value {
+    match self {
+      DefaultGenerator::ByType => json!("byType"),
+      DefaultGenerator::Pact(generator) => generator.to_json().unwrap_or_default()
+    }
+  }
+
+  /// Returns the generator to use for a column with the given value in the template. Empty values
+  /// are not generated with `byType`.
+  pub fn generator_for(&self, value: &str) -> Option<Generator> {
+    match self {
+      DefaultGenerator::ByType => generator_for_type(value),
+      DefaultGenerator::Pact(generator) => Some(generator.clone())
+    }
+  }
+}
+
+// Infers the type of the value, and returns a generator for random values of that type
+fn generator_for_type(value: &str) -> Option<Generator> {
+  if value.is_empty() {
+    None
+  } else if value == "true" || value == "false" {
+    Some(Generator::RandomBoolean)
+  } else if value.parse::<i64>().is_ok() {
+    let digits = value.trim_start_matches('-').len() as u32;
+    if digits < 10 {
+      let min = if digits == 1 { 0 } else { 10_i32.pow(digits - 1) };
+      Some(Generator::RandomInt(min, 10_i32.pow(digits) - 1))
+    } else {
+      Some(Generator::Regex(format!("[1-9][0-9]{{{}}}", digits - 1)))
+    }
+  } else if value.contains('.') && value.parse::<f64>().is_ok() {
+    Some(Generator::RandomDecimal(value.chars().filter(char::is_ascii_digit).count() as u16))
+  } else if Uuid::parse_str(value).is_ok() {
+    Some(Generator::Uuid(None))
+  } else {
+    Some(Generator::RandomString(value.chars().count() as u16))
+  }
+}
+
+/// Checks that all the required parameters for the generator type have been provided
+pub fn check_generator_params(generator_type: &str, values: &serde_json::Map<String, Value>) -> anyhow::Result<()> {
+  let missing = required_generator_params(generator_type).iter()
+    .filter(|param| !values.contains_key(**param))
+    .copied()
+    .collect::<Vec<_>>();
+  if missing.is_empty() {
+    Ok(())
+  } else {
+    Err(anyhow!("Generator of type {} requires the parameters {}, but {} missing", generator_type,
+      missing.join(", "), if missing.len() == 1 { "it is" } else { "they are" }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use rand::SeedableRng;
+  use rand::rngs::StdRng;
+  use serde_json::json;
+
+  use pact_models::generators::Generator;
+
+  use super::{check_generator_params, CsvGenerator, DefaultGenerator, generate_pact_value, GenerationSpec};
+
+  #[test]
+  fn sequence_generator_test() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let generator = CsvGenerator::Sequence { start: 1, step: 1 };
+    expect!(generator.generate_value(0, &mut rng).unwrap()).to(be_equal_to("1"));
+    expect!(generator.generate_value(2, &mut rng).unwrap()).to(be_equal_to("3"));
+
+    let generator = CsvGenerator::Sequence { start: 100, step: -10 };
+    expect!(generator.generate_value(0, &mut rng).unwrap()).to(be_equal_to("100"));
+    expect!(generator.generate_value(3, &mut rng).unwrap()).to(be_equal_to("70"));
+    expect!(CsvGenerator::from_json(&generator.to_json()).unwrap()).to(be_equal_to(generator));
+
+    let generator = CsvGenerator::Sequence { start: i64::MAX - 1, step: 1 };
+    expect!(generator.generate_value(1, &mut rng).unwrap()).to(be_equal_to(i64::MAX.to_string()));
+    expect!(generator.generate_value(2, &mut rng)).to(be_err());
+    let generator = CsvGenerator::Sequence { start: 0, step: i64::MIN };
+    expect!(generator.generate_value(2, &mut rng)).to(be_err());
+  }
+
+  #[test]
+  fn row_index_generator_test() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let generator = CsvGenerator::from_json(&json!({ "type": "rowIndex", "base": 0 })).unwrap();
+    expect!((0..3).map(|row| generator.generate_value(row, &mut rng).unwrap()).collect::<Vec<_>>())
+      .to(be_equal_to(vec!["0".to_string(), "1".to_string(), "2".to_string()]));
+    expect!(CsvGenerator::from_json(&json!({ "type": "rowIndex" })).unwrap())
+      .to(be_equal_to(CsvGenerator::Sequence { start: 1, step: 1 }));
+    expect!(CsvGenerator::row_index(2)).to(be_err());
+  }
+
+  #[test]
+  fn weighted_generator_test() {
+    let generator = CsvGenerator::weighted(vec![("A".to_string(), 1), ("B".to_string(), 0)]).unwrap();
+    let mut rng = StdRng::seed_from_u64(1);
+    expect!((0..10).all(|row| generator.generate_value(row, &mut rng).unwrap() == "A")).to(be_true());
+    expect!(CsvGenerator::from_json(&generator.to_json()).unwrap()).to(be_equal_to(generator));
+
+    expect!(CsvGenerator::weighted(vec![])).to(be_err());
+    expect!(CsvGenerator::weighted(vec![("A".to_string(), 0)])).to(be_err());
+    expect!(CsvGenerator::from_json(&json!({ "type": "weighted", "weights": [{ "value": "A", "weight": -1 }] }))).to(be_err());
+    expect!(CsvGenerator::from_json(&json!({ "type": "weighted" }))).to(be_err());
+  }
+
+  #[test]
+  fn default_generator_test() {
+    let by_type = DefaultGenerator::from_json(&json!("byType")).unwrap();
+    expect!(by_type.generator_for("42")).to(be_some().value(Generator::RandomInt(10, 99)));
+    expect!(by_type.generator_for("7")).to(be_some().value(Generator::RandomInt(0, 9)));
+    expect!(by_type.generator_for("12345678901")).to(be_some().value(Generator::Regex("[1-9][0-9]{10}".to_string())));
+    expect!(by_type.generator_for("12.50")).to(be_some().value(Generator::RandomDecimal(4)));
+    expect!(by_type.generator_for("true")).to(be_some().value(Generator::RandomBoolean));
+    expect!(by_type.generator_for("6c6a6e1b-2ac3-4cf2-8a5a-0f2e6e4b3c1d")).to(be_some().value(Generator::Uuid(None)));
+    expect!(by_type.generator_for("José")).to(be_some().value(Generator::RandomString(4)));
+    expect!(by_type.generator_for("")).to(be_none());
+    expect!(DefaultGenerator::from_json(&by_type.to_json()).unwrap()).to(be_equal_to(by_type));
+
+    let pact = DefaultGenerator::from_json(&json!({ "type": "RandomString", "size": 3 })).unwrap();
+    expect!(pact.generator_for("")).to(be_some().value(Generator::RandomString(3)));
+    expect!(DefaultGenerator::from_json(&pact.to_json()).unwrap()).to(be_equal_to(pact));
+    expect!(DefaultGenerator::from_json(&json!("Uuid")).unwrap()).to(be_equal_to(DefaultGenerator::Pact(Generator::Uuid(None))));
+
+    expect!(DefaultGenerator::from_json(&json!("Regex"))).to(be_err());
+    expect!(DefaultGenerator::from_json(&json!("Other"))).to(be_err());
+    expect!(DefaultGenerator::from_json(&json!({ "size": 3 }))).to(be_err());
+    expect!(DefaultGenerator::from_json(&json!(10))).to(be_err());
+  }
+
+  #[test]
+  fn check_generator_params_test() {
+    let empty = serde_json::Map::new();
+    expect!(check_generator_params("Uuid", &empty)).to(be_ok());
+    expect!(check_generator_params("RandomInt", &empty)).to(be_ok());
+    expect!(check_generator_params("Regex", &empty)).to(be_err());
+
+    let values = json!({ "regex": "\\d+" }).as_object().cloned().unwrap();
+    expect!(check_generator_params("Regex", &values)).to(be_ok());
+    expect!(check_generator_params("MockServerURL", &values)).to(be_err());
+  }
+
+  #[test]
+  fn generation_spec_test() {
+    let spec = GenerationSpec::from_json(&json!({
+      "count": 3,
+      "columns": [
+        { "name": "id", "type": "sequence", "start": 10 },
+        { "name": "code", "type": "RandomString", "size": 4 }
+      ]
+    })).unwrap();
+    expect!(GenerationSpec::from_json(&spec.to_json()).unwrap()).to(be_equal_to(spec.clone()));
+
+    let (header, rows) = spec.generate(&mut StdRng::from_entropy()).unwrap();
+    expect!(header).to(be_equal_to(vec!["id".to_string(), "code".to_string()]));
+    expect!(rows.iter().map(|row| row[0].clone()).collect::<Vec<_>>())
+      .to(be_equal_to(vec!["10".to_string(), "11".to_string(), "12".to_string()]));
+    expect!(rows.iter().all(|row| row[1].len() == 4)).to(be_true());
+
+    expect!(GenerationSpec::from_json(&json!({ "count": 3, "columns": [{ "name": "a", "type": "Regex" }] }))).to(be_err());
+    expect!(GenerationSpec::from_json(&json!({ "count": 3, "columns": [{ "name": "a", "type": "Other" }] }))).to(be_err());
+    expect!(GenerationSpec::from_json(&json!({ "columns": [{ "name": "a", "type": "Uuid" }] }))).to(be_err());
+  }
+
+  #[test]
+  fn generate_pact_value_uses_the_random_number_generator() {
+    let generate = |generator: &Generator, seed: u64| generate_pact_value(generator, "", &mut StdRng::seed_from_u64(seed)).unwrap();
+    for generator in [Generator::RandomInt(0, 1000000), Generator::RandomDecimal(8), Generator::RandomHexadecimal(8),
+      Generator::RandomString(8), Generator::Uuid(None)] {
+      expect!(generate(&generator, 1)).to(be_equal_to(generate(&generator, 1)));
+      expect!(generate(&generator, 1)).to_not(be_equal_to(generate(&generator, 2)));
+    }
+
+    let int = generate(&Generator::RandomInt(10, 20), 1).parse::<i32>().unwrap();
+    expect!((10..=20).contains(&int)).to(be_true());
+    let decimal = generate(&Generator::RandomDecimal(8), 1);
+    expect!(decimal.len()).to(be_equal_to(9));
+    expect!(decimal.parse::<f64>()).to(be_ok());
+    expect!(generate(&Generator::RandomHexadecimal(4), 1).chars().all(|ch| ch.is_ascii_hexdigit())).to(be_true());
+    expect!(generate(&Generator::RandomString(5), 1).len()).to(be_equal_to(5));
+    expect!(["true", "false"].contains(&generate(&Generator::RandomBoolean, 1).as_str())).to(be_true());
+    expect!(uuid::Uuid::parse_str(&generate(&Generator::Uuid(None), 1))).to(be_ok());
+    expect!(generate(&Generator::Regex("[a-c]{3}".to_string()), 1).len()).to(be_equal_to(3));
+  }
+}
@@ -0,0 +1,61 @@
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use log::debug;
+
+/// Environment variable with the directory golden files are loaded from. Defaults to the current
+/// working directory of the plugin.
+pub const GOLDEN_DIR_ENV: &str = "PACT_CSV_GOLDEN_DIR";
+
+/// Loads the golden file with the given relative path, to be used as the expected contents
+pub fn load_golden_file(path: &str) -> anyhow::Result<Vec<u8>> {
+  let dir = match env::var(GOLDEN_DIR_ENV) {
+    Ok(dir) => PathBuf::from(dir),
+    Err(_) => env::current_dir().context("Failed to get the current directory to load the golden file from")?
+  };
+  read_golden_file(&dir, path)
+}
+
+// Golden files must be relative to the golden file directory, and can not refer to files outside
+// of it
+fn read_golden_file(dir: &Path, path: &str) -> anyhow::Result<Vec<u8>> {
+  let relative = Path::new(path);
+  if path.is_empty() || !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+    return Err(anyhow!("'{}' is not a valid golden file, it must be a relative path within the {} directory",
+      path, GOLDEN_DIR_ENV));
+  }
+
+  let file = dir.join(relative);
+  if !file.is_file() {
+    return Err(anyhow!("Golden file '{}' was not found, there is no file {} in {}", path, file.display(), dir.display()));
+  }
+  debug!("Loading the expected contents from golden file {}", file.display());
+  fs::read(&file).with_context(|| format!("Failed to read golden file {}", file.display()))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use expectest::prelude::*;
+
+  use super::read_golden_file;
+
+  #[test]
+  fn read_golden_file_test() {
+    let dir = std::env::temp_dir().join(format!("pact-csv-golden-{}", std::process::id()));
+    fs::create_dir_all(dir.join("orders")).unwrap();
+    fs::write(dir.join("orders/expected.csv"), "id,name\n1,a\n").unwrap();
+
+    expect!(read_golden_file(&dir, "orders/expected.csv").unwrap()).to(be_equal_to(b"id,name\n1,a\n".to_vec()));
+    expect!(read_golden_file(&dir, "orders/other.csv")).to(be_err());
+    expect!(read_golden_file(&dir, "orders")).to(be_err());
+    expect!(read_golden_file(&dir, "../expected.csv")).to(be_err());
+    expect!(read_golden_file(&dir, "orders/../orders/expected.csv")).to(be_err());
+    expect!(read_golden_file(&dir, dir.join("orders/expected.csv").to_str().unwrap())).to(be_err());
+    expect!(read_golden_file(&dir, "")).to(be_err());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
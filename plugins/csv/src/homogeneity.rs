@@ -0,0 +1,150 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use csv::StringRecord;
+
+use crate::categories::MismatchCategory;
+use crate::constraints::display_name;
+use crate::options::CsvOptions;
+use crate::proto;
+
+/// Type of a value, as inferred for the columns that must only have values of one type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind {
+  Integer,
+  Decimal,
+  Boolean,
+  Date,
+  DateTime,
+  Text
+}
+
+impl ValueKind {
+  /// Infers the type of the value. Empty values do not have a type.
+  pub fn infer(value: &str) -> Option<ValueKind> {
+    let value = value.trim();
+    if value.is_empty() {
+      None
+    } else if value.strip_prefix(['-', '+']).unwrap_or(value).chars().all(|ch| ch.is_ascii_digit())
+      && value.chars().any(|ch| ch.is_ascii_digit()) {
+      Some(ValueKind::Integer)
+    } else if value.chars().any(|ch| ch.is_ascii_digit()) && BigDecimal::from_str(value).is_ok() {
+      Some(ValueKind::Decimal)
+    } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+      Some(ValueKind::Boolean)
+    } else if NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+      Some(ValueKind::Date)
+    } else if DateTime::parse_from_rfc3339(value).is_ok()
+      || NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+      || NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").is_ok() {
+      Some(ValueKind::DateTime)
+    } else {
+      Some(ValueKind::Text)
+    }
+  }
+
+  /// Description of a single value of the type
+  pub fn description(&self) -> &'static str {
+    match self {
+      ValueKind::Integer => "an integer",
+      ValueKind::Decimal => "a decimal number",
+      ValueKind::Boolean => "a boolean",
+      ValueKind::Date => "a date",
+      ValueKind::DateTime => "a date/time",
+      ValueKind::Text => "text"
+    }
+  }
+}
+
+/// The types of the actual values of a homogeneous column, collected to check that they are all the
+/// same type once all the rows have been read
+#[derive(Debug, Clone, PartialEq)]
+pub struct HomogeneityCheck {
+  /// Index and header of the expected column
+  pub index: usize,
+  pub header: String,
+  /// Type of the first value, and the number of the row it was in
+  first: Option<(ValueKind, u64)>,
+  /// First value with a different type, its type and the number of the row it was in
+  change: Option<(ValueKind, u64, String)>
+}
+
+impl HomogeneityCheck {
+  /// Creates the checks for each of the expected columns that are homogeneous
+  pub fn for_columns(options: &CsvOptions, expected_headers: &StringRecord, columns: usize) -> Vec<HomogeneityCheck> {
+    (0..columns)
+      .filter_map(|index| {
+        let header = expected_headers.get(index).unwrap_or_default();
+        options.column(index + 1, header)
+          .filter(|column| column.homogeneous)
+          .map(|_| HomogeneityCheck { index, header: header.to_string(), first: None, change: None })
+      })
+      .collect()
+  }
+
+  /// Adds the value of the column from an actual row. Only the first change of type is kept.
+  pub fn add_value(&mut self, row: u64, value: &str) {
+    if self.change.is_some() {
+      return;
+    }
+    if let Some(kind) = ValueKind::infer(value) {
+      match self.first {
+        None => self.first = Some((kind, row)),
+        Some((first, _)) if first != kind => self.change = Some((kind, row, value.to_string())),
+        Some(_) => {}
+      }
+    }
+  }
+
+  /// Reports the first row where the type of the values changed
+  pub fn compare(&self, options: &CsvOptions, results: &mut Vec<proto::ContentMismatch>) {
+    if let (Some((first, first_row)), Some((kind, row, value))) = (&self.first, &self.change) {
+      results.push(options.categorise(MismatchCategory::Aggregate, proto::ContentMismatch {
+        expected: Some(first.description().as_bytes().to_vec()),
+        actual: Some(value.as_bytes().to_vec()),
+        mismatch: format!("Expected all the values of column {} to be the same type as row {} ({}), but row {} has {} '{}'",
+          display_name(self.index, &self.header), first_row, first.description(), row, kind.description(), value),
+        path: format!("row:{:5}, column:{:2}", row, self.index),
+        diff: String::default()
+      }));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+
+  use crate::options::CsvOptions;
+
+  use super::{HomogeneityCheck, ValueKind};
+
+  #[test]
+  fn infer_test() {
+    expect!(ValueKind::infer("")).to(be_none());
+    expect!(ValueKind::infer(" ")).to(be_none());
+    expect!(ValueKind::infer("-12")).to(be_some().value(ValueKind::Integer));
+    expect!(ValueKind::infer("12.50")).to(be_some().value(ValueKind::Decimal));
+    expect!(ValueKind::infer("1e5")).to(be_some().value(ValueKind::Decimal));
+    expect!(ValueKind::infer("TRUE")).to(be_some().value(ValueKind::Boolean));
+    expect!(ValueKind::infer("2023-01-31")).to(be_some().value(ValueKind::Date));
+    expect!(ValueKind::infer("2023-01-31T10:00:00Z")).to(be_some().value(ValueKind::DateTime));
+    expect!(ValueKind::infer("2023-01-31 10:00:00")).to(be_some().value(ValueKind::DateTime));
+    expect!(ValueKind::infer("-")).to(be_some().value(ValueKind::Text));
+    expect!(ValueKind::infer("e")).to(be_some().value(ValueKind::Text));
+  }
+
+  #[test]
+  fn compare_reports_the_first_change_of_type() {
+    let mut check = HomogeneityCheck { index: 1, header: "amount".to_string(), first: None, change: None };
+    for (row, value) in ["", "10", "20", "n/a", "2023-01-01"].iter().enumerate() {
+      check.add_value(row as u64 + 2, value);
+    }
+    let mut results = vec![];
+    check.compare(&CsvOptions::default(), &mut results);
+    expect!(results.iter().map(|mismatch| mismatch.mismatch.clone()).collect::<Vec<_>>()).to(be_equal_to(vec![
+      "Expected all the values of column amount to be the same type as row 3 (an integer), but row 5 has text 'n/a'".to_string()
+    ]));
+  }
+}
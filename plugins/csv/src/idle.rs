@@ -0,0 +1,99 @@
+use std::env;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Environment variable with the number of seconds the plugin can be idle for before it shuts down
+pub const IDLE_TIMEOUT_ENV: &str = "PACT_CSV_IDLE_TIMEOUT";
+
+/// Keeps track of when the last request was received, so the plugin can shut down once it has not
+/// been used for the idle timeout. Clones share the time of the last request.
+#[derive(Debug, Clone)]
+pub struct IdleTimer {
+  started: Instant,
+  /// Time of the last request, in milliseconds since the timer was started
+  last_request: Arc<AtomicU64>,
+  timeout: Option<Duration>
+}
+
+impl IdleTimer {
+  /// Create a timer with the given idle timeout. With no timeout, the plugin is never shut down.
+  pub fn new(timeout: Option<Duration>) -> Self {
+    IdleTimer {
+      started: Instant::now(),
+      last_request: Arc::new(AtomicU64::new(0)),
+      timeout
+    }
+  }
+
+  /// Reads the idle timeout from the `PACT_CSV_IDLE_TIMEOUT` environment variable. There is no
+  /// timeout if it is not set, is not a valid number of seconds or is zero.
+  pub fn from_env() -> Self {
+    let timeout = env::var(IDLE_TIMEOUT_ENV).ok()
+      .and_then(|timeout| timeout.parse::<u64>().ok())
+      .filter(|timeout| *timeout > 0)
+      .map(Duration::from_secs);
+    IdleTimer::new(timeout)
+  }
+
+  /// The idle timeout, if there is one
+  pub fn timeout(&self) -> Option<Duration> {
+    self.timeout
+  }
+
+  /// Records that a request has been received
+  pub fn touch(&self) {
+    self.last_request.store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+  }
+
+  /// Time since the last request was received (or the timer was started, if there have been none)
+  pub fn idle_time(&self) -> Duration {
+    let last_request = Duration::from_millis(self.last_request.load(Ordering::Relaxed));
+    self.started.elapsed().saturating_sub(last_request)
+  }
+
+  /// Completes once no request has been received for the idle timeout. Never completes if there
+  /// is no timeout.
+  pub async fn idle(&self) {
+    match self.timeout {
+      Some(timeout) => loop {
+        let idle_time = self.idle_time();
+        if idle_time >= timeout {
+          return;
+        }
+        tokio::time::sleep(timeout - idle_time).await;
+      },
+      None => futures::future::pending().await
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use expectest::prelude::*;
+
+  use super::IdleTimer;
+
+  #[tokio::test]
+  async fn idle_completes_after_the_timeout() {
+    let timer = IdleTimer::new(Some(Duration::from_millis(50)));
+    let handle = tokio::spawn({
+      let timer = timer.clone();
+      async move { timer.idle().await }
+    });
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    timer.touch();
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    expect!(handle.is_finished()).to(be_false());
+    expect!(tokio::time::timeout(Duration::from_secs(5), handle).await).to(be_ok());
+    expect!(timer.idle_time() >= Duration::from_millis(50)).to(be_true());
+  }
+
+  #[tokio::test]
+  async fn idle_never_completes_without_a_timeout() {
+    let timer = IdleTimer::new(None);
+    expect!(tokio::time::timeout(Duration::from_millis(20), timer.idle()).await).to(be_err());
+  }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use tonic::{Request, Status};
+use tonic::service::Interceptor;
+
+use crate::listener::ConnectionId;
+
+/// Environment variable with the maximum size (in bytes) of a request message
+pub const MAX_MESSAGE_SIZE_ENV: &str = "PACT_CSV_MAX_MESSAGE_SIZE";
+
+/// Environment variable with the maximum number of requests per second for each connection
+pub const RATE_LIMIT_ENV: &str = "PACT_CSV_RATE_LIMIT";
+
+/// Default maximum size of a request message (64 MiB)
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Default maximum size of a field of the CSV contents (16 MiB)
+pub const DEFAULT_MAX_FIELD_SIZE: usize = 16 * 1024 * 1024;
+
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Limits applied to the requests to the plugin, to protect a shared plugin instance from a
+/// misbehaving client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestLimits {
+  /// Maximum size of a request message in bytes. This is applied by the gRPC server as the messages
+  /// are decoded, so a larger message is rejected before it is read.
+  pub max_message_size: usize,
+  /// Maximum number of requests per second for each connection
+  pub rate_limit: Option<u32>
+}
+
+impl Default for RequestLimits {
+  fn default() -> Self {
+    RequestLimits {
+      max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+      rate_limit: None
+    }
+  }
+}
+
+impl RequestLimits {
+  /// Reads the limits from the `PACT_CSV_MAX_MESSAGE_SIZE` and `PACT_CSV_RATE_LIMIT` environment
+  /// variables, using the defaults for any that are not set or not valid
+  pub fn from_env() -> Self {
+    let defaults = RequestLimits::default();
+    RequestLimits {
+      max_message_size: env::var(MAX_MESSAGE_SIZE_ENV).ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(defaults.max_message_size),
+      rate_limit: env::var(RATE_LIMIT_ENV).ok()
+        .and_then(|rate| rate.parse().ok())
+        .filter(|rate| *rate > 0)
+    }
+  }
+}
+
+/// Checks that none of the fields of the CSV data are larger than the maximum field size (in
+/// bytes), before the data is read. The CSV reader buffers each record, so a single enormous
+/// quoted field would otherwise be buffered in full.
+pub fn check_field_sizes(data: &[u8], delimiter: u8, quote: u8, max_field_size: usize) -> anyhow::Result<()> {
+  let mut size = 0;
+  let mut line = 1;
+  let mut quoted = false;
+  for b in data {
+    if *b == b'\n' {
+      line += 1;
+    }
+    if quoted {
+      if *b == quote {
+        quoted = false;
+      } else {
+        size += 1;
+      }
+    } else if *b == quote {
+      quoted = true;
+    } else if *b == delimiter || *b == b'\n' || *b == b'\r' {
+      size = 0;
+    } else {
+      size += 1;
+    }
+    if size > max_field_size {
+      return Err(anyhow!("Line {} has a field that is larger than the maximum field size of {} bytes", line, max_field_size));
+    }
+  }
+  Ok(())
+}
+
+/// Interceptor that enforces the rate limit for each connection, using a one second window. The
+/// size of the requests is limited by the gRPC server instead, as the interceptor is called before
+/// the request message is read.
+#[derive(Debug, Clone)]
+pub struct RequestLimitInterceptor {
+  limits: RequestLimits,
+  windows: Arc<Mutex<RateWindows>>
+}
+
+/// Start of the current window and the number of requests in it, for each connection
+type RateWindows = HashMap<Connection, (Instant, u32)>;
+
+/// Connection a request was received on. Connections to a TCP port have the address of the client,
+/// and connections to a Unix domain socket have an ID instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Connection {
+  Address(SocketAddr),
+  Socket(ConnectionId),
+  Unknown
+}
+
+impl Connection {
+  fn of<T>(request: &Request<T>) -> Self {
+    match (request.remote_addr(), request.extensions().get::<ConnectionId>()) {
+      (Some(address), _) => Connection::Address(address),
+      (None, Some(id)) => Connection::Socket(*id),
+      (None, None) => Connection::Unknown
+    }
+  }
+}
+
+impl RequestLimitInterceptor {
+  /// Create an interceptor for the limits
+  pub fn new(limits: RequestLimits) -> Self {
+    RequestLimitInterceptor {
+      limits,
+      windows: Arc::new(Mutex::new(HashMap::new()))
+    }
+  }
+
+  fn rate_error(&self, connection: Connection, rate_limit: u32) -> Option<Status> {
+    let mut windows = match self.windows.lock() {
+      Ok(windows) => windows,
+      Err(_) => return Some(Status::internal("Rate limit lock is poisoned"))
+    };
+    let now = Instant::now();
+    windows.retain(|_, (start, _)| now.duration_since(*start) < RATE_WINDOW);
+    let (_, count) = windows.entry(connection).or_insert((now, 0));
+    *count += 1;
+    if *count > rate_limit {
+      Some(Status::unavailable(format!("The rate limit of {} requests per second has been exceeded", rate_limit)))
+    } else {
+      None
+    }
+  }
+}
+
+impl Interceptor for RequestLimitInterceptor {
+  fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+    if let Some(err) = self.limits.rate_limit.and_then(|rate_limit| self.rate_error(Connection::of(&request), rate_limit)) {
+      return Err(err);
+    }
+    Ok(request)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use tonic::{Code, Request};
+  use tonic::service::Interceptor;
+
+  use crate::listener::ConnectionId;
+
+  use super::{check_field_sizes, RequestLimitInterceptor, RequestLimits};
+
+  #[test]
+  fn check_field_sizes_test() {
+    expect!(check_field_sizes(b"id,name\n1,abcde\n", b',', b'"', 5)).to(be_ok());
+    expect!(check_field_sizes(b"id,name\n1,abcdef\n", b',', b'"', 5).unwrap_err().to_string())
+      .to(be_equal_to("Line 2 has a field that is larger than the maximum field size of 5 bytes"));
+    expect!(check_field_sizes(b"id;name\n1;\"ab,\ncd\"\n", b';', b'"', 6)).to(be_ok());
+    expect!(check_field_sizes(b"id;name\n1;\"ab,\ncde\"\n", b';', b'"', 6)).to(be_err());
+    expect!(check_field_sizes(b"id,n\n1,'a,bc'\n", b',', b'"', 3)).to(be_ok());
+    expect!(check_field_sizes(b"id,n\n1,'a,bc'\n", b',', b'\'', 3)).to(be_err());
+  }
+
+  #[test]
+  fn rate_limit_test() {
+    let mut interceptor = RequestLimitInterceptor::new(RequestLimits { max_message_size: 10, rate_limit: Some(2) });
+    expect!(interceptor.call(Request::new(()))).to(be_ok());
+    expect!(interceptor.call(Request::new(()))).to(be_ok());
+    expect!(interceptor.call(Request::new(())).unwrap_err().code()).to(be_equal_to(Code::Unavailable));
+
+    // Each connection to a socket has its own limit
+    let mut interceptor = RequestLimitInterceptor::new(RequestLimits { max_message_size: 10, rate_limit: Some(1) });
+    let request = |id: u64| {
+      let mut request = Request::new(());
+      request.extensions_mut().insert(ConnectionId(id));
+      request
+    };
+    expect!(interceptor.call(request(1))).to(be_ok());
+    expect!(interceptor.call(request(2))).to(be_ok());
+    expect!(interceptor.call(request(1)).unwrap_err().code()).to(be_equal_to(Code::Unavailable));
+
+    let mut interceptor = RequestLimitInterceptor::new(RequestLimits::default());
+    for _ in 0..10 {
+      expect!(interceptor.call(Request::new(()))).to(be_ok());
+    }
+  }
+}
@@ -0,0 +1,233 @@
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+#[cfg(unix)] use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)] use std::path::{Path, PathBuf};
+
+use futures::Stream;
+use log::warn;
+#[cfg(unix)] use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)] use tokio::net::{UnixListener, UnixStream};
+#[cfg(unix)] use tonic::transport::server::Connected;
+
+/// Environment variable with the path of a Unix domain socket for the plugin to listen on instead of
+/// a TCP port. The path can have a `{pid}` placeholder for the process ID of the plugin, so that
+/// each instance of the plugin gets its own socket.
+pub const SOCKET_ENV: &str = "PACT_CSV_SOCKET";
+
+/// Environment variable with the IP address for the plugin to listen on, i.e. `0.0.0.0` for all the
+/// interfaces when the driver connects from another host or container
+pub const HOST_ENV: &str = "PACT_CSV_HOST";
+
+/// Address the plugin listens on if it is not set, as the driver connects from the same host
+const DEFAULT_HOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// Where the gRPC server of the plugin listens for connections
+#[derive(Debug)]
+pub enum PluginListener {
+  /// TCP port
+  Tcp(TcpListener),
+  /// Unix domain socket, with its path
+  #[cfg(unix)]
+  Unix(UnixListener, PathBuf)
+}
+
+impl PluginListener {
+  /// Listens on the Unix domain socket from the `PACT_CSV_SOCKET` environment variable, or on a
+  /// random TCP port if it is not set. The port is on the loopback address, unless a different
+  /// address is set with the `PACT_CSV_HOST` environment variable. Unix domain sockets are not
+  /// supported on Windows, so there the plugin listens on a random port instead.
+  pub async fn from_env() -> anyhow::Result<Self> {
+    let host = match env::var(HOST_ENV).ok().filter(|host| !host.trim().is_empty()) {
+      Some(host) => host.trim().parse::<IpAddr>()
+        .map_err(|err| anyhow::anyhow!("{} must be an IP address, got '{}' - {}", HOST_ENV, host, err))?,
+      None => DEFAULT_HOST
+    };
+    match env::var(SOCKET_ENV).ok().filter(|path| !path.is_empty()) {
+      #[cfg(unix)]
+      Some(path) => Self::bind_socket(&path.replace("{pid}", &std::process::id().to_string())).await,
+      #[cfg(not(unix))]
+      Some(path) => {
+        warn!("Unix domain sockets are not supported on this platform, ignoring the {} value '{}' and listening on a \
+          port instead", SOCKET_ENV, path);
+        Self::bind_port(host).await
+      }
+      None => Self::bind_port(host).await
+    }
+  }
+
+  /// Listens on a random TCP port on the address
+  async fn bind_port(host: IpAddr) -> anyhow::Result<Self> {
+    Ok(PluginListener::Tcp(TcpListener::bind(SocketAddr::new(host, 0)).await?))
+  }
+
+  /// Listens on the Unix domain socket. A socket left at the path from a previous run is removed
+  /// first, but any other file at the path is not, and is an error.
+  #[cfg(unix)]
+  async fn bind_socket(path: &str) -> anyhow::Result<Self> {
+    let path = PathBuf::from(path);
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+      if !metadata.file_type().is_socket() {
+        return Err(anyhow::anyhow!("Can not listen on the socket {}, there is already a file at that path that is not a socket",
+          path.display()));
+      }
+      std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)
+      .map_err(|err| anyhow::anyhow!("Failed to listen on the socket {} - {}", path.display(), err))?;
+    Ok(PluginListener::Unix(listener, path))
+  }
+
+  /// The port for the startup message, if listening on a TCP port
+  pub fn port(&self) -> Option<u16> {
+    match self {
+      PluginListener::Tcp(listener) => listener.local_addr().ok().map(|address| address.port()),
+      #[cfg(unix)]
+      PluginListener::Unix(_, _) => None
+    }
+  }
+
+  /// The socket path for the startup message, if listening on a Unix domain socket
+  pub fn socket(&self) -> Option<String> {
+    match self {
+      PluginListener::Tcp(_) => None,
+      #[cfg(unix)]
+      PluginListener::Unix(_, path) => Some(path.to_string_lossy().to_string())
+    }
+  }
+}
+
+/// Removes the Unix domain socket file once the server has shut down
+#[cfg(unix)]
+pub fn remove_socket(path: &Path) {
+  if let Err(err) = std::fs::remove_file(path) {
+    warn!("Failed to remove the socket {} - {}", path.display(), err);
+  }
+}
+
+pub struct TcpIncoming {
+  pub inner: TcpListener
+}
+
+impl Stream for TcpIncoming {
+  type Item = Result<TcpStream, std::io::Error>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    Pin::new(&mut self.inner).poll_accept(cx)
+      .map_ok(|(stream, _)| stream).map(Some)
+  }
+}
+
+/// ID of a connection to the Unix domain socket. The clients of a socket do not have an address, so
+/// this is added to the requests instead, so that the rate limit can be applied to each connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionId(pub u64);
+
+#[cfg(unix)]
+pub struct UnixIncoming {
+  pub inner: UnixListener,
+  pub next_id: u64
+}
+
+#[cfg(unix)]
+impl UnixIncoming {
+  /// Accept connections from the listener
+  pub fn new(inner: UnixListener) -> Self {
+    UnixIncoming { inner, next_id: 0 }
+  }
+}
+
+#[cfg(unix)]
+impl Stream for UnixIncoming {
+  type Item = Result<UnixConnection, std::io::Error>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    let incoming = &mut *self;
+    Pin::new(&mut incoming.inner).poll_accept(cx)
+      .map_ok(|(stream, _)| {
+        incoming.next_id += 1;
+        UnixConnection { stream, id: ConnectionId(incoming.next_id) }
+      })
+      .map(Some)
+  }
+}
+
+/// Connection to the Unix domain socket, with its ID
+#[cfg(unix)]
+pub struct UnixConnection {
+  stream: UnixStream,
+  id: ConnectionId
+}
+
+#[cfg(unix)]
+impl Connected for UnixConnection {
+  type ConnectInfo = ConnectionId;
+
+  fn connect_info(&self) -> Self::ConnectInfo {
+    self.id
+  }
+}
+
+#[cfg(unix)]
+impl AsyncRead for UnixConnection {
+  fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+    Pin::new(&mut self.stream).poll_read(cx, buf)
+  }
+}
+
+#[cfg(unix)]
+impl AsyncWrite for UnixConnection {
+  fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+    Pin::new(&mut self.stream).poll_write(cx, buf)
+  }
+
+  fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+    Pin::new(&mut self.stream).poll_flush(cx)
+  }
+
+  fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+    Pin::new(&mut self.stream).poll_shutdown(cx)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+
+  use super::{DEFAULT_HOST, PluginListener};
+
+  #[tokio::test]
+  async fn bind_port_listens_on_the_loopback_address() {
+    let listener = PluginListener::bind_port(DEFAULT_HOST).await.unwrap();
+    let port = listener.port().unwrap();
+    expect!(listener.socket()).to(be_none());
+    // The plugin driver tries the IP6 loopback address first, and then connects to the IP4 one
+    expect!(tokio::net::TcpStream::connect(("127.0.0.1", port)).await).to(be_ok());
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn bind_socket_replaces_a_stale_socket_file() {
+    let path = std::env::temp_dir().join(format!("pact-csv-listener-{}.sock", std::process::id()));
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    expect!(path.exists()).to(be_true());
+    let listener = PluginListener::bind_socket(path.to_str().unwrap()).await.unwrap();
+    expect!(listener.port()).to(be_none());
+    expect!(listener.socket()).to(be_some().value(path.to_string_lossy().to_string()));
+    expect!(tokio::net::UnixStream::connect(&path).await).to(be_ok());
+    super::remove_socket(&path);
+    expect!(path.exists()).to(be_false());
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn bind_socket_does_not_remove_a_file_that_is_not_a_socket() {
+    let path = std::env::temp_dir().join(format!("pact-csv-listener-file-{}.sock", std::process::id()));
+    std::fs::write(&path, "not a socket").unwrap();
+    expect!(PluginListener::bind_socket(path.to_str().unwrap()).await).to(be_err());
+    expect!(std::fs::read_to_string(&path).unwrap()).to(be_equal_to("not a socket"));
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -1,37 +1,103 @@
-use core::pin::Pin;
-use core::task::{Context, Poll};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io;
 use std::io::{Read, Write};
-use std::net::SocketAddr;
 
 use anyhow::anyhow;
 use csv::{Reader, ReaderBuilder, StringRecord};
 use env_logger::Env;
-use futures::Stream;
-use log::debug;
+use itertools::Itertools;
+use log::{debug, info, Level, log_enabled};
 use maplit::hashmap;
 use pact_matching::matchers::Matches;
-use pact_models::matchingrules::{MatchingRule, RuleList, RuleLogic};
-use pact_models::prelude::ContentType;
-use serde_json::Value;
-use tokio::net::{TcpListener, TcpStream};
+use pact_models::matchingrules::{MatchingRule, RuleList};
+use serde::{Deserialize, Serialize};
+use regex::Regex;
 use tonic::{Response, transport::Server};
+use tonic::service::Interceptor;
+use tonic::service::interceptor::InterceptedService;
 use uuid::Uuid;
 
-use crate::csv_content::{generate_csv_content, has_headers, setup_csv_contents};
+use crate::archive::read_csv_members;
+use crate::audit::write_mismatch_log;
+use crate::auth::ServerKeyInterceptor;
+use crate::categories::MismatchCategory;
+use crate::compression::decompress;
+use crate::constraints::ConstraintCheck;
+use crate::csv_content::{decode_content, generate_csv_content, setup_csv_contents, strip_bom};
+use crate::matchers::{CaseFolding, CsvRule, normalize_number, RoundingMode, strip_thousands_separators};
+use crate::generators::CSV_GENERATOR_TYPES;
+use crate::golden::load_golden_file;
+use crate::homogeneity::HomogeneityCheck;
+use crate::idle::IdleTimer;
+use crate::limits::{check_field_sizes, RequestLimitInterceptor, RequestLimits};
+use crate::listener::{PluginListener, TcpIncoming};
+use crate::options::{
+  body_content_type, ColumnOptions, CompareMode, CONTENT_TYPES, CsvFormat, CsvOptions, HeaderOrder, is_csv_content_type
+};
 use crate::proto::body::ContentTypeHint;
 use crate::proto::catalogue_entry::EntryType;
 use crate::proto::pact_plugin_server::{PactPlugin, PactPluginServer};
-use crate::proto::to_object;
+use crate::pages::{limit_column_mismatches, MismatchPages};
+use crate::parser::parse_field;
+use crate::profiles::ProfileLoader;
+use crate::proto::to_rule_list;
+use crate::report::{CellDecision, cell_diff, render_explanation, render_report, row_count_diff, RuleOutcome};
+use crate::rows::{RowOverride, RowSelector};
+use crate::transforms::apply_transforms;
 
 mod proto;
+mod archive;
+mod audit;
+mod auth;
+mod categories;
+mod compression;
+mod constraints;
 mod parser;
 mod utils;
 mod csv_content;
+mod options;
+mod faults;
+mod fixed_width;
+mod generators;
+mod golden;
+mod homogeneity;
+mod idle;
+mod limits;
+mod listener;
+mod matchers;
+mod pages;
+mod profiles;
+mod report;
+mod rows;
+mod sniffer;
+mod transforms;
+
+/// Startup message printed to standard output once the plugin is running. This has the same shape
+/// as the `RunningPluginInfo` struct the plugin driver reads it into, i.e.
+/// `{"port":12345,"serverKey":"..."}`, or `{"socket":"/tmp/csv.sock","serverKey":"..."}` when
+/// listening on a Unix domain socket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginStartupInfo {
+  /// Port the gRPC server is listening on
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub port: Option<u16>,
+  /// Path of the Unix domain socket the gRPC server is listening on, instead of a port
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub socket: Option<String>,
+  /// Key the driver sends with each request
+  pub server_key: String,
+  /// Version of the plugin, which the driver checks against the plugin manifest
+  pub version: String
+}
 
 #[derive(Debug, Default)]
-pub struct CsvPactPlugin {}
+pub struct CsvPactPlugin {
+  profiles: ProfileLoader,
+  pages: MismatchPages,
+  limits: RequestLimits
+}
 
 #[tonic::async_trait]
 impl PactPlugin for CsvPactPlugin {
@@ -48,16 +114,12 @@ impl PactPlugin for CsvPactPlugin {
         proto::CatalogueEntry {
           r#type: EntryType::ContentMatcher as i32,
           key: "csv".to_string(),
-          values: hashmap! {
-            "content-types".to_string() => "text/csv;application/csv".to_string()
-          }
+          values: catalogue_values()
         },
         proto::CatalogueEntry {
           r#type: EntryType::ContentGenerator as i32,
           key: "csv".to_string(),
-          values: hashmap! {
-            "content-types".to_string() => "text/csv;application/csv".to_string()
-          }
+          values: catalogue_values()
         }
       ]
     }))
@@ -66,9 +128,14 @@ impl PactPlugin for CsvPactPlugin {
   // Not used
   async fn update_catalogue(
     &self,
-    _request: tonic::Request<proto::Catalogue>,
+    request: tonic::Request<proto::Catalogue>,
   ) -> Result<tonic::Response<()>, tonic::Status> {
-    debug!("Update catalogue request, ignoring");
+    // The driver sends an empty catalogue to check that the plugin is responding
+    if request.get_ref().catalogue.is_empty() {
+      debug!("Ping request (an empty update catalogue request)");
+    } else {
+      debug!("Update catalogue request, ignoring");
+    }
     Ok(Response::new(()))
   }
 
@@ -80,72 +147,115 @@ impl PactPlugin for CsvPactPlugin {
     let request = request.get_ref();
     debug!("compare_contents request - {:?}", request);
 
-    let has_headers = has_headers(&request.plugin_configuration);
+    let content_type = request.expected.as_ref().map(|body| body.content_type.as_str()).unwrap_or_default();
+    let options = CsvOptions::from_plugin_config(&request.plugin_configuration)
+      .and_then(|options| options.with_content_type(content_type))
+      .map_err(|err| tonic::Status::invalid_argument(format!("Invalid CSV plugin configuration: {}", err)))?;
 
-    match (request.expected.as_ref(), request.actual.as_ref()) {
+    if let Some(token) = &options.continuation_token {
+      let page = self.pages.next_page(token)
+        .map_err(|err| tonic::Status::not_found(err.to_string()))?;
+      return Ok(Response::new(proto::CompareContentsResponse {
+        results: page.into_results(),
+        .. proto::CompareContentsResponse::default()
+      }));
+    }
+
+    let golden = match &options.golden_file {
+      Some(path) => Some(proto::Body {
+        content_type: body_content_type(content_type).to_string(),
+        content: Some(load_golden_file(path)
+          .map_err(|err| tonic::Status::failed_precondition(format!("Failed to load the expected CSV contents: {}", err)))?),
+        content_type_hint: 0
+      }),
+      None => None
+    };
+
+    match (golden.as_ref().or(request.expected.as_ref()), request.actual.as_ref()) {
       (Some(expected), Some(actual)) => {
-        let expected_csv_data = std::str::from_utf8(expected.content.as_ref().unwrap())
-          .map_err(|err| tonic::Status::aborted(format!("Failed to compare CSV contents: {}", err)))?;
-        let mut expected_rdr = ReaderBuilder::new().has_headers(has_headers)
-          .from_reader(expected_csv_data.as_bytes());
-        let actual_csv_data = actual.content.as_ref().unwrap();
-        let mut actual_rdr = ReaderBuilder::new().has_headers(has_headers)
-          .from_reader(actual_csv_data.as_slice());
-
-        let rules = request.rules.iter()
-          .map(|(key, rules)| {
-            let rules = rules.rule.iter().fold(RuleList::empty(RuleLogic::And), |mut list, rule| {
-              match to_object(&rule.values.as_ref().unwrap()) {
-                Value::Object(mut map) => {
-                  map.insert("match".to_string(), Value::String(rule.r#type.clone()));
-                  debug!("Creating matching rule with {:?}", map);
-                  list.add_rule(&MatchingRule::from_json(&Value::Object(map)).unwrap());
-                }
-                _ => {}
-              }
-              list
-            });
-            (key.clone(), rules)
-          }).collect();
-        compare_contents(has_headers, &mut expected_rdr, &mut actual_rdr,
-                         request.allow_unexpected_keys, rules)
+        if let Some(type_mismatch) = content_type_mismatch(expected, actual, &options) {
+          let response = proto::CompareContentsResponse {
+            type_mismatch: Some(type_mismatch),
+            .. proto::CompareContentsResponse::default()
+          };
+          write_mismatch_log(&options, &response);
+          return Ok(Response::new(response));
+        }
+        // Gzip compressed contents are decompressed first, so all the comparisons are on the CSV data
+        let expected_data = decompress(expected.content.as_deref().unwrap_or_default())
+          .map_err(|err| tonic::Status::aborted(format!("Failed to read the expected CSV contents: {}", err)))?;
+        let actual_data = decompress(actual.content.as_deref().unwrap_or_default())
+          .map_err(|err| tonic::Status::aborted(format!("Failed to read the actual CSV contents: {}", err)))?;
+        if options.compare_mode == CompareMode::Bytes {
+          let response = compare_bytes(&expected_data, &actual_data, &options);
+          write_mismatch_log(&options, response.get_ref());
+          return Ok(response);
+        }
+
+        let rules = matching_rules(&request.rules)
+          .map_err(|err| tonic::Status::invalid_argument(err.to_string()))?;
+        let expected_data = expected_data.as_ref();
+        let actual_data = actual_data.as_ref();
+        let result = if options.format == CsvFormat::Zip {
+          compare_archives(&options, expected_data, actual_data, request.allow_unexpected_keys, &rules)
+        } else {
+          compare_csv_data(&options, expected_data, actual_data, request.allow_unexpected_keys, rules)
+        };
+        result
+          .inspect(|response| if log_enabled!(Level::Debug) {
+            debug!("compare_contents result:\n{}", render_report(response.get_ref()));
+          })
+          .inspect(|response| write_mismatch_log(&options, response.get_ref()))
+          .map(|response| limit_response_mismatches(response, options.max_column_mismatches))
+          .and_then(|response| match options.page_size {
+            Some(page_size) => {
+              let mismatches = response.into_inner().results.into_values()
+                .flat_map(|mismatches| mismatches.mismatches)
+                .collect();
+              self.pages.first_page(mismatches, page_size).map(|page| Response::new(proto::CompareContentsResponse {
+                results: page.into_results(),
+                .. proto::CompareContentsResponse::default()
+              }))
+            }
+            None => Ok(response)
+          })
           .map_err(|err| tonic::Status::aborted(format!("Failed to compare CSV contents: {}", err)))
       }
       (None, Some(actual)) => {
-        let contents = actual.content.as_ref().unwrap();
+        let contents = actual.content.clone().unwrap_or_default();
         Ok(Response::new(proto::CompareContentsResponse {
           error: String::default(),
           type_mismatch: None,
           results: hashmap! {
             String::default() => proto::ContentMismatches {
               mismatches: vec![
-                proto::ContentMismatch {
+                options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
                   expected: None,
-                  actual: Some(contents.clone()),
                   mismatch: format!("Expected no CSV content, but got {} bytes", contents.len()),
+                  actual: Some(contents),
                   path: "".to_string(),
                   diff: "".to_string()
-                }
+                })
               ]
             }
           }
         }))
       }
       (Some(expected), None) => {
-        let contents = expected.content.as_ref().unwrap();
+        let contents = expected.content.clone().unwrap_or_default();
         Ok(Response::new(proto::CompareContentsResponse {
           error: String::default(),
           type_mismatch: None,
           results: hashmap! {
             String::default() => proto::ContentMismatches {
               mismatches: vec![
-                proto::ContentMismatch {
-                  expected: Some(contents.clone()),
+                options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+                  expected: Some(contents),
                   actual: None,
                   mismatch: format!("Expected CSV content, but did not get any"),
                   path: "".to_string(),
                   diff: "".to_string()
-                }
+                })
               ]
             }
           }
@@ -171,7 +281,12 @@ impl PactPlugin for CsvPactPlugin {
     request: tonic::Request<proto::ConfigureInteractionRequest>,
   ) -> Result<tonic::Response<proto::ConfigureInteractionResponse>, tonic::Status> {
     debug!("Received configure_contents request for '{}'", request.get_ref().content_type);
-    setup_csv_contents(&request)
+    let mut request = request.into_inner();
+    if let Some(config) = request.contents_config.as_mut() {
+      config.fields = self.profiles.apply_profile(&config.fields)
+        .map_err(|err| tonic::Status::aborted(format!("Invalid profile: {}", err)))?;
+    }
+    setup_csv_contents(&tonic::Request::new(request))
       .map_err(|err| tonic::Status::aborted(format!("Invalid column definition: {}", err)))
   }
 
@@ -181,12 +296,17 @@ impl PactPlugin for CsvPactPlugin {
     request: tonic::Request<proto::GenerateContentRequest>,
   ) -> Result<tonic::Response<proto::GenerateContentResponse>, tonic::Status> {
     debug!("Received generate_content request");
+    let content_type = request.get_ref().contents.as_ref()
+      .map(|contents| contents.content_type.as_str())
+      .unwrap_or_default();
     generate_csv_content(&request)
       .map(|contents| {
         debug!("Generated contents: {}", contents);
         Response::new(proto::GenerateContentResponse {
           contents: Some(proto::Body {
-            content_type: contents.content_type().unwrap_or(ContentType::from("text/csv")).to_string(),
+            content_type: contents.content_type()
+              .map(|content_type| content_type.to_string())
+              .unwrap_or_else(|| body_content_type(content_type).to_string()),
             content: Some(contents.value().unwrap().to_vec()),
             content_type_hint: ContentTypeHint::Default as i32
           })
@@ -196,8 +316,156 @@ impl PactPlugin for CsvPactPlugin {
   }
 }
 
+/// Values for the catalogue entries. Along with the content types, this includes the optional
+/// features supported by this build of the plugin (as `capability:<name>` entries), so the driver
+/// can check for them instead of assuming they are available. Lists of values are separated with
+/// semicolons.
+fn catalogue_values() -> HashMap<String, String> {
+  hashmap! {
+    "content-types".to_string() => CONTENT_TYPES.join(";"),
+    "capability:tls".to_string() => "false".to_string(),
+    "capability:compression".to_string() => "gzip".to_string(),
+    "capability:streaming".to_string() => "false".to_string(),
+    "capability:multiTable".to_string() => "false".to_string(),
+    "capability:charsets".to_string() => "UTF-8".to_string(),
+    "capability:formats".to_string() => "csv;fixedWidth;zip".to_string(),
+    "capability:compareModes".to_string() => "parsed;bytes".to_string(),
+    "capability:headerOrder".to_string() => "strict;any;schemaMatch".to_string(),
+    "capability:generators".to_string() => CSV_GENERATOR_TYPES.join(";"),
+    "capability:options".to_string() => "csvHeaders;format;columnWidths;delimiter;quote;quoteStyle;compression;compareMode;normalizeLineEndings;\
+      minColumns;headerOrder;singleRecord;normalizeNumbers;explain;alignColumns;generatorSpec;profile;pageSize;maxColumnMismatches;goldenFile;mismatchLog;roundingMode;noBlankLines;seed;mismatchCategories;exactColumns;faultInjection;defaultGenerator;ignoreCase;ignoreCaseLocale;maxFieldSize;thousandsSeparators;lossyUtf8;rowCount;retryGeneration;maxGenerationAttempts".to_string()
+  }
+}
+
+// Decodes and compares the expected and actual CSV data
+fn compare_csv_data(
+  options: &CsvOptions,
+  expected: &[u8],
+  actual: &[u8],
+  allow_unexpected_keys: bool,
+  rules: HashMap<String, RuleList>
+) -> anyhow::Result<tonic::Response<proto::CompareContentsResponse>> {
+  let has_headers = options.has_headers;
+
+  // The CSV reader skips blank lines, so they are checked on the raw contents
+  let structural_mismatches = if options.no_blank_lines {
+    blank_line_mismatches(strip_bom(actual)).into_iter()
+      .map(|mismatch| options.categorise(MismatchCategory::Format, mismatch))
+      .collect()
+  } else {
+    vec![]
+  };
+  let expected_csv_data = decode_content(expected, options)?;
+  let expected_delimiter = options.reader_delimiter(expected_csv_data.as_ref(), b'"');
+  check_field_sizes(expected_csv_data.as_ref(), expected_delimiter, b'"', options.max_field_size)
+    .map_err(|err| anyhow!("Could not read the expected content: {}", err))?;
+  let mut expected_rdr = ReaderBuilder::new().has_headers(has_headers).flexible(true)
+    .delimiter(expected_delimiter)
+    .from_reader(expected_csv_data.as_ref());
+  let actual_csv_data = decode_content(actual, options)?;
+  let actual_delimiter = options.reader_delimiter(actual_csv_data.as_ref(), options.actual_quote());
+  if let Err(err) = check_field_sizes(actual_csv_data.as_ref(), actual_delimiter, options.actual_quote(), options.max_field_size) {
+    let mut mismatches = structural_mismatches;
+    mismatches.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+      expected: None,
+      actual: None,
+      mismatch: format!("Could not read the actual content: {}", err),
+      path: String::default(),
+      diff: String::default()
+    }));
+    return Ok(mismatch_response(mismatches));
+  }
+  let mut actual_rdr = ReaderBuilder::new().has_headers(has_headers).flexible(true)
+    .quote(options.actual_quote())
+    .delimiter(actual_delimiter)
+    .from_reader(actual_csv_data.as_ref());
+
+  compare_contents(options, &mut expected_rdr, &mut actual_rdr, allow_unexpected_keys, rules)
+    .map(|mut response| {
+      if !structural_mismatches.is_empty() {
+        let mismatches = &mut response.get_mut().results.entry(String::default()).or_default().mismatches;
+        mismatches.splice(0..0, structural_mismatches);
+      }
+      response
+    })
+}
+
+// Compares the CSV members of the expected and actual zip archives, matching the members by name.
+// The paths of the mismatches are prefixed with the name of the member.
+fn compare_archives(
+  options: &CsvOptions,
+  expected: &[u8],
+  actual: &[u8],
+  allow_unexpected_keys: bool,
+  rules: &HashMap<String, RuleList>
+) -> anyhow::Result<tonic::Response<proto::CompareContentsResponse>> {
+  let expected_members = read_csv_members(expected)
+    .map_err(|err| anyhow!("Failed to read the expected zip archive: {}", err))?;
+  let actual_members = read_csv_members(actual)
+    .map_err(|err| anyhow!("Failed to read the actual zip archive: {}", err))?;
+  let member_options = CsvOptions { format: CsvFormat::Delimited, .. options.clone() };
+
+  let mut results = vec![];
+  for (name, expected_member) in &expected_members {
+    let member_path = format!("member:{}", name);
+    match actual_members.get(name) {
+      Some(actual_member) => {
+        let response = compare_csv_data(&member_options, expected_member, actual_member, allow_unexpected_keys, rules.clone())
+          .map_err(|err| anyhow!("Failed to compare archive member '{}': {}", name, err))?;
+        let mismatches = response.into_inner().results.into_values()
+          .flat_map(|mismatches| mismatches.mismatches)
+          .map(|mismatch| proto::ContentMismatch {
+            path: if mismatch.path.is_empty() { member_path.clone() } else { format!("{}, {}", member_path, mismatch.path) },
+            .. mismatch
+          });
+        results.extend(mismatches);
+      }
+      None => results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(name.as_bytes().to_vec()),
+        actual: None,
+        mismatch: format!("Expected archive member '{}', but was missing", name),
+        path: member_path,
+        diff: String::default()
+      }))
+    }
+  }
+
+  if !allow_unexpected_keys {
+    for name in actual_members.keys().filter(|name| !expected_members.contains_key(*name)) {
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: None,
+        actual: Some(name.as_bytes().to_vec()),
+        mismatch: format!("Unexpected archive member '{}'", name),
+        path: format!("member:{}", name),
+        diff: String::default()
+      }));
+    }
+  }
+
+  Ok(mismatch_response(results))
+}
+
+// Converts the matching rules for each column received from the driver. If any of the rules can not
+// be constructed, returns an error naming the column and rule type of each of them, instead of failing
+// on the first one. The compare request is then rejected as an invalid argument.
+fn matching_rules(rules: &HashMap<String, proto::MatchingRules>) -> anyhow::Result<HashMap<String, RuleList>> {
+  let mut result = HashMap::new();
+  let mut errors = vec![];
+  for (key, rules) in rules.iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+    match to_rule_list(rules) {
+      Ok(rules) => { result.insert(key.clone(), rules); }
+      Err(err) => errors.push(format!("{}: {}", key, err))
+    }
+  }
+  if errors.is_empty() {
+    Ok(result)
+  } else {
+    Err(anyhow!("Invalid matching rules received for the CSV contents: {}", errors.join("; ")))
+  }
+}
+
 fn compare_contents<R: Read>(
-  has_headers: bool,
+  options: &CsvOptions,
   expected: &mut Reader<R>,
   actual: &mut Reader<R>,
   allow_unexpected_keys: bool,
@@ -205,7 +473,9 @@ fn compare_contents<R: Read>(
 ) -> anyhow::Result<tonic::Response<proto::CompareContentsResponse>> {
   debug!("Comparing contents using allow_unexpected_keys ({}) and rules ({:?})", allow_unexpected_keys, rules);
 
+  let has_headers = options.has_headers;
   let mut results = vec![];
+  let mut decisions = vec![];
 
   let expected_headers = match expected.headers() {
     Ok(headers) => headers.clone(),
@@ -225,146 +495,1997 @@ fn compare_contents<R: Read>(
       StringRecord::default()
     }
   };
-  let actual_headers: HashMap<&str, usize> = actual_headers
+  let actual_header_positions: HashMap<&str, usize> = actual_headers
     .iter()
     .enumerate()
     .map(|(col, hdr)| (hdr, col))
     .collect();
 
+  // Empty contents do not have any records, not even a header row, so there is nothing to compare
+  // the columns with
+  if expected_headers.is_empty() || actual_headers.is_empty() {
+    let header_count = usize::from(has_headers);
+    let expected_count = if expected_headers.is_empty() { 0 } else { expected.records().count() + header_count };
+    let actual_count = if actual_headers.is_empty() { 0 } else { actual.records().count() + header_count };
+    if expected_count != actual_count {
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(format!("{} records", expected_count).as_bytes().to_vec()),
+        actual: Some(format!("{} records", actual_count).as_bytes().to_vec()),
+        mismatch: if expected_count == 0 {
+          format!("Expected empty CSV content, but got {} records", actual_count)
+        } else {
+          format!("Expected {} records, but the CSV content was empty", expected_count)
+        },
+        path: String::default(),
+        diff: row_count_diff(expected_count, actual_count)
+      }));
+    }
+    return Ok(mismatch_response(results));
+  }
+
   if has_headers {
-    for header in expected_headers.iter() {
-      if !actual_headers.contains_key(header) {
-        results.push(proto::ContentMismatch {
-          expected: Some(header.as_bytes().to_vec()),
-          actual: None,
-          mismatch: format!("Expected columns '{}', but was missing", header),
+    compare_headers(&expected_headers, &actual_headers, options, allow_unexpected_keys, &mut results);
+  }
+  results.extend(unresolved_column_mismatches(options, &rules, &expected_headers));
+
+  // The records are read as they are compared, so only the current rows (and the values collected
+  // for the multiset, constraint and homogeneity checks) are kept in memory
+  let mut expected_records = expected.records().peekable();
+  let mut actual_records = actual.records().peekable();
+
+  let expected_row = match expected_records.next() {
+    Some(row) => row?,
+    None if has_headers && !options.single_record => {
+      // Only the header row is expected, so the headers have been compared and there are no
+      // data rows to compare
+      let actual_count = actual_records.count();
+      if actual_count > 0 {
+        results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+          expected: Some("0 records".as_bytes().to_vec()),
+          actual: Some(format!("{} records", actual_count).as_bytes().to_vec()),
+          mismatch: format!("Expected no data rows after the header, but got {}", actual_count),
           path: String::default(),
-          diff: String::default()
-        });
+          diff: row_count_diff(0, actual_count)
+        }));
       }
+      return Ok(mismatch_response(results));
     }
+    None => return Err(anyhow!("Could not read the expected content"))
+  };
+  if options.single_record && expected_records.peek().is_some() {
+    let expected_count = expected_records.count() + 1;
+    return Err(anyhow!("Expected content must have a single record when singleRecord is set, but it has {}", expected_count));
   }
 
-  let mut expected_records = expected.records();
-  let mut actual_records = actual.records();
-
-  let expected_row = expected_records.next()
-    .ok_or_else(|| anyhow!("Could not read the expected content"))??;
-  let actual_row = actual_records.next()
-    .ok_or_else(|| anyhow!("Could not read the actual content"))??;
+  let actual_row = match actual_records.next() {
+    Some(row) => row?,
+    None if options.single_record => {
+      results.push(options.categorise(MismatchCategory::Structural, single_record_mismatch(0)));
+      return Ok(mismatch_response(results));
+    }
+    None if has_headers => {
+      // Only the header row was received, so the headers have been compared and all the expected
+      // data rows are missing
+      let expected_count = expected_records.count() + 1;
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(format!("{} records", expected_count).as_bytes().to_vec()),
+        actual: Some("0 records".as_bytes().to_vec()),
+        mismatch: format!("Expected {} rows, but got 0", expected_count),
+        path: String::default(),
+        diff: row_count_diff(expected_count, 0)
+      }));
+      return Ok(mismatch_response(results));
+    }
+    None => return Err(anyhow!("Could not read the actual content"))
+  };
 
   if !has_headers {
-    if actual_row.len() < expected_row.len() {
-      results.push(proto::ContentMismatch {
-        expected: Some(format!("{} columns", expected_row.len()).as_bytes().to_vec()),
+    // Optional columns at the end of the row can be missing
+    let required_columns = (1..=expected_row.len()).rev()
+      .find(|index| !options.is_optional(*index, ""))
+      .unwrap_or_default();
+    if actual_row.len() < required_columns {
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(format!("{} columns", required_columns).as_bytes().to_vec()),
         actual: Some(format!("{} columns", actual_row.len()).as_bytes().to_vec()),
-        mismatch: format!("Expected {} columns, but got {}", expected_row.len(), actual_row.len()),
+        mismatch: format!("Expected {} columns, but got {}", required_columns, actual_row.len()),
         path: String::default(),
         diff: String::default()
-      });
-    } else if actual_row.len() > expected_row.len() && !allow_unexpected_keys {
-      results.push(proto::ContentMismatch {
-        expected: Some(format!("{} columns", expected_row.len()).as_bytes().to_vec()),
-        actual: Some(format!("{} columns", actual_row.len()).as_bytes().to_vec()),
-        mismatch: format!("Expected at least {} columns, but got {}", expected_row.len(), actual_row.len()),
-        path: String::default(),
+      }));
+    }
+  }
+
+  // Without headers, the columns after the expected ones are not compared. If they are not allowed,
+  // only the first row that has them is reported.
+  let expected_columns = expected_row.len();
+  let mut check_unexpected_columns = !has_headers && !allow_unexpected_keys
+    && !compare_unexpected_columns(expected_columns, &actual_row, options, &mut results);
+
+  // With several expected rows, the actual rows are compared with the expected rows in the same
+  // position. With a single expected row, it is the template that all the actual rows are compared with.
+  let positional = expected_records.peek().is_some();
+  let mut expected_count = 1;
+  let mut comparison = RowComparison {
+    expected_row: expected_row.clone(),
+    rules: &rules,
+    options,
+    expected_headers: &expected_headers,
+    actual_headers: &actual_header_positions,
+    column_map: None
+  };
+  if options.align_columns && !has_headers {
+    let column_map = comparison.align_columns(&actual_row);
+    for (index, actual_index) in column_map.iter().enumerate() {
+      if actual_index.is_none() {
+        results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+          expected: expected_row.get(index).map(|value| value.as_bytes().to_vec()),
+          actual: None,
+          mismatch: format!("Could not align expected column {} with any of the actual columns", index + 1),
+          path: format!("column:{:2}", index),
+          diff: String::default()
+        }));
+      }
+    }
+    comparison.column_map = Some(column_map);
+  }
+  let mut multisets = ColumnMultiset::for_columns(options, &expected_headers, &expected_row);
+  let mut constraints = ConstraintCheck::for_columns(options, &expected_headers, expected_row.len())?;
+  let mut homogeneity = HomogeneityCheck::for_columns(options, &expected_headers, expected_row.len());
+  let row_overrides = select_row_overrides(options, &actual_row, 1, actual_records.peek().is_none(),
+    &actual_header_positions);
+  compare_row_width(&actual_row, options, &mut results);
+  comparison.compare_row(&actual_row, &row_overrides, &mut results, &mut decisions);
+  comparison.collect_multiset_values(&actual_row, &mut multisets);
+  comparison.collect_constraint_values(&actual_row, &mut constraints);
+  comparison.collect_homogeneity_values(&actual_row, &mut homogeneity);
+  if options.single_record {
+    let actual_count = actual_records.count() + 1;
+    if actual_count != 1 {
+      results.push(options.categorise(MismatchCategory::Structural, single_record_mismatch(actual_count)));
+    }
+  } else {
+    let mut row_number = 1;
+    while let Some(row) = actual_records.next() {
+      let row = row?;
+      row_number += 1;
+      let row_overrides = select_row_overrides(options, &row, row_number, actual_records.peek().is_none(),
+        &actual_header_positions);
+      compare_row_width(&row, options, &mut results);
+      if check_unexpected_columns {
+        check_unexpected_columns = !compare_unexpected_columns(expected_columns, &row, options, &mut results);
+      }
+      // Actual rows after the last expected row are reported by the row count, and only the
+      // columns with rules for every row are compared
+      if positional {
+        if let Some(expected_row) = expected_records.next().transpose()? {
+          expected_count += 1;
+          collect_expected_multiset_values(&expected_row, &mut multisets);
+          comparison.expected_row = expected_row;
+          comparison.compare_row(&row, &row_overrides, &mut results, &mut decisions);
+        } else {
+          comparison.compare_each_values(&row, &row_overrides, &mut results, &mut decisions);
+        }
+      } else {
+        comparison.compare_row(&row, &row_overrides, &mut results, &mut decisions);
+      }
+      comparison.collect_multiset_values(&row, &mut multisets);
+      comparison.collect_constraint_values(&row, &mut constraints);
+      comparison.collect_homogeneity_values(&row, &mut homogeneity);
+    }
+
+    // The expected rows that are left are missing from the actual contents. They are reported after
+    // the row count, once they have all been read.
+    let mut missing_rows = vec![];
+    for missing in expected_records {
+      let missing = missing?;
+      expected_count += 1;
+      collect_expected_multiset_values(&missing, &mut multisets);
+      let values = missing.iter().join(",");
+      missing_rows.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(values.as_bytes().to_vec()),
+        actual: None,
+        mismatch: format!("Expected row {} '{}', but was missing", expected_count, values),
+        path: format!("row:{:5}", expected_count),
         diff: String::default()
-      });
+      }));
+    }
+    if positional && row_number != expected_count {
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(format!("{} records", expected_count).as_bytes().to_vec()),
+        actual: Some(format!("{} records", row_number).as_bytes().to_vec()),
+        mismatch: format!("Expected {} rows, but got {}", expected_count, row_number),
+        path: String::default(),
+        diff: row_count_diff(expected_count, row_number)
+      }));
     }
+    results.extend(missing_rows);
+  }
+  for multiset in &multisets {
+    multiset.compare(options, &mut results);
+  }
+  for constraint in &constraints {
+    constraint.compare(options, &mut results);
+  }
+  for check in &homogeneity {
+    check.compare(options, &mut results);
   }
 
-  compare_row(&expected_row, &actual_row, &rules, has_headers, &expected_headers, &actual_headers, &mut results);
-  for row in actual_records {
-    compare_row(&expected_row, &row?, &rules, has_headers, &expected_headers, &actual_headers, &mut results);
+  // The explanation is only logged, as the driver treats any entry in the results as a mismatch
+  if options.explain {
+    info!("Explanation of the CSV comparison:\n{}", render_explanation(&decisions));
   }
+  Ok(mismatch_response(results))
+}
 
-  Ok(Response::new(proto::CompareContentsResponse {
+fn single_record_mismatch(actual_count: usize) -> proto::ContentMismatch {
+  proto::ContentMismatch {
+    expected: Some("1 record".as_bytes().to_vec()),
+    actual: Some(format!("{} records", actual_count).as_bytes().to_vec()),
+    mismatch: format!("Expected a single record, but got {}", actual_count),
+    path: String::default(),
+    diff: row_count_diff(1, actual_count)
+  }
+}
+
+fn mismatch_response(mismatches: Vec<proto::ContentMismatch>) -> tonic::Response<proto::CompareContentsResponse> {
+  Response::new(proto::CompareContentsResponse {
     error: String::default(),
     type_mismatch: None,
     results: hashmap! {
       String::default() => proto::ContentMismatches {
-        mismatches: results
+        mismatches
       }
     }
-  }))
+  })
 }
 
-fn compare_row(
-  expected_row: &StringRecord,
-  actual_row: &StringRecord,
+// Limits the number of mismatches for each column. The mismatch log has all of them, as it is
+// written before they are limited.
+fn limit_response_mismatches(
+  response: tonic::Response<proto::CompareContentsResponse>,
+  max_column_mismatches: usize
+) -> tonic::Response<proto::CompareContentsResponse> {
+  let mut response = response.into_inner();
+  for mismatches in response.results.values_mut() {
+    mismatches.mismatches = limit_column_mismatches(std::mem::take(&mut mismatches.mismatches), max_column_mismatches);
+  }
+  Response::new(response)
+}
+
+// The actual contents are not parsed as CSV if they have a content type that is not CSV. Contents
+// without a content type, and the fixed width and zip formats (which have their own content types),
+// are always compared.
+fn content_type_mismatch(
+  expected: &proto::Body,
+  actual: &proto::Body,
+  options: &CsvOptions
+) -> Option<proto::ContentTypeMismatch> {
+  if options.format != CsvFormat::Delimited || actual.content_type.is_empty() || is_csv_content_type(&actual.content_type) {
+    None
+  } else {
+    Some(proto::ContentTypeMismatch {
+      expected: if expected.content_type.is_empty() { "text/csv".to_string() } else { expected.content_type.clone() },
+      actual: actual.content_type.clone()
+    })
+  }
+}
+
+// Adds the values of an expected row to the multiset columns
+fn collect_expected_multiset_values(expected_row: &StringRecord, multisets: &mut [ColumnMultiset]) {
+  for multiset in multisets.iter_mut() {
+    if let Some(value) = expected_row.get(multiset.index) {
+      *multiset.expected.entry(value.to_string()).or_default() += 1;
+    }
+  }
+}
+
+// Columns defined by name must be in the expected header row, otherwise their definitions would be
+// silently ignored
+fn unresolved_column_mismatches(
+  options: &CsvOptions,
   rules: &HashMap<String, RuleList>,
-  has_headers: bool,
+  expected_headers: &StringRecord
+) -> Vec<proto::ContentMismatch> {
+  let names = rules.keys()
+    .chain(options.columns.keys())
+    .chain(options.rows.iter().map(|row| &row.column))
+    .filter_map(|key| parse_field(key).ok())
+    .filter_map(|column| column.right())
+    .collect::<BTreeSet<_>>();
+  names.iter()
+    .filter(|name| !options.has_headers || !expected_headers.iter().any(|header| header == name.as_str()))
+    .map(|name| options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+      expected: Some(name.as_bytes().to_vec()),
+      actual: None,
+      mismatch: if options.has_headers {
+        format!("Column '{}' has a definition, but is not in the expected header row", name)
+      } else {
+        format!("Column '{}' is defined by name, but the contents do not have a header row", name)
+      },
+      path: format!("column:{}", name),
+      diff: String::default()
+    }))
+    .collect()
+}
+
+// Checks the actual header row against the expected one. Missing and unexpected columns are
+// reported separately from the columns being in a different order.
+fn compare_headers(
   expected_headers: &StringRecord,
-  actual_headers: &HashMap<&str, usize>,
-  results: &mut Vec<proto::ContentMismatch>) {
-  for (index, expected_item) in expected_row.iter().enumerate() {
-    let header = expected_headers.get(index).unwrap_or_default();
-    let item = if has_headers {
-      match actual_headers.get(header) {
-        Some(actual_index) => actual_row.get(*actual_index).unwrap_or_default(),
-        None => ""
+  actual_headers: &StringRecord,
+  options: &CsvOptions,
+  allow_unexpected_keys: bool,
+  results: &mut Vec<proto::ContentMismatch>
+) {
+  // With schemaMatch the header row is the schema, so it must have exactly the expected columns, and
+  // the header mismatches are kept apart from the value mismatches (which have a row and column path)
+  let schema_match = options.header_order == HeaderOrder::SchemaMatch;
+  let path = if schema_match { "header".to_string() } else { String::default() };
+  for (index, header) in expected_headers.iter().enumerate() {
+    if !actual_headers.iter().any(|actual| actual == header) && !options.is_optional(index + 1, header) {
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(header.as_bytes().to_vec()),
+        actual: None,
+        mismatch: format!("Expected columns '{}', but was missing", header),
+        path: path.clone(),
+        diff: String::default()
+      }));
+    }
+  }
+
+  if !allow_unexpected_keys || schema_match {
+    for header in actual_headers.iter() {
+      if !expected_headers.iter().any(|expected| expected == header) {
+        results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+          expected: None,
+          actual: Some(header.as_bytes().to_vec()),
+          mismatch: format!("Unexpected column '{}'", header),
+          path: path.clone(),
+          diff: String::default()
+        }));
+      }
+    }
+  }
+
+  if options.header_order == HeaderOrder::Strict {
+    let expected_order = expected_headers.iter()
+      .filter(|header| actual_headers.iter().any(|actual| actual == *header))
+      .collect::<Vec<_>>();
+    let actual_order = actual_headers.iter()
+      .filter(|header| expected_headers.iter().any(|expected| expected == *header))
+      .collect::<Vec<_>>();
+    if expected_order != actual_order {
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(expected_order.join(",").as_bytes().to_vec()),
+        actual: Some(actual_order.join(",").as_bytes().to_vec()),
+        mismatch: format!("Expected the columns to be in the order [{}], but got [{}]",
+          expected_order.join(", "), actual_order.join(", ")),
+        path: String::default(),
+        diff: String::default()
+      }));
+    }
+  }
+}
+
+fn compare_bytes(
+  expected: &[u8],
+  actual: &[u8],
+  options: &CsvOptions
+) -> tonic::Response<proto::CompareContentsResponse> {
+  debug!("Comparing contents as bytes using normalize_line_endings ({})", options.normalize_line_endings);
+
+  let (expected, actual) = if options.normalize_line_endings {
+    (Cow::Owned(normalize_newlines(expected)), Cow::Owned(normalize_newlines(actual)))
+  } else {
+    (Cow::Borrowed(expected), Cow::Borrowed(actual))
+  };
+
+  let mut results = vec![];
+  if expected != actual {
+    let line = expected.split_inclusive(|b| *b == b'\n')
+      .zip(actual.split_inclusive(|b| *b == b'\n'))
+      .take_while(|(e, a)| e == a)
+      .count() + 1;
+    results.push(options.categorise(MismatchCategory::Value, proto::ContentMismatch {
+      expected: Some(expected.to_vec()),
+      actual: Some(actual.to_vec()),
+      mismatch: format!("Expected the contents to be equal ({} bytes) but got {} bytes, differing from line {}",
+        expected.len(), actual.len(), line),
+      path: String::default(),
+      diff: String::default()
+    }));
+  }
+
+  mismatch_response(results)
+}
+
+// Checks that the contents do not start or end with blank lines. A single line ending at the end
+// of the contents is not counted as a blank line.
+fn blank_line_mismatches(data: &[u8]) -> Vec<proto::ContentMismatch> {
+  let mut lines = data.split(|b| *b == b'\n').collect::<Vec<_>>();
+  if lines.last().map(|line| line.is_empty()).unwrap_or(false) {
+    lines.pop();
+  }
+  let is_blank = |line: &&&[u8]| line.iter().all(|b| b.is_ascii_whitespace());
+  let leading = lines.iter().take_while(is_blank).count();
+  let trailing = if leading == lines.len() { 0 } else { lines.iter().rev().take_while(is_blank).count() };
+
+  let mut results = vec![];
+  if leading > 0 {
+    results.push(proto::ContentMismatch {
+      expected: None,
+      actual: Some(format!("{} blank lines", leading).as_bytes().to_vec()),
+      mismatch: format!("Expected the contents to not start with blank lines, but found {}", leading),
+      path: String::default(),
+      diff: String::default()
+    });
+  }
+  if trailing > 0 {
+    results.push(proto::ContentMismatch {
+      expected: None,
+      actual: Some(format!("{} blank lines", trailing).as_bytes().to_vec()),
+      mismatch: format!("Expected the contents to not end with blank lines, but found {}", trailing),
+      path: String::default(),
+      diff: String::default()
+    });
+  }
+  results
+}
+
+/// Converts CRLF and CR line endings to LF
+fn normalize_newlines(data: &[u8]) -> Vec<u8> {
+  let mut result = Vec::with_capacity(data.len());
+  let mut iter = data.iter().peekable();
+  while let Some(b) = iter.next() {
+    if *b == b'\r' {
+      if iter.peek() == Some(&&b'\n') {
+        iter.next();
+      }
+      result.push(b'\n');
+    } else {
+      result.push(*b);
+    }
+  }
+  result
+}
+
+/// Number of the row (counting the header row) for the mismatch paths and messages. This is from the
+/// index of the record and not the line it starts on, as quoted values can have line breaks in them.
+fn row_number(row: &StringRecord) -> u64 {
+  row.position().map(|position| position.record() + 1).unwrap_or_default()
+}
+
+// Checks the number of columns in the actual row against the configured column counts
+// Reports the columns of the actual row after the expected columns, returning true if there are any
+fn compare_unexpected_columns(
+  expected_columns: usize,
+  actual_row: &StringRecord,
+  options: &CsvOptions,
+  results: &mut Vec<proto::ContentMismatch>
+) -> bool {
+  if actual_row.len() > expected_columns {
+    results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+      expected: Some(format!("{} columns", expected_columns).as_bytes().to_vec()),
+      actual: Some(format!("{} columns", actual_row.len()).as_bytes().to_vec()),
+      mismatch: format!("Expected at most {} columns in row {}, but got {} (unexpected columns are not allowed)",
+        expected_columns, row_number(actual_row), actual_row.len()),
+      path: format!("row:{:5}", row_number(actual_row)),
+      diff: String::default()
+    }));
+    true
+  } else {
+    false
+  }
+}
+
+fn compare_row_width(
+  actual_row: &StringRecord,
+  options: &CsvOptions,
+  results: &mut Vec<proto::ContentMismatch>
+) {
+  if let Some(min_columns) = options.min_columns {
+    if actual_row.len() < min_columns {
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(format!("{} columns", min_columns).as_bytes().to_vec()),
+        actual: Some(format!("{} columns", actual_row.len()).as_bytes().to_vec()),
+        mismatch: format!("Expected at least {} columns, but got {}", min_columns, actual_row.len()),
+        path: format!("row:{:5}", row_number(actual_row)),
+        diff: String::default()
+      }));
+    }
+  }
+  // This is checked even if unexpected columns are allowed
+  if let Some(exact_columns) = options.exact_columns {
+    if actual_row.len() != exact_columns {
+      results.push(options.categorise(MismatchCategory::Structural, proto::ContentMismatch {
+        expected: Some(format!("{} columns", exact_columns).as_bytes().to_vec()),
+        actual: Some(format!("{} columns", actual_row.len()).as_bytes().to_vec()),
+        mismatch: format!("Expected exactly {} columns in row {}, but got {}", exact_columns,
+          row_number(actual_row), actual_row.len()),
+        path: format!("row:{:5}", row_number(actual_row)),
+        diff: String::default()
+      }));
+    }
+  }
+}
+
+/// Values of a column that is compared as a multiset, where only the number of times each value
+/// occurs matters and not the order of the rows
+#[derive(Debug, Clone, PartialEq)]
+struct ColumnMultiset {
+  index: usize,
+  header: String,
+  expected: BTreeMap<String, usize>,
+  actual: BTreeMap<String, usize>
+}
+
+impl ColumnMultiset {
+  /// Creates the multisets for each of the columns that are compared as multisets, with the
+  /// values from the first expected row. The values of any other expected rows are added as they
+  /// are read.
+  fn for_columns(options: &CsvOptions, expected_headers: &StringRecord, expected_row: &StringRecord) -> Vec<ColumnMultiset> {
+    let mut multisets = (0..expected_row.len())
+      .filter_map(|index| {
+        let header = expected_headers.get(index).unwrap_or_default();
+        options.column(index + 1, header)
+          .filter(|column| column.multiset)
+          .map(|_| ColumnMultiset { index, header: header.to_string(), expected: BTreeMap::new(), actual: BTreeMap::new() })
+      })
+      .collect::<Vec<_>>();
+    collect_expected_multiset_values(expected_row, &mut multisets);
+    multisets
+  }
+
+  /// Reports the values that occur more or less often in the actual column than expected
+  fn compare(&self, options: &CsvOptions, results: &mut Vec<proto::ContentMismatch>) {
+    let column = if self.header.is_empty() { (self.index + 1).to_string() } else { self.header.clone() };
+    let values = self.expected.keys().chain(self.actual.keys()).collect::<BTreeSet<_>>();
+    for value in values {
+      let expected_count = self.expected.get(value).copied().unwrap_or_default();
+      let actual_count = self.actual.get(value).copied().unwrap_or_default();
+      if expected_count != actual_count {
+        results.push(options.categorise(MismatchCategory::Aggregate, proto::ContentMismatch {
+          expected: Some(format!("{} x '{}'", expected_count, value).as_bytes().to_vec()),
+          actual: Some(format!("{} x '{}'", actual_count, value).as_bytes().to_vec()),
+          mismatch: format!("Expected the value '{}' to occur {} times in column {}, but it occurred {} times ({})",
+            value, expected_count, column, actual_count,
+            if actual_count > expected_count { "over-represented" } else { "under-represented" }),
+          path: format!("column:{:2}", self.index),
+          diff: String::default()
+        }));
+      }
+    }
+  }
+}
+
+// Returns the row overrides that select the actual row
+fn select_row_overrides<'a>(
+  options: &'a CsvOptions,
+  actual_row: &StringRecord,
+  row_number: usize,
+  is_last: bool,
+  actual_headers: &HashMap<&str, usize>
+) -> Vec<&'a RowOverride> {
+  options.rows.iter()
+    .filter(|row| row.selector.matches(actual_row, row_number, is_last, actual_headers))
+    .collect()
+}
+
+/// The expected row and the rules used to compare each of the actual rows against it
+struct RowComparison<'a> {
+  expected_row: StringRecord,
+  rules: &'a HashMap<String, RuleList>,
+  options: &'a CsvOptions,
+  expected_headers: &'a StringRecord,
+  actual_headers: &'a HashMap<&'a str, usize>,
+  /// Actual column for each of the expected columns when the columns have been aligned. Columns
+  /// that could not be aligned are not compared.
+  column_map: Option<Vec<Option<usize>>>
+}
+
+impl <'a> RowComparison<'a> {
+  /// Returns the actual value for the expected column, or None if it is not compared
+  fn actual_item<'b>(&self, index: usize, header: &str, actual_row: &'b StringRecord) -> Option<&'b str> {
+    if self.options.has_headers {
+      Some(self.actual_headers.get(header)
+        .and_then(|actual_index| actual_row.get(*actual_index))
+        .unwrap_or_default())
+    } else if let Some(column_map) = &self.column_map {
+      column_map.get(index).copied().flatten().map(|actual_index| actual_row.get(actual_index).unwrap_or_default())
+    } else {
+      Some(actual_row.get(index).unwrap_or_default())
+    }
+  }
+
+  /// Adds the actual values of the multiset columns from the row
+  fn collect_multiset_values(&self, actual_row: &StringRecord, multisets: &mut [ColumnMultiset]) {
+    for multiset in multisets.iter_mut() {
+      if !self.is_missing(multiset.index, &multiset.header, actual_row) {
+        if let Some(value) = self.actual_item(multiset.index, &multiset.header, actual_row) {
+          *multiset.actual.entry(value.to_string()).or_default() += 1;
+        }
       }
+    }
+  }
+
+  /// Adds the actual values of the columns with constraints from the row
+  fn collect_constraint_values(&self, actual_row: &StringRecord, constraints: &mut [ConstraintCheck]) {
+    let row = row_number(actual_row);
+    for constraint in constraints.iter_mut() {
+      let value = Some(constraint.index)
+        .filter(|index| !self.is_missing(*index, &constraint.header, actual_row))
+        .and_then(|index| self.actual_item(index, &constraint.header, actual_row));
+      let other_value = Some(constraint.other_index)
+        .filter(|index| !self.is_missing(*index, &constraint.other_header, actual_row))
+        .and_then(|index| self.actual_item(index, &constraint.other_header, actual_row));
+      constraint.add_row(row, value, other_value);
+    }
+  }
+
+  /// Adds the actual values of the homogeneous columns from the row
+  fn collect_homogeneity_values(&self, actual_row: &StringRecord, checks: &mut [HomogeneityCheck]) {
+    let row = row_number(actual_row);
+    for check in checks.iter_mut() {
+      if !self.is_missing(check.index, &check.header, actual_row) {
+        if let Some(value) = self.actual_item(check.index, &check.header, actual_row) {
+          check.add_value(row, value);
+        }
+      }
+    }
+  }
+
+  /// If the expected column is not present in the actual row
+  fn is_missing(&self, index: usize, header: &str, actual_row: &StringRecord) -> bool {
+    if self.options.has_headers {
+      self.actual_headers.get(header).and_then(|actual_index| actual_row.get(*actual_index)).is_none()
+    } else if let Some(column_map) = &self.column_map {
+      column_map.get(index).copied().flatten().and_then(|actual_index| actual_row.get(actual_index)).is_none()
     } else {
-      actual_row.get(index).unwrap_or_default()
+      actual_row.get(index).is_none()
+    }
+  }
+
+  /// Definition to use for the expected column: the expected value, the column options and the
+  /// Pact matching rules
+  fn column_definition(
+    &self,
+    index: usize,
+    header: &str,
+    expected_item: &'a str,
+    row_overrides: &[&'a RowOverride]
+  ) -> (&'a str, Option<&'a ColumnOptions>, Option<&'a [MatchingRule]>) {
+    match row_overrides.iter().find(|row| row.applies_to(index + 1, header)) {
+      Some(row) => (row.value.as_str(), Some(&row.options), Some(row.matching_rules.as_slice()).filter(|rules| !rules.is_empty())),
+      None => (expected_item, self.options.column(index + 1, header),
+        self.rules.get(&format!("column:{}", index + 1))
+          .or_else(|| self.rules.get(&format!("column:{}", header)))
+          .map(|rules| rules.rules.as_slice()))
+    }
+  }
+
+  /// Best-effort alignment of the expected columns with the actual columns for content without
+  /// headers, using the values in the first actual row. Each expected column is aligned with the
+  /// actual column in the same position if the value matches the column definition, otherwise
+  /// with the first unaligned actual column that does.
+  fn align_columns(&self, actual_row: &StringRecord) -> Vec<Option<usize>> {
+    let column_matches = |index: usize, expected_item: &str, actual_index: usize| {
+      let (expected_item, column, column_rules) = self.column_definition(index, "", expected_item, &[]);
+      let actual_item = actual_row.get(actual_index).unwrap_or_default();
+      let item = match column.filter(|column| !column.transforms.is_empty()) {
+        Some(column) => apply_transforms(&column.transforms, actual_item),
+        None => actual_item.to_string()
+      };
+      let plugin_rules = column.map(|column| column.rules.as_slice()).unwrap_or_default();
+      check_value(index, expected_item, &item, column_rules, plugin_rules, self.options.rounding_mode,
+        self.options.ignore_case.as_ref()).iter().all(|outcome| outcome.result.is_ok())
     };
 
-    let path = format!("column:{}", index + 1);
-    let header_path = format!("column:{}", header);
-
-    if let Some(rules) = rules.get(&path).or_else(|| rules.get(header_path.as_str())) {
-      for rule in &rules.rules {
-        if let Err(err) = expected_item.matches_with(item, rule, false) {
-          results.push(proto::ContentMismatch {
-            expected: Some(expected_item.as_bytes().to_vec()),
-            actual: Some(item.as_bytes().to_vec()),
-            mismatch: err.to_string(),
-            path: format!("row:{:5}, column:{:2}", actual_row.position().unwrap().line(), index),
-            diff: String::default()
+    let mut column_map = vec![None; self.expected_row.len()];
+    let mut aligned = vec![false; actual_row.len()];
+    for (index, expected_item) in self.expected_row.iter().enumerate() {
+      if index < actual_row.len() && column_matches(index, expected_item, index) {
+        column_map[index] = Some(index);
+        aligned[index] = true;
+      }
+    }
+    for (index, expected_item) in self.expected_row.iter().enumerate() {
+      if column_map[index].is_none() {
+        if let Some(actual_index) = (0..actual_row.len())
+          .find(|actual_index| !aligned[*actual_index] && column_matches(index, expected_item, *actual_index)) {
+          column_map[index] = Some(actual_index);
+          aligned[actual_index] = true;
+        }
+      }
+    }
+
+    debug!("Aligned columns (expected -> actual): {}", column_map.iter().enumerate()
+      .map(|(index, actual_index)| match actual_index {
+        Some(actual_index) => format!("{} -> {}", index + 1, actual_index + 1),
+        None => format!("{} -> ?", index + 1)
+      })
+      .join(", "));
+    column_map
+  }
+
+  fn compare_row(
+    &self,
+    actual_row: &StringRecord,
+    row_overrides: &[&'a RowOverride],
+    results: &mut Vec<proto::ContentMismatch>,
+    decisions: &mut Vec<CellDecision>
+  ) {
+    self.compare_columns(actual_row, row_overrides, false, results, decisions);
+  }
+
+  /// Compares the columns of an actual row after the last expected row that have their rules
+  /// applied to every row (`eachValue`), using the last expected row for the expected values
+  fn compare_each_values(
+    &self,
+    actual_row: &StringRecord,
+    row_overrides: &[&'a RowOverride],
+    results: &mut Vec<proto::ContentMismatch>,
+    decisions: &mut Vec<CellDecision>
+  ) {
+    self.compare_columns(actual_row, row_overrides, true, results, decisions);
+  }
+
+  fn compare_columns(
+    &self,
+    actual_row: &StringRecord,
+    row_overrides: &[&'a RowOverride],
+    each_value_only: bool,
+    results: &mut Vec<proto::ContentMismatch>,
+    decisions: &mut Vec<CellDecision>
+  ) {
+    for (index, expected_item) in self.expected_row.iter().enumerate() {
+      let header = self.expected_headers.get(index).unwrap_or_default();
+      if each_value_only && !self.options.column(index + 1, header).map(|column| column.each_value).unwrap_or(false) {
+        continue;
+      }
+      if self.is_missing(index, header, actual_row) && self.options.is_optional(index + 1, header) {
+        continue;
+      }
+      // Multiset columns are compared once all the rows have been read
+      if self.options.column(index + 1, header).map(|column| column.multiset).unwrap_or(false) {
+        continue;
+      }
+      let actual_item = match self.actual_item(index, header, actual_row) {
+        Some(item) => item,
+        None => continue
+      };
+      let (expected_item, column, column_rules) = self.column_definition(index, header, expected_item, row_overrides);
+      // Rules from a row override selected by the value of another column are cross-field rules
+      let cross_field = row_overrides.iter()
+        .find(|row| row.applies_to(index + 1, header))
+        .map(|row| matches!(row.selector, RowSelector::Where(..)))
+        .unwrap_or(false);
+
+      let transformed = column
+        .filter(|column| !column.transforms.is_empty())
+        .map(|column| apply_transforms(&column.transforms, actual_item));
+      let item = transformed.as_deref().unwrap_or(actual_item);
+      let plugin_rules = column
+        .map(|column| column.rules.as_slice())
+        .unwrap_or_default();
+      // Text columns are compared as their literal values (i.e. zip codes with leading zeros)
+      let is_text = column.map(|column| column.text).unwrap_or(false);
+      // Thousands separators are removed from both values of numeric columns (i.e. `1,234` and `1234`)
+      let separators = &self.options.thousands_separators;
+      let stripped = if !separators.is_empty() && !is_text {
+        strip_thousands_separators(expected_item, separators).zip(strip_thousands_separators(item, separators))
+          .filter(|(expected, actual)| expected != expected_item || actual != item)
+          .filter(|(expected, _)| is_numeric_column(expected, column_rules, plugin_rules))
+      } else {
+        None
+      };
+      let (expected_number, number) = stripped.as_ref()
+        .map(|(expected, actual)| (expected.as_str(), actual.as_str()))
+        .unwrap_or((expected_item, item));
+      let normalized = if self.options.normalize_numbers && !is_text
+        && is_numeric_column(expected_number, column_rules, plugin_rules) {
+        normalize_number(expected_number).zip(normalize_number(number))
+      } else {
+        None
+      };
+      let (expected_value, value) = normalized.as_ref()
+        .map(|(expected, actual)| (expected.as_str(), actual.as_str()))
+        .unwrap_or((expected_number, number));
+
+      let row = row_number(actual_row);
+      for outcome in check_value(index, expected_value, value, column_rules, plugin_rules, self.options.rounding_mode,
+        self.options.ignore_case.as_ref()) {
+        if self.options.explain {
+          decisions.push(CellDecision {
+            row,
+            column: index,
+            expected: expected_value.to_string(),
+            actual: value.to_string(),
+            rule: outcome.rule.clone(),
+            result: outcome.result.clone()
           });
         }
+        let message = match outcome.result {
+          Ok(()) => continue,
+          Err(message) => message
+        };
+        let message = match &transformed {
+          Some(transformed) => format!("{} (the actual value '{}' was transformed to '{}')", message, actual_item, transformed),
+          None => message
+        };
+        let message = match (&normalized, &stripped) {
+          (Some((expected, actual)), _) => format!("{} (compared as the normalized values '{}' and '{}')", message, expected, actual),
+          (None, Some((expected, actual))) => format!("{} (compared as the numeric values '{}' and '{}')", message, expected, actual),
+          (None, None) => message
+        };
+        let category = if cross_field { MismatchCategory::CrossField } else { outcome.category };
+        results.push(self.options.categorise(category, proto::ContentMismatch {
+          expected: Some(expected_item.as_bytes().to_vec()),
+          actual: Some(actual_item.as_bytes().to_vec()),
+          mismatch: message,
+          path: format!("row:{:5}, column:{:2}", row, index),
+          diff: cell_diff(expected_item, actual_item)
+        }));
       }
-    } else if item != expected_item {
-      results.push(proto::ContentMismatch {
-        expected: Some(expected_item.as_bytes().to_vec()),
-        actual: Some(item.as_bytes().to_vec()),
-        mismatch: format!("Expected column {} value to equal '{}', but got '{}'", index, expected_item, item),
-        path: format!("row:{:5}, column:{:2}", actual_row.position().unwrap().line(), index),
-        diff: String::default()
-      });
     }
   }
 }
 
-struct TcpIncoming {
-  inner: TcpListener
+// A column is numeric if it has a numeric matching rule, or if it has no rules and the expected
+// value is a number
+fn is_numeric_column(expected_item: &str, column_rules: Option<&[MatchingRule]>, plugin_rules: &[CsvRule]) -> bool {
+  let has_numeric_rule = column_rules.unwrap_or_default().iter()
+    .any(|rule| matches!(rule, MatchingRule::Number | MatchingRule::Integer | MatchingRule::Decimal))
+    || plugin_rules.iter().any(|rule| matches!(rule, CsvRule::DecimalPrecision { .. } | CsvRule::Rounded { .. }));
+  has_numeric_rule || (column_rules.is_none() && plugin_rules.is_empty() && normalize_number(expected_item).is_some())
+}
+
+// Checks the actual value of a column against each of the rules, or against the expected value if
+// there are no rules, returning the outcome of each rule
+fn check_value(
+  index: usize,
+  expected_item: &str,
+  item: &str,
+  column_rules: Option<&[MatchingRule]>,
+  plugin_rules: &[CsvRule],
+  rounding_mode: RoundingMode,
+  case_folding: Option<&CaseFolding>
+) -> Vec<RuleOutcome> {
+  let mut outcomes = vec![];
+  if column_rules.is_some() || !plugin_rules.is_empty() {
+    for rule in column_rules.unwrap_or_default() {
+      let result = match (rule, case_folding) {
+        (MatchingRule::Equality, Some(case_folding)) => if case_folding.equals(expected_item, item) {
+          Ok(())
+        } else {
+          Err(format!("Expected '{}' to be equal to '{}' (ignoring case)", item, expected_item))
+        },
+        // The pattern is shown in the same form as the regex in the column definition
+        (MatchingRule::Regex(pattern), _) => match Regex::new(pattern) {
+          Ok(regex) if regex.is_match(item) => Ok(()),
+          Ok(_) => Err(format!("Expected '{}' to match /{}/", item, pattern)),
+          Err(err) => Err(format!("'{}' is not a valid regular expression - {}", pattern, err))
+        },
+        _ => expected_item.matches_with(item, rule, false).map_err(|err| err.to_string())
+      };
+      outcomes.push(RuleOutcome {
+        rule: rule.to_json().to_string(),
+        result,
+        category: MismatchCategory::for_rule(rule)
+      });
+    }
+    for rule in plugin_rules {
+      outcomes.push(RuleOutcome {
+        rule: rule.to_json().to_string(),
+        result: rule.match_value(expected_item, item, rounding_mode),
+        category: MismatchCategory::for_csv_rule(rule)
+      });
+    }
+  } else {
+    outcomes.push(RuleOutcome {
+      rule: "equality (the column has no matching rules)".to_string(),
+      result: match case_folding {
+        Some(case_folding) if case_folding.equals(expected_item, item) => Ok(()),
+        Some(_) => Err(format!("Expected column {} value to equal '{}' (ignoring case), but got '{}'", index, expected_item, item)),
+        None if item == expected_item => Ok(()),
+        None => Err(format!("Expected column {} value to equal '{}', but got '{}'", index, expected_item, item))
+      },
+      category: MismatchCategory::Value
+    });
+  }
+  outcomes
 }
 
-impl Stream for TcpIncoming {
-  type Item = Result<TcpStream, std::io::Error>;
+/// Records the request for the idle timeout, and then checks the server key and the request limits
+/// for each request
+#[derive(Debug, Clone)]
+struct PluginInterceptor {
+  server_key: ServerKeyInterceptor,
+  limits: RequestLimitInterceptor,
+  idle: IdleTimer
+}
 
-  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-    Pin::new(&mut self.inner).poll_accept(cx)
-      .map_ok(|(stream, _)| stream).map(|v| Some(v))
+impl Interceptor for PluginInterceptor {
+  fn call(&mut self, request: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+    self.idle.touch();
+    let request = self.server_key.call(request)?;
+    self.limits.call(request)
   }
 }
 
+// The plugin gRPC service with the interceptor. The maximum request size is applied as the request
+// messages are decoded, so that a message that is too large is rejected from its length before it is
+// read into memory.
+fn plugin_service(
+  plugin: CsvPactPlugin,
+  interceptor: PluginInterceptor
+) -> InterceptedService<PactPluginServer<CsvPactPlugin>, PluginInterceptor> {
+  let max_message_size = plugin.limits.max_message_size;
+  InterceptedService::new(PactPluginServer::new(plugin).max_decoding_message_size(max_message_size), interceptor)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
   let env = Env::new().filter("LOG_LEVEL");
   env_logger::init_from_env(env);
 
-  let addr: SocketAddr = "0.0.0.0:0".parse()?;
-  let listener = TcpListener::bind(addr).await?;
-  let address = listener.local_addr()?;
+  let listener = PluginListener::from_env().await?;
 
-  let server_key = Uuid::new_v4().to_string();
-  println!("{{\"port\":{}, \"serverKey\":\"{}\"}}", address.port(), server_key);
+  let startup_info = PluginStartupInfo {
+    port: listener.port(),
+    socket: listener.socket(),
+    server_key: Uuid::new_v4().to_string(),
+    version: env!("CARGO_PKG_VERSION").to_string()
+  };
+  println!("{}", serde_json::to_string(&startup_info)?);
   let _ = io::stdout().flush();
 
-  let plugin = CsvPactPlugin::default();
-  Server::builder()
-    .add_service(PactPluginServer::new(plugin))
-    .serve_with_incoming(TcpIncoming { inner: listener }).await?;
+  let limits = RequestLimits::from_env();
+  let plugin = CsvPactPlugin { limits, .. CsvPactPlugin::default() };
+  let idle = IdleTimer::from_env();
+  let interceptor = PluginInterceptor {
+    server_key: ServerKeyInterceptor::new(&startup_info.server_key),
+    limits: RequestLimitInterceptor::new(limits),
+    idle: idle.clone()
+  };
+  // Without an idle timeout the shutdown signal never completes, and the server runs until the
+  // plugin process is killed
+  let router = Server::builder()
+    .add_service(plugin_service(plugin, interceptor));
+  match listener {
+    PluginListener::Tcp(listener) => router.serve_with_incoming_shutdown(TcpIncoming { inner: listener }, idle.idle()).await?,
+    #[cfg(unix)]
+    PluginListener::Unix(listener, path) => {
+      let result = router.serve_with_incoming_shutdown(listener::UnixIncoming::new(listener), idle.idle()).await;
+      listener::remove_socket(&path);
+      result?
+    }
+  }
 
+  if let Some(timeout) = idle.timeout() {
+    info!("No requests received in the last {} seconds, the CSV plugin has shut down", timeout.as_secs());
+  }
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use csv::{ReaderBuilder, StringRecord};
+  use expectest::prelude::*;
+  use maplit::{btreemap, hashmap};
+
+  use either::Either;
+  use pact_models::matchingrules::{MatchingRule, RuleList};
+  use pact_plugin_driver::utils::to_proto_struct;
+
+  use crate::archive::zip_archive;
+  use crate::compression::compress;
+  use crate::constraints::ColumnConstraint;
+  use crate::csv_content::setup_csv_contents;
+  use crate::matchers::CaseFolding;
+  use crate::options::{
+    body_content_type, ColumnOptions, CsvFormat, CsvOptions, HeaderOrder, is_csv_content_type, media_type
+  };
+  use crate::proto;
+  use crate::proto::pact_plugin_client::PactPluginClient;
+  use crate::proto::pact_plugin_server::PactPlugin;
+  use crate::rows::{RowOverride, RowSelector};
+
+  use super::{
+    blank_line_mismatches, catalogue_values, compare_archives, compare_contents, compare_csv_data, compare_headers,
+    content_type_mismatch, CsvPactPlugin, IdleTimer, matching_rules, plugin_service, PluginInterceptor,
+    PluginStartupInfo, RequestLimitInterceptor, RequestLimits, Server, ServerKeyInterceptor, TcpIncoming
+  };
+
+  fn content_mismatches(options: &CsvOptions, expected: &str, actual: &str) -> anyhow::Result<Vec<String>> {
+    let mut expected = ReaderBuilder::new().has_headers(options.has_headers).flexible(true).from_reader(expected.as_bytes());
+    let mut actual = ReaderBuilder::new().has_headers(options.has_headers).flexible(true).quote(options.quote)
+      .from_reader(actual.as_bytes());
+    let response = compare_contents(options, &mut expected, &mut actual, false, hashmap!{})?;
+    Ok(response.get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+      .collect())
+  }
+
+  fn header_mismatches(expected: &[&str], actual: &[&str], header_order: HeaderOrder) -> Vec<String> {
+    let mut results = vec![];
+    let options = CsvOptions { header_order, .. CsvOptions::default() };
+    compare_headers(&StringRecord::from(expected.to_vec()), &StringRecord::from(actual.to_vec()),
+      &options, false, &mut results);
+    results.iter().map(|mismatch| mismatch.mismatch.clone()).collect()
+  }
+
+  #[test]
+  fn compare_headers_test() {
+    expect!(header_mismatches(&["a", "b", "c"], &["a", "b", "c"], HeaderOrder::Strict).iter()).to(be_empty());
+    expect!(header_mismatches(&["a", "b", "c"], &["c", "a", "b"], HeaderOrder::Strict)).to(be_equal_to(vec![
+      "Expected the columns to be in the order [a, b, c], but got [c, a, b]".to_string()
+    ]));
+    expect!(header_mismatches(&["a", "b", "c"], &["c", "a", "b"], HeaderOrder::Any).iter()).to(be_empty());
+    expect!(header_mismatches(&["a", "b", "c"], &["d", "a", "b"], HeaderOrder::Any)).to(be_equal_to(vec![
+      "Expected columns 'c', but was missing".to_string(),
+      "Unexpected column 'd'".to_string()
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_any_header_order_compares_the_columns_by_name() {
+    let options = CsvOptions { header_order: HeaderOrder::Any, .. CsvOptions::default() };
+    let compare = |actual: &str| {
+      let mut expected = ReaderBuilder::new().flexible(true).from_reader("id,name,city\n1,Alice,Paris\n".as_bytes());
+      let mut actual = ReaderBuilder::new().flexible(true).from_reader(actual.as_bytes());
+      let response = compare_contents(&options, &mut expected, &mut actual, false, hashmap!{}).unwrap();
+      response.get_ref().results.values()
+        .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+        .collect::<Vec<_>>()
+    };
+    expect!(compare("city,id,name\nParis,1,Alice\n").iter()).to(be_empty());
+    expect!(compare("name,city,id\nBob,Paris,1\n")).to(be_equal_to(vec![
+      ("row:    2, column: 1".to_string(), "Expected column 1 value to equal 'Alice', but got 'Bob'".to_string())
+    ]));
+    expect!(compare("name,country,id\nAlice,France,1\n")).to(be_equal_to(vec![
+      ("".to_string(), "Expected columns 'city', but was missing".to_string()),
+      ("".to_string(), "Unexpected column 'country'".to_string()),
+      ("row:    2, column: 2".to_string(), "Expected column 2 value to equal 'Paris', but got ''".to_string())
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_schema_match() {
+    let options = CsvOptions { header_order: HeaderOrder::SchemaMatch, .. CsvOptions::default() };
+    let compare = |actual: &str, allow_unexpected_keys: bool| {
+      let mut expected = ReaderBuilder::new().flexible(true).from_reader("id,name\n1,Alice\n".as_bytes());
+      let mut actual = ReaderBuilder::new().flexible(true).from_reader(actual.as_bytes());
+      let rules = hashmap! {
+        "column:id".to_string() => RuleList::new(MatchingRule::Integer),
+        "column:name".to_string() => RuleList::new(MatchingRule::Type)
+      };
+      let response = compare_contents(&options, &mut expected, &mut actual, allow_unexpected_keys, rules).unwrap();
+      response.get_ref().results.values()
+        .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+        .collect::<Vec<_>>()
+    };
+    expect!(compare("name,id\nBob,2\nCarol,3\n", false).iter()).to(be_empty());
+    expect!(compare("name,id,email\nBob,x\n", true)).to(be_equal_to(vec![
+      ("header".to_string(), "Unexpected column 'email'".to_string()),
+      ("row:    2, column: 0".to_string(), "Expected 'x' to match an integer number".to_string())
+    ]));
+    expect!(compare("name\nBob\n", false)).to(be_equal_to(vec![
+      ("header".to_string(), "Expected columns 'id', but was missing".to_string()),
+      ("row:    2, column: 0".to_string(), "Expected '' to match an integer number".to_string())
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_single_record() {
+    let options = CsvOptions { single_record: true, .. CsvOptions::default() };
+    expect!(content_mismatches(&options, "a,b\n1,2\n", "a,b\n1,2\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "a,b\n1,2\n", "a,b\n1,2\n1,2\n").unwrap()).to(be_equal_to(vec![
+      "Expected a single record, but got 2".to_string()
+    ]));
+    expect!(content_mismatches(&options, "a,b\n1,2\n", "a,b\n").unwrap()).to(be_equal_to(vec![
+      "Expected a single record, but got 0".to_string()
+    ]));
+    expect!(content_mismatches(&options, "a,b\n1,2\n3,4\n", "a,b\n1,2\n")).to(be_err());
+  }
+
+  #[test]
+  fn compare_contents_with_row_overrides() {
+    let options = CsvOptions {
+      rows: vec![
+        RowOverride {
+          selector: RowSelector::Last,
+          column: "column:type".to_string(),
+          value: "TOTAL".to_string(),
+          matching_rules: vec![],
+          options: ColumnOptions::default()
+        },
+        RowOverride {
+          selector: RowSelector::Where(Either::Left(1), "TOTAL".to_string()),
+          column: "column:2".to_string(),
+          value: "0".to_string(),
+          matching_rules: vec![MatchingRule::Integer],
+          options: ColumnOptions::default()
+        }
+      ],
+      .. CsvOptions::default()
+    };
+    expect!(content_mismatches(&options, "type,amount\nITEM,10\n", "type,amount\nITEM,10\nITEM,10\nTOTAL,20\n")
+      .unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "type,amount\nITEM,10\n", "type,amount\nITEM,10\nTOTAL,20\nITEM,10\n")
+      .unwrap()).to(be_equal_to(vec![
+        "Expected column 0 value to equal 'ITEM', but got 'TOTAL'".to_string(),
+        "Expected column 0 value to equal 'TOTAL', but got 'ITEM'".to_string()
+      ]));
+  }
+
+  #[test]
+  fn compare_contents_with_a_regex_rule() {
+    let compare = |pattern: &str, actual: &str| {
+      let mut expected = ReaderBuilder::new().from_reader("code\n123\n".as_bytes());
+      let mut actual = ReaderBuilder::new().from_reader(actual.as_bytes());
+      let rules = hashmap! { "column:code".to_string() => RuleList::new(MatchingRule::Regex(pattern.to_string())) };
+      compare_contents(&CsvOptions::default(), &mut expected, &mut actual, false, rules).unwrap()
+        .get_ref().results.values()
+        .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+        .collect::<Vec<_>>()
+    };
+    expect!(compare("^[0-9]+$", "code\n456\n").iter()).to(be_empty());
+    expect!(compare("^[0-9]+$", "code\nabc\n")).to(be_equal_to(vec![
+      "Expected 'abc' to match /^[0-9]+$/".to_string()
+    ]));
+    expect!(compare("[0-9", "code\nabc\n")[0].starts_with("'[0-9' is not a valid regular expression")).to(be_true());
+  }
+
+  #[test]
+  fn compare_contents_with_aligned_columns() {
+    let options = CsvOptions { has_headers: false, align_columns: true, .. CsvOptions::default() };
+    let rules = hashmap! {
+      "column:1".to_string() => RuleList::new(MatchingRule::Regex("^\\d{4}-\\d{2}-\\d{2}$".to_string())),
+      "column:2".to_string() => RuleList::new(MatchingRule::Regex("^.+@.+$".to_string()))
+    };
+    let compare = |expected: &str, actual: &str| {
+      let mut expected = ReaderBuilder::new().has_headers(false).flexible(true).from_reader(expected.as_bytes());
+      let mut actual = ReaderBuilder::new().has_headers(false).flexible(true).from_reader(actual.as_bytes());
+      compare_contents(&options, &mut expected, &mut actual, false, rules.clone()).unwrap()
+        .get_ref().results.values()
+        .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+        .collect::<Vec<_>>()
+    };
+
+    expect!(compare("2000-01-01,a@b.com\n", "c@d.com,2023-05-06\ne@f.com,2023-05-07\n").iter()).to(be_empty());
+    expect!(compare("2000-01-01,a@b.com\n", "c@d.com,tomorrow\n")).to(be_equal_to(vec![
+      "Could not align expected column 1 with any of the actual columns".to_string()
+    ]));
+  }
+
+  #[test]
+  fn plugin_startup_info_test() {
+    let info = PluginStartupInfo { port: Some(1234), socket: None, server_key: "abc".to_string(), version: "0.0.4".to_string() };
+    expect!(serde_json::to_string(&info).unwrap()).to(be_equal_to("{\"port\":1234,\"serverKey\":\"abc\",\"version\":\"0.0.4\"}"));
+    let info = PluginStartupInfo { port: None, socket: Some("/tmp/csv.sock".to_string()), .. info };
+    expect!(serde_json::to_string(&info).unwrap()).to(be_equal_to("{\"socket\":\"/tmp/csv.sock\",\"serverKey\":\"abc\",\"version\":\"0.0.4\"}"));
+  }
+
+  #[test]
+  fn compare_contents_with_normalized_numbers() {
+    let options = CsvOptions { normalize_numbers: true, .. CsvOptions::default() };
+    expect!(content_mismatches(&options, "a,b\n100.50,x\n", "a,b\n100.5,x\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "a,b\n100.50,x\n", "a,b\n100.51,x\n").unwrap()).to(be_equal_to(vec![
+      "Expected column 0 value to equal '100.5', but got '100.51' (compared as the normalized values '100.5' and '100.51')".to_string()
+    ]));
+    expect!(content_mismatches(&CsvOptions::default(), "a,b\n100.50,x\n", "a,b\n100.5,x\n").unwrap().len()).to(be_equal_to(1));
+  }
+
+  #[test]
+  fn compare_contents_with_text_columns() {
+    let options = CsvOptions {
+      normalize_numbers: true,
+      columns: btreemap! {
+        "column:zip".to_string() => ColumnOptions { text: true, .. ColumnOptions::default() }
+      },
+      .. CsvOptions::default()
+    };
+    expect!(content_mismatches(&options, "zip,amount\n01230.0,1.50\n", "zip,amount\n01230.0,1.5\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "zip,amount\n01230.0,1.50\n", "zip,amount\n01230,1.5\n").unwrap()).to(be_equal_to(vec![
+      "Expected column 0 value to equal '01230.0', but got '01230'".to_string()
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_quoted_values() {
+    let options = CsvOptions::default();
+    expect!(content_mismatches(&options, "a,b\n\"1,5\",x\n", "a,b\n\"1,5\",\"x\"\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "a,b\n\"x\",y\n", "\"a\",b\nx,\"y\"\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "a,b\n\"1,5\",x\n", "a,b\n'1,5',x\n").unwrap().iter()).to_not(be_empty());
+
+    let options = CsvOptions { quote: b'\'', .. CsvOptions::default() };
+    expect!(content_mismatches(&options, "a,b\n\"1,5\",x\n", "a,b\n'1,5',x\n").unwrap().iter()).to(be_empty());
+  }
+
+  #[test]
+  fn compare_contents_with_optional_columns() {
+    let optional = ColumnOptions { optional: true, .. ColumnOptions::default() };
+    let options = CsvOptions {
+      has_headers: false,
+      columns: btreemap! { "column:3".to_string() => optional.clone() },
+      .. CsvOptions::default()
+    };
+    expect!(content_mismatches(&options, "1,2,3\n", "1,2,3\n1,2\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "1,2,3\n", "1,2,4\n").unwrap()).to(be_equal_to(vec![
+      "Expected column 2 value to equal '3', but got '4'".to_string()
+    ]));
+    expect!(content_mismatches(&options, "1,2,3\n", "1\n").unwrap()).to(be_equal_to(vec![
+      "Expected 2 columns, but got 1".to_string(),
+      "Expected column 1 value to equal '2', but got ''".to_string()
+    ]));
+
+    let options = CsvOptions {
+      columns: btreemap! { "column:c".to_string() => optional },
+      .. CsvOptions::default()
+    };
+    expect!(content_mismatches(&options, "a,b,c\n1,2,3\n", "a,b\n1,2\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "a,b,c\n1,2,3\n", "a,c\n1,3\n").unwrap()).to(be_equal_to(vec![
+      "Expected columns 'b', but was missing".to_string(),
+      "Expected column 1 value to equal '2', but got ''".to_string()
+    ]));
+  }
+
+  #[test]
+  fn blank_line_mismatches_test() {
+    let messages = |data: &str| blank_line_mismatches(data.as_bytes()).iter()
+      .map(|mismatch| mismatch.mismatch.clone())
+      .collect::<Vec<_>>();
+    expect!(messages("a,b\n1,2\n").iter()).to(be_empty());
+    expect!(messages("a,b\r\n1,2").iter()).to(be_empty());
+    expect!(messages("\n\r\na,b\n1,2\n")).to(be_equal_to(vec![
+      "Expected the contents to not start with blank lines, but found 2".to_string()
+    ]));
+    expect!(messages("a,b\n1,2\n\n  \n")).to(be_equal_to(vec![
+      "Expected the contents to not end with blank lines, but found 2".to_string()
+    ]));
+    expect!(messages("\n\n")).to(be_equal_to(vec![
+      "Expected the contents to not start with blank lines, but found 2".to_string()
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_mismatch_categories() {
+    let options = CsvOptions {
+      mismatch_categories: true,
+      columns: btreemap! {
+        "column:tag".to_string() => ColumnOptions { multiset: true, .. ColumnOptions::default() }
+      },
+      rows: vec![
+        RowOverride::from_json(&serde_json::json!({
+          "selector": "where(column:id='2')", "column": "column:code", "value": "X",
+          "matchingRules": [{ "match": "regex", "regex": "^X$" }]
+        })).unwrap()
+      ],
+      .. CsvOptions::default()
+    };
+    let expected = "id,code,tag\n1,A1,a\n";
+    let actual = "id,code,other\n2,B,b\n";
+    expect!(content_mismatches(&options, expected, actual).unwrap()).to(be_equal_to(vec![
+      "[structural] Expected columns 'tag', but was missing".to_string(),
+      "[structural] Unexpected column 'other'".to_string(),
+      "[value] Expected column 0 value to equal '1', but got '2'".to_string(),
+      "[crossField] Expected 'B' to match /^X$/".to_string(),
+      "[aggregate] Expected the value 'a' to occur 1 times in column tag, but it occurred 0 times (under-represented)".to_string()
+    ]));
+
+    let options = CsvOptions::default();
+    expect!(content_mismatches(&options, "id\n1\n", "id\n2\n").unwrap()).to(be_equal_to(vec![
+      "Expected column 0 value to equal '1', but got '2'".to_string()
+    ]));
+  }
+
+  #[test]
+  fn matching_rules_reports_all_the_invalid_rules() {
+    let rule = |r#type: &str| proto::MatchingRule { r#type: r#type.to_string(), values: None };
+    let rules = hashmap! {
+      "column:1".to_string() => proto::MatchingRules { rule: vec![rule("type")] },
+      "column:2".to_string() => proto::MatchingRules { rule: vec![rule("unknown")] },
+      "column:name".to_string() => proto::MatchingRules { rule: vec![rule("integer"), rule("other")] }
+    };
+    let err = matching_rules(&rules).unwrap_err().to_string();
+    expect!(err.starts_with("Invalid matching rules received for the CSV contents: column:2: 'unknown' is not a valid matching rule"))
+      .to(be_true());
+    expect!(err.contains("; column:name: 'other' is not a valid matching rule")).to(be_true());
+
+    let rules = hashmap! {
+      "column:1".to_string() => proto::MatchingRules { rule: vec![rule("type")] }
+    };
+    expect!(matching_rules(&rules).unwrap().get("column:1").map(|rules| rules.rules.clone()))
+      .to(be_some().value(vec![MatchingRule::Type]));
+  }
+
+  #[tokio::test]
+  async fn compare_contents_request_with_invalid_rules_or_missing_content() {
+    let body = |content: Option<&str>| proto::Body {
+      content_type: "text/csv".to_string(),
+      content: content.map(|content| content.as_bytes().to_vec()),
+      content_type_hint: 0
+    };
+    let rule = proto::MatchingRule {
+      r#type: "unknown".to_string(),
+      values: Some(prost_types::Struct { fields: btreemap! { "value".to_string() => prost_types::Value { kind: None } } })
+    };
+    let plugin = CsvPactPlugin::default();
+    let err = plugin.compare_contents(tonic::Request::new(proto::CompareContentsRequest {
+      expected: Some(body(Some("id\n1\n"))),
+      actual: Some(body(Some("id\n2\n"))),
+      rules: hashmap! { "column:id".to_string() => proto::MatchingRules { rule: vec![rule] } },
+      .. proto::CompareContentsRequest::default()
+    })).await.unwrap_err();
+    expect!(err.code()).to(be_equal_to(tonic::Code::InvalidArgument));
+    expect!(err.message().starts_with("Invalid matching rules received for the CSV contents: column:id: 'unknown' is not a valid matching rule"))
+      .to(be_true());
+
+    // Bodies without any content are compared as empty contents
+    let response = plugin.compare_contents(tonic::Request::new(proto::CompareContentsRequest {
+      expected: Some(body(Some("id\n1\n"))),
+      actual: Some(body(None)),
+      .. proto::CompareContentsRequest::default()
+    })).await.unwrap();
+    expect!(response.get_ref().results[""].mismatches[0].mismatch.clone())
+      .to(be_equal_to("Expected 2 records, but the CSV content was empty".to_string()));
+    let response = plugin.compare_contents(tonic::Request::new(proto::CompareContentsRequest {
+      actual: Some(body(None)),
+      .. proto::CompareContentsRequest::default()
+    })).await.unwrap();
+    expect!(response.get_ref().results[""].mismatches[0].mismatch.clone())
+      .to(be_equal_to("Expected no CSV content, but got 0 bytes".to_string()));
+  }
+
+  #[tokio::test]
+  async fn compare_contents_request_limits_the_mismatches_for_each_column() {
+    let body = |content: String| proto::Body {
+      content_type: "text/csv".to_string(),
+      content: Some(content.into_bytes()),
+      content_type_hint: 0
+    };
+    let expected = (1..=15).map(|row| format!("{},a\n", row)).collect::<String>();
+    let actual = (1..=15).map(|row| format!("{},b\n", row * 10)).collect::<String>();
+    let plugin = CsvPactPlugin::default();
+    let compare = |options: CsvOptions| plugin.compare_contents(tonic::Request::new(proto::CompareContentsRequest {
+      expected: Some(body(format!("id,name\n{}", expected))),
+      actual: Some(body(format!("id,name\n{}", actual))),
+      plugin_configuration: Some(proto::PluginConfiguration {
+        interaction_configuration: Some(to_proto_struct(&options.to_config())),
+        pact_configuration: None
+      }),
+      .. proto::CompareContentsRequest::default()
+    }));
+
+    let response = compare(CsvOptions::default()).await.unwrap();
+    let mismatches = &response.get_ref().results[""].mismatches;
+    expect!(mismatches.len()).to(be_equal_to(22));
+    expect!(mismatches[20].mismatch.as_str()).to(be_equal_to("and 5 more mismatches in column:0"));
+    expect!(mismatches[21].mismatch.as_str()).to(be_equal_to("and 5 more mismatches in column:1"));
+    expect!(mismatches[21].path.as_str()).to(be_equal_to("column:1"));
+
+    let response = compare(CsvOptions { max_column_mismatches: 0, .. CsvOptions::default() }).await.unwrap();
+    expect!(response.get_ref().results[""].mismatches.len()).to(be_equal_to(30));
+  }
+
+  #[tokio::test]
+  async fn requests_larger_than_the_maximum_message_size_are_rejected() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let limits = RequestLimits { max_message_size: 1024, rate_limit: None };
+    let interceptor = PluginInterceptor {
+      server_key: ServerKeyInterceptor::new("key"),
+      limits: RequestLimitInterceptor::new(limits),
+      idle: IdleTimer::new(None)
+    };
+    let service = plugin_service(CsvPactPlugin { limits, .. CsvPactPlugin::default() }, interceptor);
+    let server = tokio::spawn(Server::builder().add_service(service)
+      .serve_with_incoming(TcpIncoming { inner: listener }));
+
+    let mut client = PactPluginClient::connect(format!("http://{}", address)).await.unwrap();
+    let request = |size: usize| {
+      let mut request = tonic::Request::new(proto::CompareContentsRequest {
+        actual: Some(proto::Body {
+          content_type: "text/csv".to_string(),
+          content: Some(vec![b'x'; size]),
+          content_type_hint: 0
+        }),
+        .. proto::CompareContentsRequest::default()
+      });
+      request.metadata_mut().insert("authorization", "key".parse().unwrap());
+      request
+    };
+    let err = client.compare_contents(request(2048)).await.unwrap_err();
+    expect!(err.code()).to(be_equal_to(tonic::Code::OutOfRange));
+    expect!(client.compare_contents(request(100)).await).to(be_ok());
+    server.abort();
+  }
+
+  #[tokio::test]
+  async fn compare_contents_request_does_not_return_the_explanation() {
+    let body = |content: &str| proto::Body {
+      content_type: "text/csv".to_string(),
+      content: Some(content.as_bytes().to_vec()),
+      content_type_hint: 0
+    };
+    let plugin = CsvPactPlugin::default();
+    let options = CsvOptions { explain: true, .. CsvOptions::default() };
+    let compare = |actual: &str| plugin.compare_contents(tonic::Request::new(proto::CompareContentsRequest {
+      expected: Some(body("id,name\n1,Alice\n")),
+      actual: Some(body(actual)),
+      plugin_configuration: Some(proto::PluginConfiguration {
+        interaction_configuration: Some(to_proto_struct(&options.to_config())),
+        pact_configuration: None
+      }),
+      .. proto::CompareContentsRequest::default()
+    }));
+
+    // Matching contents are still a pass, as the driver treats any entry in the results as a mismatch
+    let response = compare("id,name\n1,Alice\n").await.unwrap().into_inner();
+    expect!(response.results.values().flat_map(|mismatches| mismatches.mismatches.iter())).to(be_empty());
+
+    let response = compare("id,name\n1,Bob\n").await.unwrap().into_inner();
+    let entries = response.results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter())
+      .map(|entry| (entry.path.clone(), entry.mismatch.clone()))
+      .collect::<Vec<_>>();
+    expect!(entries).to(be_equal_to(vec![
+      ("row:    2, column: 1".to_string(), "Expected column 1 value to equal 'Alice', but got 'Bob'".to_string())
+    ]));
+  }
+
+  #[tokio::test]
+  async fn compare_contents_request_with_gzip_compressed_contents() {
+    let body = |content: Vec<u8>| proto::Body {
+      content_type: "text/csv".to_string(),
+      content: Some(content),
+      content_type_hint: 0
+    };
+    let plugin = CsvPactPlugin::default();
+    let compare = |expected: Vec<u8>, actual: Vec<u8>| plugin.compare_contents(tonic::Request::new(proto::CompareContentsRequest {
+      expected: Some(body(expected)),
+      actual: Some(body(actual)),
+      .. proto::CompareContentsRequest::default()
+    }));
+    let messages = |response: tonic::Response<proto::CompareContentsResponse>| response.into_inner().results.into_values()
+      .flat_map(|mismatches| mismatches.mismatches.into_iter().map(|mismatch| mismatch.mismatch))
+      .collect::<Vec<_>>();
+
+    let expected = b"id,name\n1,Alice\n".to_vec();
+    let response = compare(expected.clone(), compress(&expected).unwrap()).await.unwrap();
+    expect!(messages(response).iter()).to(be_empty());
+    let response = compare(compress(&expected).unwrap(), compress(b"id,name\n2,Alice\n").unwrap()).await.unwrap();
+    expect!(messages(response)).to(be_equal_to(vec!["Expected column 0 value to equal '1', but got '2'".to_string()]));
+
+    let err = compare(expected, vec![0x1f, 0x8b, 0]).await.unwrap_err();
+    expect!(err.message().starts_with("Failed to read the actual CSV contents: Failed to decompress the gzip contents"))
+      .to(be_true());
+  }
+
+  #[test]
+  fn compare_archives_by_member() {
+    let options = CsvOptions { format: CsvFormat::Zip, .. CsvOptions::default() };
+    let expected = zip_archive(&[("orders.csv", "id,total\n1,10\n"), ("items.csv", "id\n1\n"), ("notes.txt", "x")]);
+    let actual = zip_archive(&[("orders.csv", "id,total\n1,12\n"), ("extra.csv", "id\n1\n")]);
+    let response = compare_archives(&options, &expected, &actual, false, &hashmap!{}).unwrap();
+    let mismatches = response.get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+      .collect::<Vec<_>>();
+    expect!(mismatches).to(be_equal_to(vec![
+      ("member:items.csv".to_string(), "Expected archive member 'items.csv', but was missing".to_string()),
+      ("member:orders.csv, row:    2, column: 1".to_string(), "Expected column 1 value to equal '10', but got '12'".to_string()),
+      ("member:extra.csv".to_string(), "Unexpected archive member 'extra.csv'".to_string())
+    ]));
+
+    let response = compare_archives(&options, &expected, &actual, true, &hashmap!{}).unwrap();
+    expect!(response.get_ref().results.values().map(|mismatches| mismatches.mismatches.len()).sum::<usize>()).to(be_equal_to(2));
+    expect!(compare_archives(&options, b"id\n1\n", &actual, false, &hashmap!{})).to(be_err());
+  }
+
+  #[test]
+  fn configure_and_compare_tsv_contents() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(prost_types::value::Kind::StringValue(value.to_string())) };
+    let request = tonic::Request::new(proto::ConfigureInteractionRequest {
+      content_type: "text/tab-separated-values".to_string(),
+      contents_config: Some(prost_types::Struct {
+        fields: btreemap! {
+          "column:name".to_string() => string_value("matching(type, 'Alice')"),
+          "column:age".to_string() => string_value("matching(integer, 30)")
+        }
+      })
+    });
+    let response = setup_csv_contents(&request).unwrap();
+    let interaction = &response.get_ref().interaction[0];
+    let contents = interaction.contents.as_ref().unwrap();
+    expect!(contents.content_type.as_str()).to(be_equal_to("text/tab-separated-values;charset=UTF-8"));
+    expect!(contents.content.clone().unwrap()).to(be_equal_to(b"age\tname\n30\tAlice\n".to_vec()));
+
+    let options = CsvOptions::from_plugin_config(&interaction.plugin_configuration).unwrap()
+      .with_content_type(&contents.content_type).unwrap();
+    let rules = matching_rules(&interaction.rules).unwrap();
+    let mismatches = |actual: &[u8]| compare_csv_data(&options, contents.content.as_ref().unwrap(), actual, false, rules.clone())
+      .unwrap().get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+      .collect::<Vec<_>>();
+    expect!(mismatches(b"age\tname\n42\tBob\n").iter()).to(be_empty());
+    expect!(mismatches(b"age\tname\nforty\tBob\n")).to(be_equal_to(vec![
+      "Expected 'forty' to match an integer number".to_string()
+    ]));
+  }
+
+  #[test]
+  fn compare_csv_data_with_invalid_utf8() {
+    let actual = b"id,name\n1,Al\xffce\n";
+    expect!(compare_csv_data(&CsvOptions::default(), b"id,name\n1,Alice\n", actual, false, hashmap!{})).to(be_err());
+    let options = CsvOptions { lossy_utf8: true, .. CsvOptions::default() };
+    let response = compare_csv_data(&options, b"id,name\n1,Alice\n", actual, false, hashmap!{}).unwrap();
+    let mismatches = response.get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+      .collect::<Vec<_>>();
+    expect!(mismatches).to(be_equal_to(vec![
+      "Expected column 1 value to equal 'Alice', but got 'Al\u{FFFD}ce'".to_string()
+    ]));
+  }
+
+  #[test]
+  fn content_type_mismatch_test() {
+    let body = |content_type: &str| proto::Body { content_type: content_type.to_string(), .. proto::Body::default() };
+    let options = CsvOptions::default();
+    expect!(content_type_mismatch(&body("text/csv"), &body("text/csv; charset=UTF-8"), &options)).to(be_none());
+    expect!(content_type_mismatch(&body("text/csv"), &body("Text/CSV"), &options)).to(be_none());
+    expect!(content_type_mismatch(&body("text/csv"), &body("text/tab-separated-values"), &options)).to(be_none());
+    expect!(content_type_mismatch(&body("text/csv"), &body("application/vnd.report+csv"), &options)).to(be_none());
+    expect!(content_type_mismatch(&body("text/csv"), &body(""), &options)).to(be_none());
+    expect!(content_type_mismatch(&body("text/csv;charset=UTF-8"), &body("application/json"), &options)).to(be_some().value(
+      proto::ContentTypeMismatch { expected: "text/csv;charset=UTF-8".to_string(), actual: "application/json".to_string() }));
+    expect!(content_type_mismatch(&body(""), &body("application/json"), &options).map(|mismatch| mismatch.expected))
+      .to(be_some().value("text/csv".to_string()));
+    let options = CsvOptions { format: CsvFormat::Zip, .. CsvOptions::default() };
+    expect!(content_type_mismatch(&body("application/zip"), &body("application/zip"), &options)).to(be_none());
+  }
+
+  #[test]
+  fn content_types_are_compared_ignoring_case_and_parameters() {
+    expect!(media_type("TEXT/CSV; charset=utf-8")).to(be_equal_to("text/csv".to_string()));
+    expect!(is_csv_content_type("Application/CSV")).to(be_true());
+    expect!(is_csv_content_type("text/plain; charset=text/csv")).to(be_false());
+    expect!(body_content_type("TEXT/Tab-Separated-Values; charset=utf-8")).to(be_equal_to("text/tab-separated-values;charset=UTF-8"));
+    expect!(body_content_type("application/csv")).to(be_equal_to("text/csv;charset=UTF-8"));
+    expect!(catalogue_values().get("content-types").cloned())
+      .to(be_some().value("text/csv;application/csv;text/tab-separated-values".to_string()));
+    expect!(catalogue_values().get("capability:generators").cloned())
+      .to(be_some().value("sequence;rowIndex;weighted".to_string()));
+  }
+
+  #[test]
+  fn compare_csv_data_with_max_field_size() {
+    let options = CsvOptions { max_field_size: 5, .. CsvOptions::default() };
+    let response = compare_csv_data(&options, b"id,name\n1,a\n", b"id,name\n1,\"abcdef\"\n", false, hashmap!{}).unwrap();
+    let mismatches = response.get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+      .collect::<Vec<_>>();
+    expect!(mismatches).to(be_equal_to(vec![
+      "Could not read the actual content: Line 2 has a field that is larger than the maximum field size of 5 bytes".to_string()
+    ]));
+    expect!(compare_csv_data(&options, b"id,name\n1,abcdef\n", b"id,name\n1,a\n", false, hashmap!{})).to(be_err());
+  }
+
+  #[test]
+  fn compare_contents_with_exact_columns() {
+    let options = CsvOptions { has_headers: false, exact_columns: Some(3), .. CsvOptions::default() };
+    let mut expected = ReaderBuilder::new().has_headers(false).flexible(true).from_reader("1,2,3\n".as_bytes());
+    let mut actual = ReaderBuilder::new().has_headers(false).flexible(true)
+      .from_reader("1,2,3\n1,2\n1,2,3,4\n".as_bytes());
+    let response = compare_contents(&options, &mut expected, &mut actual, true, hashmap!{}).unwrap();
+    let mismatches = response.get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+      .collect::<Vec<_>>();
+    expect!(mismatches).to(be_equal_to(vec![
+      "Expected exactly 3 columns in row 2, but got 2".to_string(),
+      "Expected column 2 value to equal '3', but got ''".to_string(),
+      "Expected exactly 3 columns in row 3, but got 4".to_string()
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_ignoring_case() {
+    let options = CsvOptions { ignore_case: Some(CaseFolding::Simple), .. CsvOptions::default() };
+    expect!(content_mismatches(&options, "name,city\nAlice,Zürich\n", "name,city\nALICE,zürich\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "name\nAlice\n", "name\nBob\n").unwrap()).to(be_equal_to(vec![
+      "Expected column 0 value to equal 'Alice' (ignoring case), but got 'Bob'".to_string()
+    ]));
+
+    let options = CsvOptions { ignore_case: Some(CaseFolding::Locale("tr-TR".to_string())), .. CsvOptions::default() };
+    expect!(content_mismatches(&options, "city\nistanbul\n", "city\nİSTANBUL\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "city\nistanbul\n", "city\nISTANBUL\n").unwrap().len()).to(be_equal_to(1));
+  }
+
+  #[test]
+  fn compare_contents_with_only_headers() {
+    let options = CsvOptions::default();
+    expect!(content_mismatches(&options, "a,b\n", "a,b\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "a,b", "a,b").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "a,b\n", "a,c\n").unwrap()).to(be_equal_to(vec![
+      "Expected columns 'b', but was missing".to_string(),
+      "Unexpected column 'c'".to_string()
+    ]));
+    expect!(content_mismatches(&options, "a,b\n", "a,b,c\n").unwrap()).to(be_equal_to(vec![
+      "Unexpected column 'c'".to_string()
+    ]));
+    expect!(content_mismatches(&options, "a,b,c\n", "a,b\n").unwrap()).to(be_equal_to(vec![
+      "Expected columns 'c', but was missing".to_string()
+    ]));
+    expect!(content_mismatches(&options, "a,b\n", "a,b\n1,2\n").unwrap()).to(be_equal_to(vec![
+      "Expected no data rows after the header, but got 1".to_string()
+    ]));
+    expect!(content_mismatches(&options, "a,b\n1,2\n3,4\n", "a,b\n").unwrap()).to(be_equal_to(vec![
+      "Expected 2 rows, but got 0".to_string()
+    ]));
+    expect!(content_mismatches(&options, "a,b\n1,2\n", "a,c").unwrap()).to(be_equal_to(vec![
+      "Expected columns 'b', but was missing".to_string(),
+      "Unexpected column 'c'".to_string(),
+      "Expected 1 rows, but got 0".to_string()
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_multiset_columns() {
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:tag".to_string() => ColumnOptions { multiset: true, .. ColumnOptions::default() }
+      },
+      .. CsvOptions::default()
+    };
+    let expected = "id,tag\n1,a\n1,b\n1,a\n";
+    expect!(content_mismatches(&options, expected, "id,tag\n1,b\n1,a\n1,a\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, expected, "id,tag\n1,b\n1,b\n1,a\n").unwrap()).to(be_equal_to(vec![
+      "Expected the value 'a' to occur 2 times in column tag, but it occurred 1 times (under-represented)".to_string(),
+      "Expected the value 'b' to occur 1 times in column tag, but it occurred 2 times (over-represented)".to_string()
+    ]));
+    expect!(content_mismatches(&options, expected, "id,tag\n2,b\n1,a\n1,a\n").unwrap()).to(be_equal_to(vec![
+      "Expected column 0 value to equal '1', but got '2'".to_string()
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_homogeneous_columns() {
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:amount".to_string() => ColumnOptions { homogeneous: true, .. ColumnOptions::default() }
+      },
+      .. CsvOptions::default()
+    };
+    let mut expected = ReaderBuilder::new().from_reader("id,amount\n1,10\n".as_bytes());
+    let mut actual = ReaderBuilder::new().from_reader("id,amount\n1,10\n2,\n3,2023-01-01\n4,x\n".as_bytes());
+    let rules = hashmap! {
+      "column:id".to_string() => RuleList::new(MatchingRule::Integer),
+      "column:amount".to_string() => RuleList::new(MatchingRule::Type)
+    };
+    let response = compare_contents(&options, &mut expected, &mut actual, false, rules).unwrap();
+    let mismatches = response.get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+      .collect::<Vec<_>>();
+    expect!(mismatches).to(be_equal_to(vec![
+      "Expected all the values of column amount to be the same type as row 2 (an integer), but row 4 has a date '2023-01-01'".to_string()
+    ]));
+  }
+
+  #[test]
+  fn configure_and_compare_include_columns() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(prost_types::value::Kind::StringValue(value.to_string())) };
+    let request = tonic::Request::new(proto::ConfigureInteractionRequest {
+      content_type: "text/csv".to_string(),
+      contents_config: Some(prost_types::Struct {
+        fields: btreemap! {
+          "column:id".to_string() => string_value("matching(integer, 1)"),
+          "column:notes".to_string() => string_value("matching(include, 'SPRING23')")
+        }
+      })
+    });
+    let response = setup_csv_contents(&request).unwrap();
+    let interaction = &response.get_ref().interaction[0];
+    let contents = interaction.contents.as_ref().unwrap();
+    expect!(contents.content.clone().unwrap()).to(be_equal_to(b"id,notes\n1,SPRING23\n".to_vec()));
+    let rules = matching_rules(&interaction.rules).unwrap();
+    expect!(rules.get("column:notes").map(|rules| rules.rules.clone()))
+      .to(be_some().value(vec![MatchingRule::Include("SPRING23".to_string())]));
+
+    let options = CsvOptions::from_plugin_config(&interaction.plugin_configuration).unwrap();
+    let mismatches = compare_csv_data(&options, contents.content.as_ref().unwrap(),
+      b"id,notes\n1,Use code SPRING23 at checkout\n2,foo bar\n", false, rules)
+      .unwrap().get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+      .collect::<Vec<_>>();
+    expect!(mismatches).to(be_equal_to(vec![
+      ("row:    3, column: 1".to_string(), "Expected 'foo bar' to include 'SPRING23'".to_string())
+    ]));
+  }
+
+  #[test]
+  fn configure_and_compare_not_empty_and_empty_columns() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(prost_types::value::Kind::StringValue(value.to_string())) };
+    let request = tonic::Request::new(proto::ConfigureInteractionRequest {
+      content_type: "text/csv".to_string(),
+      contents_config: Some(prost_types::Struct {
+        fields: btreemap! {
+          "column:name".to_string() => string_value("matching(notEmpty, 'Alice')"),
+          "column:reserved".to_string() => string_value("matching(empty)")
+        }
+      })
+    });
+    let response = setup_csv_contents(&request).unwrap();
+    let interaction = &response.get_ref().interaction[0];
+    let contents = interaction.contents.as_ref().unwrap();
+    expect!(contents.content.clone().unwrap()).to(be_equal_to(b"name,reserved\nAlice,\n".to_vec()));
+
+    let options = CsvOptions::from_plugin_config(&interaction.plugin_configuration).unwrap();
+    let rules = matching_rules(&interaction.rules).unwrap();
+    let mismatches = compare_csv_data(&options, contents.content.as_ref().unwrap(),
+      b"name,reserved\nBob,\n,\n\" \",\"\"\nCarol,x\n", false, rules)
+      .unwrap().get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+      .collect::<Vec<_>>();
+    expect!(mismatches).to(be_equal_to(vec![
+      ("row:    3, column: 0".to_string(), "Expected a non-empty value, but got an empty value".to_string()),
+      ("row:    4, column: 0".to_string(), "Expected a non-empty value, but got only whitespace ' '".to_string()),
+      ("row:    5, column: 1".to_string(), "Expected an empty value, but got 'x'".to_string())
+    ]));
+  }
+
+  #[test]
+  fn configure_and_compare_decimal_places_columns() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(prost_types::value::Kind::StringValue(value.to_string())) };
+    let request = tonic::Request::new(proto::ConfigureInteractionRequest {
+      content_type: "text/csv".to_string(),
+      contents_config: Some(prost_types::Struct {
+        fields: btreemap! {
+          "column:id".to_string() => string_value("matching(integer, 1)"),
+          "column:amount".to_string() => string_value("decimal(2)")
+        }
+      })
+    });
+    let response = setup_csv_contents(&request).unwrap();
+    let interaction = &response.get_ref().interaction[0];
+    let contents = interaction.contents.as_ref().unwrap();
+    expect!(contents.content.clone().unwrap()).to(be_equal_to(b"amount,id\n0.00,1\n".to_vec()));
+
+    let options = CsvOptions::from_plugin_config(&interaction.plugin_configuration).unwrap();
+    let rules = matching_rules(&interaction.rules).unwrap();
+    let mismatches = compare_csv_data(&options, contents.content.as_ref().unwrap(),
+      b"amount,id\n12.50,1\n12.5,2\n", false, rules)
+      .unwrap().get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+      .collect::<Vec<_>>();
+    expect!(mismatches).to(be_equal_to(vec![
+      ("row:    3, column: 0".to_string(), "Expected '12.5' to have 2 decimal places, but it has 1".to_string())
+    ]));
+  }
+
+  #[test]
+  fn configure_and_compare_each_value_columns() {
+    let string_value = |value: &str| prost_types::Value { kind: Some(prost_types::value::Kind::StringValue(value.to_string())) };
+    let request = tonic::Request::new(proto::ConfigureInteractionRequest {
+      content_type: "text/csv".to_string(),
+      contents_config: Some(prost_types::Struct {
+        fields: btreemap! {
+          "column:code".to_string() => string_value("eachValue(matching(regex, '^[A-Z]{3}$', 'ABC'))")
+        }
+      })
+    });
+    let response = setup_csv_contents(&request).unwrap();
+    let interaction = &response.get_ref().interaction[0];
+    let contents = interaction.contents.as_ref().unwrap();
+    expect!(contents.content.clone().unwrap()).to(be_equal_to(b"code\nABC\n".to_vec()));
+
+    let options = CsvOptions::from_plugin_config(&interaction.plugin_configuration).unwrap();
+    expect!(options.column(1, "code").map(|column| column.each_value)).to(be_some().value(true));
+    let rules = matching_rules(&interaction.rules).unwrap();
+    let mismatches = |expected: &[u8], actual: &[u8]| compare_csv_data(&options, expected, actual, false, rules.clone())
+      .unwrap().get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+      .collect::<Vec<_>>();
+    expect!(mismatches(contents.content.as_ref().unwrap(), b"code\nXYZ\nab\nDEF\n1234\n")).to(be_equal_to(vec![
+      ("row:    3, column: 0".to_string(), "Expected 'ab' to match /^[A-Z]{3}$/".to_string()),
+      ("row:    5, column: 0".to_string(), "Expected '1234' to match /^[A-Z]{3}$/".to_string())
+    ]));
+
+    // The rows after the last of several expected rows are still checked
+    let mismatches = mismatches(b"code\nABC\nDEF\n", b"code\nABC\nDEF\nghi\n");
+    expect!(mismatches.iter().any(|(path, mismatch)| path == "row:    4, column: 0"
+      && mismatch == "Expected 'ghi' to match /^[A-Z]{3}$/")).to(be_true());
+  }
+
+  #[test]
+  fn compare_contents_with_several_expected_rows() {
+    let options = CsvOptions::default();
+    let expected = "id,name\n1,a\n2,b\n3,c\n";
+    expect!(content_mismatches(&options, expected, expected).unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, expected, "id,name\n1,a\n2,x\n3,c\n").unwrap()).to(be_equal_to(vec![
+      "Expected column 1 value to equal 'b', but got 'x'".to_string()
+    ]));
+    expect!(content_mismatches(&options, expected, "id,name\n1,a\n").unwrap()).to(be_equal_to(vec![
+      "Expected 3 rows, but got 1".to_string(),
+      "Expected row 2 '2,b', but was missing".to_string(),
+      "Expected row 3 '3,c', but was missing".to_string()
+    ]));
+    expect!(content_mismatches(&options, expected, "id,name\n1,a\n2,b\n3,c\n4,d\n").unwrap()).to(be_equal_to(vec![
+      "Expected 3 rows, but got 4".to_string()
+    ]));
+
+    // A single expected row is compared with all the actual rows
+    expect!(content_mismatches(&options, "id,name\n1,a\n", "id,name\n1,a\n1,a\n").unwrap().iter()).to(be_empty());
+  }
+
+  #[test]
+  fn compare_contents_with_columns_that_are_not_in_the_header() {
+    let options = CsvOptions {
+      columns: btreemap! {
+        "column:total_amount".to_string() => ColumnOptions { text: true, .. ColumnOptions::default() }
+      },
+      .. CsvOptions::default()
+    };
+    expect!(content_mismatches(&options, "id,total_amount\n1,10\n", "id,total_amount\n1,10\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "id,total\n1,10\n", "id,total\n1,10\n").unwrap()).to(be_equal_to(vec![
+      "Column 'total_amount' has a definition, but is not in the expected header row".to_string()
+    ]));
+
+    let options = CsvOptions { has_headers: false, .. options };
+    expect!(content_mismatches(&options, "1,10\n", "1,10\n").unwrap()).to(be_equal_to(vec![
+      "Column 'total_amount' is defined by name, but the contents do not have a header row".to_string()
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_thousands_separators() {
+    let options = CsvOptions { thousands_separators: vec![',', ' ', '\u{a0}'], .. CsvOptions::default() };
+    expect!(content_mismatches(&options, "amount\n\"1,234\"\n", "amount\n1234\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "amount\n1234.5\n", "amount\n1\u{a0}234.5\n").unwrap().iter()).to(be_empty());
+    expect!(content_mismatches(&options, "amount\n\"1,234\"\n", "amount\n1 235\n").unwrap()).to(be_equal_to(vec![
+      "Expected column 0 value to equal '1234', but got '1235' (compared as the numeric values '1234' and '1235')".to_string()
+    ]));
+    expect!(content_mismatches(&options, "code\nA 1\n", "code\nA1\n").unwrap().len()).to(be_equal_to(1));
+
+    let options = CsvOptions { normalize_numbers: true, .. options };
+    expect!(content_mismatches(&options, "amount\n\"1,234.50\"\n", "amount\n1234.5\n").unwrap().iter()).to(be_empty());
+  }
+
+  #[test]
+  fn compare_contents_fills_in_the_diff() {
+    let options = CsvOptions::default();
+    let mut expected = ReaderBuilder::new().from_reader("id,note\n1,a\n2,b\n".as_bytes());
+    let mut actual = ReaderBuilder::new().from_reader("id,note\n1,\"line 1\nline 2\"\n".as_bytes());
+    let response = compare_contents(&options, &mut expected, &mut actual, false, hashmap!{}).unwrap();
+    let diffs = response.get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.diff.clone()))
+      .collect::<Vec<_>>();
+    expect!(diffs).to(be_equal_to(vec![
+      "- a\n+ line 1\\nline 2".to_string(),
+      "--- expected (2 rows)\n+++ actual (1 row)".to_string(),
+      String::default()
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_unexpected_columns() {
+    let options = CsvOptions { has_headers: false, .. CsvOptions::default() };
+    let compare = |actual: &str, allow_unexpected_keys: bool| {
+      let mut expected = ReaderBuilder::new().has_headers(false).flexible(true).from_reader("1,a\n".as_bytes());
+      let mut actual = ReaderBuilder::new().has_headers(false).flexible(true).from_reader(actual.as_bytes());
+      let response = compare_contents(&options, &mut expected, &mut actual, allow_unexpected_keys, hashmap!{}).unwrap();
+      response.get_ref().results.values()
+        .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+        .collect::<Vec<_>>()
+    };
+    expect!(compare("1,a\n1,a,x\n1,a,y,z\n", true).iter()).to(be_empty());
+    expect!(compare("1,a\n1,a,x\n1,a,y,z\n", false)).to(be_equal_to(vec![
+      ("row:    2".to_string(), "Expected at most 2 columns in row 2, but got 3 (unexpected columns are not allowed)".to_string())
+    ]));
+    expect!(compare("1,a,x\n", false)).to(be_equal_to(vec![
+      ("row:    1".to_string(), "Expected at most 2 columns in row 1, but got 3 (unexpected columns are not allowed)".to_string())
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_a_byte_order_mark() {
+    let rules = hashmap! { "column:id".to_string() => RuleList::new(MatchingRule::Integer) };
+    let mismatches = |options: &CsvOptions| compare_csv_data(options, b"id,name\n1,a\n", b"\xEF\xBB\xBFid,name\nx,a\n", false,
+      rules.clone()).unwrap().get_ref().results.values()
+      .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+      .collect::<Vec<_>>();
+    expect!(mismatches(&CsvOptions::default())).to(be_equal_to(vec![
+      "Expected 'x' to match an integer number".to_string()
+    ]));
+
+    // The byte order mark is not a value, so the first line after it is blank
+    let options = CsvOptions { no_blank_lines: true, .. CsvOptions::default() };
+    let response = compare_csv_data(&options, b"id\n1\n", b"\xEF\xBB\xBF\nid\n1\n", false, hashmap!{}).unwrap();
+    expect!(response.get_ref().results[""].mismatches.iter().map(|mismatch| mismatch.mismatch.clone()).collect::<Vec<_>>())
+      .to(be_equal_to(vec!["Expected the contents to not start with blank lines, but found 1".to_string()]));
+  }
+
+  #[test]
+  fn compare_contents_with_empty_content() {
+    for has_headers in [true, false] {
+      let options = CsvOptions { has_headers, .. CsvOptions::default() };
+      expect!(content_mismatches(&options, "", "").unwrap().iter()).to(be_empty());
+      expect!(content_mismatches(&options, "", "id,name\n1,a\n").unwrap()).to(be_equal_to(vec![
+        "Expected empty CSV content, but got 2 records".to_string()
+      ]));
+      expect!(content_mismatches(&options, "id,name\n1,a\n", "").unwrap()).to(be_equal_to(vec![
+        "Expected 2 records, but the CSV content was empty".to_string()
+      ]));
+    }
+
+    let response = compare_csv_data(&CsvOptions::default(), b"", b"id\n1\n", false, hashmap!{}).unwrap();
+    let mismatch = &response.get_ref().results[""].mismatches[0];
+    expect!(mismatch.expected.clone()).to(be_some().value(b"0 records".to_vec()));
+    expect!(mismatch.actual.clone()).to(be_some().value(b"2 records".to_vec()));
+  }
+
+  #[test]
+  fn compare_contents_with_multi_line_values() {
+    let paths = |options: &CsvOptions, expected: &str, actual: &str| {
+      let mut expected = ReaderBuilder::new().has_headers(options.has_headers).from_reader(expected.as_bytes());
+      let mut actual = ReaderBuilder::new().has_headers(options.has_headers).from_reader(actual.as_bytes());
+      let response = compare_contents(options, &mut expected, &mut actual, false, hashmap!{}).unwrap();
+      response.get_ref().results.values()
+        .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| (mismatch.path.clone(), mismatch.mismatch.clone())))
+        .collect::<Vec<_>>()
+    };
+
+    // The address of the first row spans three lines, so the second row starts on line 5
+    let actual = "id,address\n1,\"1 Main St\nSpringfield\n\"\"North\"\"\"\n1,x\n";
+    expect!(paths(&CsvOptions::default(), "id,address\n1,x\n", actual)).to(be_equal_to(vec![
+      ("row:    2, column: 1".to_string(), "Expected column 1 value to equal 'x', but got '1 Main St\nSpringfield\n\"North\"'".to_string())
+    ]));
+    expect!(paths(&CsvOptions::default(), "id,address\n1,x\n", "id,address\n1,\"a\nb\"\n1,y\n")).to(be_equal_to(vec![
+      ("row:    2, column: 1".to_string(), "Expected column 1 value to equal 'x', but got 'a\nb'".to_string()),
+      ("row:    3, column: 1".to_string(), "Expected column 1 value to equal 'x', but got 'y'".to_string())
+    ]));
+
+    let options = CsvOptions { has_headers: false, .. CsvOptions::default() };
+    expect!(paths(&options, "1,x\n", "1,\"a\n\"\"b\"\"\"\n1,x\n1,c\n")).to(be_equal_to(vec![
+      ("row:    1, column: 1".to_string(), "Expected column 1 value to equal 'x', but got 'a\n\"b\"'".to_string()),
+      ("row:    3, column: 1".to_string(), "Expected column 1 value to equal 'x', but got 'c'".to_string())
+    ]));
+  }
+
+  #[test]
+  fn compare_contents_with_column_constraints() {
+    let mismatches = |columns: &BTreeMap<String, ColumnOptions>, actual: &str| {
+      let options = CsvOptions { columns: columns.clone(), .. CsvOptions::default() };
+      let mut expected = ReaderBuilder::new().from_reader("source,target\n1,2\n".as_bytes());
+      let mut actual = ReaderBuilder::new().from_reader(actual.as_bytes());
+      let rules = hashmap!{
+        "column:source".to_string() => RuleList::new(MatchingRule::Type),
+        "column:target".to_string() => RuleList::new(MatchingRule::Type)
+      };
+      compare_contents(&options, &mut expected, &mut actual, false, rules).map(|response| response.get_ref().results.values()
+        .flat_map(|mismatches| mismatches.mismatches.iter().map(|mismatch| mismatch.mismatch.clone()))
+        .collect::<Vec<_>>())
+    };
+
+    let mut columns = btreemap! {
+      "column:source".to_string() => ColumnOptions {
+        constraints: vec![ColumnConstraint::DistinctFrom(Either::Right("target".to_string()))],
+        .. ColumnOptions::default()
+      }
+    };
+    expect!(mismatches(&columns, "source,target\n1,2\n3,1\n").unwrap().iter()).to(be_empty());
+    expect!(mismatches(&columns, "source,target\n1,2\n1,1\n2,1\n").unwrap()).to(be_equal_to(vec![
+      "Expected column source to be distinct from column target, but both were '1' in row 3".to_string()
+    ]));
+
+    columns.get_mut("column:source").unwrap().constraints = vec![ColumnConstraint::DisjointFrom(Either::Left(2))];
+    expect!(mismatches(&columns, "source,target\n1,2\n1,3\n").unwrap().iter()).to(be_empty());
+    expect!(mismatches(&columns, "source,target\n1,2\n1,3\n3,2\n2,4\n").unwrap()).to(be_equal_to(vec![
+      "Expected column source to not share any values with column target, but '2' occurs in both (rows 5 and rows 2, 4)".to_string(),
+      "Expected column source to not share any values with column target, but '3' occurs in both (rows 4 and rows 3)".to_string()
+    ]));
+
+    columns.get_mut("column:source").unwrap().constraints = vec![ColumnConstraint::DistinctFrom(Either::Right("other".to_string()))];
+    expect!(mismatches(&columns, "source,target\n1,2\n")).to(be_err());
+  }
+}
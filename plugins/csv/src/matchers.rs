@@ -0,0 +1,720 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use pact_matching::matchers::Matches;
+use pact_models::json_utils::json_to_num;
+use pact_models::matchingrules::MatchingRule;
+use pact_models::time_utils::{parse_pattern, to_chrono_pattern};
+use serde_json::{json, Value};
+use unicase::UniCase;
+
+/// Matching rules provided by this plugin, in addition to the Pact matching rules. As the Pact
+/// implementations do not know about these, they are stored in the interaction plugin
+/// configuration instead of the matching rules for the interaction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvRule {
+  /// Decimal number with at most the given number of significant digits (precision) and
+  /// fraction digits (scale)
+  DecimalPrecision { precision: Option<usize>, scale: Option<usize> },
+  /// Decimal number written with exactly the given number of fraction digits (i.e. `12.50` for 2)
+  DecimalPlaces { places: usize },
+  /// Date/time in the given format that is within the tolerance (in milliseconds) of the expected
+  /// date/time
+  DateTimeTolerance { format: String, tolerance: i64 },
+  /// Value is a list of elements separated by the delimiter, where each element must match all
+  /// the rules, and there must be the given number of elements
+  List { delimiter: String, each: Vec<MatchingRule>, count: Option<usize> },
+  /// Number that is equal to the expected number once both have been rounded to the given number
+  /// of fraction digits with the configured rounding mode
+  Rounded { scale: usize },
+  /// Number with a valid Luhn check digit (i.e. a credit card number)
+  Luhn,
+  /// International bank account number with valid (mod-97) check digits
+  Iban,
+  /// Value must be one of the allowed values (i.e. the values of an enum)
+  OneOf { values: Vec<String> },
+  /// Value must have at least one character that is not whitespace
+  NotEmpty,
+  /// Value must be empty
+  Empty
+}
+
+/// How numbers are rounded by the numeric matching rules
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RoundingMode {
+  /// Round to the nearest value, and to the even value if it is exactly halfway (banker's rounding)
+  #[default]
+  HalfEven,
+  /// Round to the nearest value, and away from zero if it is exactly halfway
+  HalfUp,
+  /// Drop the extra digits (round towards zero)
+  Truncate
+}
+
+impl RoundingMode {
+  /// Parses the rounding mode (`halfEven`, `halfUp` or `truncate`)
+  pub fn parse(s: &str) -> anyhow::Result<RoundingMode> {
+    match s {
+      "halfEven" => Ok(RoundingMode::HalfEven),
+      "halfUp" => Ok(RoundingMode::HalfUp),
+      "truncate" => Ok(RoundingMode::Truncate),
+      _ => Err(anyhow!("'{}' is not a valid rounding mode, expected halfEven, halfUp or truncate", s))
+    }
+  }
+
+  /// Rounds the number to the given number of fraction digits
+  pub fn round(&self, value: &BigDecimal, scale: i64) -> BigDecimal {
+    let truncated = value.with_scale(scale);
+    if *self == RoundingMode::Truncate {
+      return truncated;
+    }
+
+    let remainder = (value - &truncated).abs();
+    let half = BigDecimal::new(5.into(), scale + 1);
+    let away_from_zero = match remainder.cmp(&half) {
+      std::cmp::Ordering::Greater => true,
+      std::cmp::Ordering::Less => false,
+      std::cmp::Ordering::Equal => *self == RoundingMode::HalfUp
+        || truncated.as_bigint_and_exponent().0.to_string().ends_with(['1', '3', '5', '7', '9'])
+    };
+    if away_from_zero {
+      let unit = BigDecimal::new(1.into(), scale);
+      if *value < BigDecimal::from(0) { truncated - unit } else { truncated + unit }
+    } else {
+      truncated
+    }
+  }
+}
+
+impl Display for RoundingMode {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RoundingMode::HalfEven => write!(f, "halfEven"),
+      RoundingMode::HalfUp => write!(f, "halfUp"),
+      RoundingMode::Truncate => write!(f, "truncate")
+    }
+  }
+}
+
+/// How values are folded when they are compared ignoring case
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaseFolding {
+  /// Both values are converted to lower case
+  Simple,
+  /// Full Unicode case folding, with the special cases for the language of the locale (i.e. the
+  /// dotted and dotless i for Turkish)
+  Locale(String)
+}
+
+impl CaseFolding {
+  /// If the values are equal once their case has been folded
+  pub fn equals(&self, a: &str, b: &str) -> bool {
+    match self {
+      CaseFolding::Simple => a.to_lowercase() == b.to_lowercase(),
+      CaseFolding::Locale(locale) => {
+        let language = locale.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+        if language == "tr" || language == "az" {
+          UniCase::unicode(turkic_dotted_i(a)) == UniCase::unicode(turkic_dotted_i(b))
+        } else {
+          UniCase::unicode(a) == UniCase::unicode(b)
+        }
+      }
+    }
+  }
+}
+
+// In Turkish and Azerbaijani, the lower case of I is the dotless ı, and the lower case of İ is i.
+// These are mapped before the values are folded, as the Unicode case folding does not include them.
+fn turkic_dotted_i(value: &str) -> String {
+  value.chars()
+    .map(|ch| match ch {
+      'I' => 'ı',
+      'İ' => 'i',
+      _ => ch
+    })
+    .collect()
+}
+
+impl CsvRule {
+  /// Name of the rule
+  pub fn name(&self) -> &'static str {
+    match self {
+      CsvRule::DecimalPrecision { .. } => "decimal",
+      CsvRule::DecimalPlaces { .. } => "decimalPlaces",
+      CsvRule::DateTimeTolerance { .. } => "datetime",
+      CsvRule::List { .. } => "list",
+      CsvRule::Rounded { .. } => "rounded",
+      CsvRule::Luhn => "luhn",
+      CsvRule::Iban => "iban",
+      CsvRule::OneOf { .. } => "oneOf",
+      CsvRule::NotEmpty => "notEmpty",
+      CsvRule::Empty => "empty"
+    }
+  }
+
+  /// Builds the rule from its stored JSON form
+  pub fn from_json(json: &Value) -> anyhow::Result<CsvRule> {
+    match json.get("match").and_then(Value::as_str) {
+      Some("decimal") => Ok(CsvRule::DecimalPrecision {
+        precision: json_to_num(json.get("precision").cloned()),
+        scale: json_to_num(json.get("scale").cloned())
+      }),
+      Some("decimalPlaces") => Ok(CsvRule::DecimalPlaces {
+        places: json_to_num(json.get("places").cloned())
+          .ok_or_else(|| anyhow!("CSV decimalPlaces matching rule JSON {} is missing the 'places' attribute", json))?
+      }),
+      Some("datetime") => {
+        let format = json.get("format").and_then(Value::as_str)
+          .ok_or_else(|| anyhow!("CSV datetime matching rule JSON {} is missing the 'format' attribute", json))?;
+        let tolerance = json.get("tolerance").and_then(Value::as_str)
+          .ok_or_else(|| anyhow!("CSV datetime matching rule JSON {} is missing the 'tolerance' attribute", json))?;
+        Ok(CsvRule::DateTimeTolerance { format: format.to_string(), tolerance: parse_duration(tolerance)? })
+      }
+      Some("list") => {
+        let delimiter = json.get("subDelimiter").and_then(Value::as_str)
+          .ok_or_else(|| anyhow!("CSV list matching rule JSON {} is missing the 'subDelimiter' attribute", json))?;
+        let each = match json.get("each") {
+          Some(Value::Array(rules)) => rules.iter().map(MatchingRule::from_json).collect::<anyhow::Result<Vec<_>>>()?,
+          _ => vec![]
+        };
+        Ok(CsvRule::List { delimiter: delimiter.to_string(), each, count: json_to_num(json.get("count").cloned()) })
+      }
+      Some("rounded") => Ok(CsvRule::Rounded {
+        scale: json_to_num(json.get("scale").cloned())
+          .ok_or_else(|| anyhow!("CSV rounded matching rule JSON {} is missing the 'scale' attribute", json))?
+      }),
+      Some("luhn") => Ok(CsvRule::Luhn),
+      Some("iban") => Ok(CsvRule::Iban),
+      Some("notEmpty") => Ok(CsvRule::NotEmpty),
+      Some("empty") => Ok(CsvRule::Empty),
+      Some("oneOf") => match json.get("values") {
+        Some(Value::Array(values)) if !values.is_empty() => Ok(CsvRule::OneOf {
+          values: values.iter()
+            .map(|value| value.as_str().map(str::to_string)
+              .ok_or_else(|| anyhow!("CSV oneOf matching rule JSON {} must only have string values", json)))
+            .collect::<anyhow::Result<Vec<_>>>()?
+        }),
+        _ => Err(anyhow!("CSV oneOf matching rule JSON {} must have a list of values", json))
+      },
+      Some(name) => Err(anyhow!("'{}' is not a known CSV matching rule", name)),
+      None => Err(anyhow!("CSV matching rule JSON {} is missing the 'match' attribute", json))
+    }
+  }
+
+  /// Converts the rule to the JSON form stored in the plugin configuration
+  pub fn to_json(&self) -> Value {
+    match self {
+      CsvRule::DecimalPrecision { precision, scale } => {
+        let mut json = json!({ "match": self.name() });
+        if let Some(precision) = precision {
+          json["precision"] = json!(precision);
+        }
+        if let Some(scale) = scale {
+          json["scale"] = json!(scale);
+        }
+        json
+      }
+      CsvRule::DateTimeTolerance { format, tolerance } => json!({
+        "match": self.name(),
+        "format": format,
+        "tolerance": format_duration(*tolerance)
+      }),
+      CsvRule::List { delimiter, each, count } => {
+        let mut json = json!({
+          "match": self.name(),
+          "subDelimiter": delimiter,
+          "each": each.iter().map(|rule| rule.to_json()).collect::<Vec<_>>()
+        });
+        if let Some(count) = count {
+          json["count"] = json!(count);
+        }
+        json
+      }
+      CsvRule::DecimalPlaces { places } => json!({ "match": self.name(), "places": places }),
+      CsvRule::Rounded { scale } => json!({ "match": self.name(), "scale": scale }),
+      CsvRule::Luhn | CsvRule::Iban | CsvRule::NotEmpty | CsvRule::Empty => json!({ "match": self.name() }),
+      CsvRule::OneOf { values } => json!({ "match": self.name(), "values": values })
+    }
+  }
+
+  /// Checks the actual value against the rule, returning the mismatch message if it does not match.
+  /// The rounding mode is used by the numeric rules.
+  pub fn match_value(&self, expected: &str, actual: &str, rounding_mode: RoundingMode) -> Result<(), String> {
+    match self {
+      CsvRule::DecimalPrecision { precision, scale } => {
+        let (actual_precision, actual_scale) = decimal_digits(actual)
+          .ok_or_else(|| format!("Expected '{}' to be a decimal number", actual))?;
+        let precision_ok = precision.map(|p| actual_precision <= p).unwrap_or(true);
+        let scale_ok = scale.map(|s| actual_scale <= s).unwrap_or(true);
+        if precision_ok && scale_ok {
+          Ok(())
+        } else {
+          Err(format!("Expected '{}' to have {}, but it has a precision of {} and a scale of {}", actual,
+            precision_description(precision, scale), actual_precision, actual_scale))
+        }
+      }
+      CsvRule::DecimalPlaces { places } => {
+        let (_, actual_places) = decimal_digits(actual)
+          .ok_or_else(|| format!("Expected '{}' to be a decimal number with {} decimal places", actual, places))?;
+        if actual_places == *places {
+          Ok(())
+        } else {
+          Err(format!("Expected '{}' to have {} decimal places, but it has {}", actual, places, actual_places))
+        }
+      }
+      CsvRule::DateTimeTolerance { format, tolerance } => {
+        let expected_millis = timestamp_millis(expected, format)
+          .map_err(|err| format!("Expected value '{}' is not a valid date/time for the format '{}' - {}", expected, format, err))?;
+        let actual_millis = timestamp_millis(actual, format)
+          .map_err(|err| format!("Expected '{}' to be a date/time with format '{}' - {}", actual, format, err))?;
+        let delta = actual_millis - expected_millis;
+        if delta.abs() <= *tolerance {
+          Ok(())
+        } else {
+          Err(format!("Expected '{}' to be within {} of '{}', but it was {} {}", actual, format_duration(*tolerance),
+            expected, format_duration(delta.abs()), if delta < 0 { "before" } else { "after" }))
+        }
+      }
+      CsvRule::List { delimiter, each, count } => {
+        let elements = split_list(actual, delimiter);
+        if let Some(count) = count {
+          if elements.len() != *count {
+            return Err(format!("Expected '{}' to be a list of {} elements, but it has {}", actual, count, elements.len()));
+          }
+        }
+        // Each element is matched against the expected element in the same position, or the first
+        // expected element if the actual list is longer
+        let expected_elements = split_list(expected, delimiter);
+        for (index, element) in elements.iter().enumerate() {
+          let expected_element = expected_elements.get(index)
+            .or_else(|| expected_elements.first())
+            .copied()
+            .unwrap_or_default();
+          for rule in each {
+            expected_element.matches_with(*element, rule, false)
+              .map_err(|err| format!("List element {} ('{}') of '{}' does not match - {}", index, element, actual, err))?;
+          }
+        }
+        Ok(())
+      }
+      CsvRule::Rounded { scale } => {
+        let expected_value = BigDecimal::from_str(expected.trim())
+          .map_err(|_| format!("Expected value '{}' is not a number", expected))?;
+        let actual_value = BigDecimal::from_str(actual.trim())
+          .map_err(|_| format!("Expected '{}' to be a number", actual))?;
+        let expected_rounded = rounding_mode.round(&expected_value, *scale as i64);
+        let actual_rounded = rounding_mode.round(&actual_value, *scale as i64);
+        if expected_rounded == actual_rounded {
+          Ok(())
+        } else {
+          Err(format!("Expected '{}' to be '{}' when rounded to {} fraction digits ({}), but it was '{}'", actual,
+            expected_rounded, scale, rounding_mode, actual_rounded))
+        }
+      }
+      CsvRule::Luhn => match luhn_checksum(actual) {
+        Some(0) => Ok(()),
+        Some(_) => Err(format!("Expected '{}' to have a valid Luhn check digit", actual)),
+        None => Err(format!("Expected '{}' to be a number with a Luhn check digit", actual))
+      },
+      CsvRule::Iban => match iban_remainder(actual) {
+        Some(1) => Ok(()),
+        Some(_) => Err(format!("Expected '{}' to be an IBAN with valid check digits", actual)),
+        None => Err(format!("Expected '{}' to be an IBAN (a country code, two check digits and up to 30 letters or digits)", actual))
+      },
+      CsvRule::OneOf { values } => if values.iter().any(|value| value == actual) {
+        Ok(())
+      } else {
+        let values = values.iter().map(|value| format!("'{}'", value)).collect::<Vec<_>>();
+        Err(format!("Expected '{}' to be one of {}", actual, values.join(", ")))
+      }
+      CsvRule::NotEmpty => if actual.is_empty() {
+        Err("Expected a non-empty value, but got an empty value".to_string())
+      } else if actual.trim().is_empty() {
+        Err(format!("Expected a non-empty value, but got only whitespace '{}'", actual))
+      } else {
+        Ok(())
+      }
+      CsvRule::Empty => if actual.is_empty() {
+        Ok(())
+      } else {
+        Err(format!("Expected an empty value, but got '{}'", actual))
+      }
+    }
+  }
+}
+
+/// Luhn checksum (mod 10) of the digits of the value, which is 0 if the check digit is valid. Spaces
+/// and hyphens between the digits are ignored, and the value must have at least two digits.
+fn luhn_checksum(value: &str) -> Option<u32> {
+  let digits = value.chars()
+    .filter(|ch| *ch != ' ' && *ch != '-')
+    .map(|ch| ch.to_digit(10))
+    .collect::<Option<Vec<_>>>()?;
+  if digits.len() < 2 {
+    return None;
+  }
+  let sum = digits.iter().rev().enumerate()
+    .map(|(index, digit)| match index % 2 {
+      0 => *digit,
+      _ if *digit > 4 => digit * 2 - 9,
+      _ => digit * 2
+    })
+    .sum::<u32>();
+  Some(sum % 10)
+}
+
+/// Remainder (mod 97) of the IBAN once the country code and check digits are moved to the end and
+/// the letters are replaced with numbers, which is 1 if the check digits are valid. Spaces are
+/// ignored, as IBANs are usually printed in groups of four characters.
+fn iban_remainder(value: &str) -> Option<u32> {
+  let iban = value.chars().filter(|ch| *ch != ' ').collect::<Vec<_>>();
+  if iban.len() < 5 || iban.len() > 34 || !iban[..2].iter().all(char::is_ascii_uppercase)
+    || !iban[2..4].iter().all(char::is_ascii_digit) || !iban[4..].iter().all(char::is_ascii_alphanumeric) {
+    return None;
+  }
+  let remainder = iban[4..].iter().chain(iban[..4].iter())
+    .fold(0, |remainder, ch| {
+      let value = ch.to_digit(36).unwrap_or_default();
+      if value < 10 { (remainder * 10 + value) % 97 } else { (remainder * 100 + value) % 97 }
+    });
+  Some(remainder)
+}
+
+/// Splits a list value into its elements. An empty value is an empty list.
+fn split_list<'a>(value: &'a str, delimiter: &str) -> Vec<&'a str> {
+  if value.is_empty() {
+    vec![]
+  } else {
+    value.split(delimiter).collect()
+  }
+}
+
+/// Parses the date/time value using the Pact date/time format, returning the number of
+/// milliseconds since the epoch. Values without a timezone are treated as UTC.
+fn timestamp_millis(value: &str, format: &str) -> anyhow::Result<i64> {
+  let pattern = to_chrono_pattern(&parse_pattern(format).map_err(|err| anyhow!(err))?);
+  if let Ok(datetime) = DateTime::parse_from_str(value, &pattern) {
+    Ok(datetime.timestamp_millis())
+  } else if let Ok(datetime) = NaiveDateTime::parse_from_str(value, &pattern) {
+    Ok(datetime.timestamp_millis())
+  } else {
+    let date = NaiveDate::parse_from_str(value, &pattern)?;
+    date.and_hms_opt(0, 0, 0)
+      .map(|datetime| datetime.timestamp_millis())
+      .ok_or_else(|| anyhow!("'{}' is not a valid date", value))
+  }
+}
+
+/// Parses a duration in the form `500ms`, `5s`, `2m` or `1h`, returning the number of milliseconds
+pub fn parse_duration(value: &str) -> anyhow::Result<i64> {
+  let value = value.trim();
+  let split = value.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(value.len());
+  let (amount, unit) = value.split_at(split);
+  let amount = amount.parse::<i64>()
+    .map_err(|_| anyhow!("'{}' is not a valid duration, expected a number followed by ms, s, m or h", value))?;
+  match unit.trim() {
+    "ms" => Ok(amount),
+    "s" => Ok(amount * 1000),
+    "m" => Ok(amount * 60_000),
+    "h" => Ok(amount * 3_600_000),
+    _ => Err(anyhow!("'{}' is not a valid duration, expected a number followed by ms, s, m or h", value))
+  }
+}
+
+/// Formats the duration in milliseconds using the largest unit it is a whole number of
+fn format_duration(millis: i64) -> String {
+  if millis != 0 && millis % 3_600_000 == 0 {
+    format!("{}h", millis / 3_600_000)
+  } else if millis != 0 && millis % 60_000 == 0 {
+    format!("{}m", millis / 60_000)
+  } else if millis != 0 && millis % 1000 == 0 {
+    format!("{}s", millis / 1000)
+  } else {
+    format!("{}ms", millis)
+  }
+}
+
+fn precision_description(precision: &Option<usize>, scale: &Option<usize>) -> String {
+  match (precision, scale) {
+    (Some(precision), Some(scale)) => format!("a precision of at most {} and a scale of at most {}", precision, scale),
+    (Some(precision), None) => format!("a precision of at most {}", precision),
+    (None, Some(scale)) => format!("a scale of at most {}", scale),
+    (None, None) => "any precision".to_string()
+  }
+}
+
+/// Normalizes a decimal number by removing trailing zeros from the fraction (and the decimal
+/// point if there is no fraction left), leading zeros and a plus sign, so that `+0100.50` and
+/// `100.5` are equal. Returns None if the value is not a decimal number.
+pub fn normalize_number(value: &str) -> Option<String> {
+  let value = value.trim();
+  decimal_digits(value)?;
+  let (sign, digits) = match value.strip_prefix('-') {
+    Some(digits) => ("-", digits),
+    None => ("", value.strip_prefix('+').unwrap_or(value))
+  };
+  let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+  let integer = integer.trim_start_matches('0');
+  let integer = if integer.is_empty() { "0" } else { integer };
+  let fraction = fraction.trim_end_matches('0');
+  let sign = if integer == "0" && fraction.is_empty() { "" } else { sign };
+  if fraction.is_empty() {
+    Some(format!("{}{}", sign, integer))
+  } else {
+    Some(format!("{}{}.{}", sign, integer, fraction))
+  }
+}
+
+/// Removes the thousands separators from a number, so that `1,234.5` and `1 234.5` are both
+/// `1234.5`. Separators are only removed from between two digits before the decimal point. Returns
+/// None if the value is not a decimal number once the separators have been removed.
+pub fn strip_thousands_separators(value: &str, separators: &[char]) -> Option<String> {
+  let value = value.trim();
+  let (integer, fraction) = match value.split_once('.') {
+    Some((integer, fraction)) => (integer, Some(fraction)),
+    None => (value, None)
+  };
+  let chars = integer.chars().collect::<Vec<_>>();
+  let mut stripped = String::with_capacity(value.len());
+  for (index, ch) in chars.iter().enumerate() {
+    let is_separator = separators.contains(ch) && index > 0 && chars[index - 1].is_ascii_digit()
+      && chars.get(index + 1).map(|next| next.is_ascii_digit()).unwrap_or(false);
+    if !is_separator {
+      stripped.push(*ch);
+    }
+  }
+  if let Some(fraction) = fraction {
+    stripped.push('.');
+    stripped.push_str(fraction);
+  }
+  decimal_digits(&stripped).map(|_| stripped)
+}
+
+/// Returns the number of significant digits and the number of fraction digits of a decimal number
+fn decimal_digits(value: &str) -> Option<(usize, usize)> {
+  let value = value.trim();
+  let value = value.strip_prefix(['-', '+']).unwrap_or(value);
+  let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+  if (integer.is_empty() && fraction.is_empty()) || !integer.chars().chain(fraction.chars()).all(|ch| ch.is_ascii_digit()) {
+    None
+  } else {
+    let integer_digits = integer.trim_start_matches('0').len();
+    Some((integer_digits + fraction.len(), fraction.len()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use pact_models::matchingrules::MatchingRule;
+  use serde_json::json;
+
+  use super::{CaseFolding, CsvRule, normalize_number, parse_duration, RoundingMode, strip_thousands_separators};
+
+  #[test]
+  fn case_folding_test() {
+    expect!(CaseFolding::Simple.equals("Zürich", "ZÜRICH")).to(be_true());
+    expect!(CaseFolding::Simple.equals("Straße", "STRASSE")).to(be_false());
+    expect!(CaseFolding::Simple.equals("a", "b")).to(be_false());
+
+    let english = CaseFolding::Locale("en-GB".to_string());
+    expect!(english.equals("Straße", "STRASSE")).to(be_true());
+    expect!(english.equals("istanbul", "ISTANBUL")).to(be_true());
+    expect!(english.equals("istanbul", "İSTANBUL")).to(be_false());
+
+    let turkish = CaseFolding::Locale("tr_TR".to_string());
+    expect!(turkish.equals("istanbul", "İSTANBUL")).to(be_true());
+    expect!(turkish.equals("istanbul", "ISTANBUL")).to(be_false());
+    expect!(turkish.equals("ırmak", "IRMAK")).to(be_true());
+  }
+
+  #[test]
+  fn rounding_mode_test() {
+    let round = |mode: RoundingMode, value: &str, scale: i64| {
+      mode.round(&value.parse().unwrap(), scale).to_string()
+    };
+    expect!(round(RoundingMode::HalfEven, "2.5", 0)).to(be_equal_to("2"));
+    expect!(round(RoundingMode::HalfEven, "3.5", 0)).to(be_equal_to("4"));
+    expect!(round(RoundingMode::HalfEven, "-2.5", 0)).to(be_equal_to("-2"));
+    expect!(round(RoundingMode::HalfEven, "2.51", 0)).to(be_equal_to("3"));
+    expect!(round(RoundingMode::HalfUp, "2.5", 0)).to(be_equal_to("3"));
+    expect!(round(RoundingMode::HalfUp, "-2.5", 0)).to(be_equal_to("-3"));
+    expect!(round(RoundingMode::HalfUp, "2.49", 0)).to(be_equal_to("2"));
+    expect!(round(RoundingMode::Truncate, "2.5", 0)).to(be_equal_to("2"));
+    expect!(round(RoundingMode::Truncate, "-2.99", 0)).to(be_equal_to("-2"));
+    expect!(round(RoundingMode::HalfEven, "2.345", 2)).to(be_equal_to("2.34"));
+    expect!(round(RoundingMode::HalfUp, "2.345", 2)).to(be_equal_to("2.35"));
+    expect!(round(RoundingMode::HalfEven, "2.3", 2)).to(be_equal_to("2.30"));
+  }
+
+  #[test]
+  fn rounded_test() {
+    let rule = CsvRule::Rounded { scale: 0 };
+    expect!(rule.match_value("2", "2.5", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("3", "2.5", RoundingMode::HalfUp)).to(be_ok());
+    expect!(rule.match_value("2", "2.5", RoundingMode::HalfUp)).to(be_err().value(
+      "Expected '2.5' to be '2' when rounded to 0 fraction digits (halfUp), but it was '3'".to_string()));
+    expect!(rule.match_value("2", "2.9", RoundingMode::Truncate)).to(be_ok());
+    expect!(rule.match_value("2", "abc", RoundingMode::default())).to(be_err());
+  }
+
+  #[test]
+  fn luhn_test() {
+    let rule = CsvRule::Luhn;
+    expect!(rule.match_value("", "79927398713", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("", "4111 1111 1111 1111", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("", "4111-1111-1111-1112", RoundingMode::default())).to(be_err().value(
+      "Expected '4111-1111-1111-1112' to have a valid Luhn check digit".to_string()));
+    expect!(rule.match_value("", "7992739871x", RoundingMode::default())).to(be_err().value(
+      "Expected '7992739871x' to be a number with a Luhn check digit".to_string()));
+    expect!(rule.match_value("", "0", RoundingMode::default())).to(be_err());
+    expect!(CsvRule::from_json(&rule.to_json()).unwrap()).to(be_equal_to(rule));
+  }
+
+  #[test]
+  fn iban_test() {
+    let rule = CsvRule::Iban;
+    expect!(rule.match_value("", "GB82WEST12345698765432", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("", "DE89 3704 0044 0532 0130 00", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("", "GB82WEST12345698765431", RoundingMode::default())).to(be_err().value(
+      "Expected 'GB82WEST12345698765431' to be an IBAN with valid check digits".to_string()));
+    expect!(rule.match_value("", "gb82WEST12345698765432", RoundingMode::default())).to(be_err().value(
+      "Expected 'gb82WEST12345698765432' to be an IBAN (a country code, two check digits and up to 30 letters or digits)".to_string()));
+    expect!(rule.match_value("", "GB82", RoundingMode::default())).to(be_err());
+    expect!(CsvRule::from_json(&rule.to_json()).unwrap()).to(be_equal_to(rule));
+  }
+
+  #[test]
+  fn one_of_test() {
+    let rule = CsvRule::OneOf { values: vec!["OPEN".to_string(), "CLOSED".to_string(), "PENDING".to_string()] };
+    expect!(rule.match_value("OPEN", "CLOSED", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("OPEN", "open", RoundingMode::default())).to(be_err().value(
+      "Expected 'open' to be one of 'OPEN', 'CLOSED', 'PENDING'".to_string()));
+    expect!(rule.match_value("OPEN", "", RoundingMode::default())).to(be_err());
+    expect!(CsvRule::from_json(&rule.to_json()).unwrap()).to(be_equal_to(rule));
+    expect!(CsvRule::from_json(&json!({ "match": "oneOf", "values": [] }))).to(be_err());
+    expect!(CsvRule::from_json(&json!({ "match": "oneOf", "values": [1] }))).to(be_err());
+  }
+
+  #[test]
+  fn list_test() {
+    let rule = CsvRule::List {
+      delimiter: ";".to_string(),
+      each: vec![MatchingRule::Regex("^[a-z]+$".to_string())],
+      count: None
+    };
+    expect!(rule.match_value("a", "a;bc;def", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("a", "", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("a", "a;B2;c", RoundingMode::default())).to(be_err().value(
+      "List element 1 ('B2') of 'a;B2;c' does not match - Expected 'B2' to match '^[a-z]+$'".to_string()));
+
+    let rule = CsvRule::List { delimiter: ";".to_string(), each: vec![], count: Some(2) };
+    expect!(rule.match_value("a;b", "x;y", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("a;b", "x;y;z", RoundingMode::default())).to(be_err().value(
+      "Expected 'x;y;z' to be a list of 2 elements, but it has 3".to_string()));
+
+    let json = CsvRule::List {
+      delimiter: "|".to_string(),
+      each: vec![MatchingRule::Integer],
+      count: Some(1)
+    }.to_json();
+    expect!(CsvRule::from_json(&json).unwrap()).to(be_equal_to(CsvRule::List {
+      delimiter: "|".to_string(),
+      each: vec![MatchingRule::Integer],
+      count: Some(1)
+    }));
+  }
+
+  #[test]
+  fn decimal_precision_test() {
+    let rule = CsvRule::DecimalPrecision { precision: Some(10), scale: Some(2) };
+    expect!(rule.match_value("0.00", "12345678.90", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("0.00", "-0.5", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("0.00", "00012", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("0.00", "1.234", RoundingMode::default())).to(be_err());
+    expect!(rule.match_value("0.00", "123456789.12", RoundingMode::default())).to(be_err());
+    expect!(rule.match_value("0.00", "1.2.3", RoundingMode::default())).to(be_err());
+    expect!(rule.match_value("0.00", "abc", RoundingMode::default())).to(be_err());
+    expect!(rule.match_value("0.00", "", RoundingMode::default())).to(be_err());
+  }
+
+  #[test]
+  fn decimal_places_test() {
+    let rule = CsvRule::DecimalPlaces { places: 2 };
+    expect!(rule.match_value("0.00", "12.50", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("0.00", "-0.05", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value("0.00", "12.5", RoundingMode::default()))
+      .to(be_err().value("Expected '12.5' to have 2 decimal places, but it has 1".to_string()));
+    expect!(rule.match_value("0.00", "12.500", RoundingMode::default())).to(be_err());
+    expect!(rule.match_value("0.00", "12", RoundingMode::default())).to(be_err());
+    expect!(rule.match_value("0.00", "abc", RoundingMode::default()))
+      .to(be_err().value("Expected 'abc' to be a decimal number with 2 decimal places".to_string()));
+    expect!(CsvRule::DecimalPlaces { places: 0 }.match_value("0", "12", RoundingMode::default())).to(be_ok());
+    expect!(CsvRule::from_json(&rule.to_json()).unwrap()).to(be_equal_to(rule));
+    expect!(CsvRule::from_json(&json!({ "match": "decimalPlaces" }))).to(be_err());
+  }
+
+  #[test]
+  fn not_empty_and_empty_test() {
+    expect!(CsvRule::NotEmpty.match_value("x", "Alice", RoundingMode::default())).to(be_ok());
+    expect!(CsvRule::NotEmpty.match_value("x", "", RoundingMode::default()))
+      .to(be_err().value("Expected a non-empty value, but got an empty value".to_string()));
+    expect!(CsvRule::NotEmpty.match_value("x", "  ", RoundingMode::default()))
+      .to(be_err().value("Expected a non-empty value, but got only whitespace '  '".to_string()));
+    expect!(CsvRule::Empty.match_value("", "", RoundingMode::default())).to(be_ok());
+    expect!(CsvRule::Empty.match_value("", " ", RoundingMode::default()))
+      .to(be_err().value("Expected an empty value, but got ' '".to_string()));
+    expect!(CsvRule::from_json(&CsvRule::NotEmpty.to_json()).unwrap()).to(be_equal_to(CsvRule::NotEmpty));
+    expect!(CsvRule::from_json(&CsvRule::Empty.to_json()).unwrap()).to(be_equal_to(CsvRule::Empty));
+  }
+
+  #[test]
+  fn normalize_number_test() {
+    expect!(normalize_number("100.50")).to(be_some().value("100.5"));
+    expect!(normalize_number("100.0")).to(be_some().value("100"));
+    expect!(normalize_number("+0100")).to(be_some().value("100"));
+    expect!(normalize_number("-0.0")).to(be_some().value("0"));
+    expect!(normalize_number("-.50")).to(be_some().value("-0.5"));
+    expect!(normalize_number("12a")).to(be_none());
+  }
+
+  #[test]
+  fn strip_thousands_separators_test() {
+    let separators = [',', ' ', '\u{a0}'];
+    expect!(strip_thousands_separators("1,234", &separators)).to(be_some().value("1234"));
+    expect!(strip_thousands_separators("1 234 567.89", &separators)).to(be_some().value("1234567.89"));
+    expect!(strip_thousands_separators("-1\u{a0}234", &separators)).to(be_some().value("-1234"));
+    expect!(strip_thousands_separators("1234", &separators)).to(be_some().value("1234"));
+    expect!(strip_thousands_separators("1,234", &[' '])).to(be_none());
+    expect!(strip_thousands_separators(",123", &separators)).to(be_none());
+    expect!(strip_thousands_separators("a,b", &separators)).to(be_none());
+  }
+
+  #[test]
+  fn datetime_tolerance_test() {
+    let rule = CsvRule::DateTimeTolerance { format: "yyyy-MM-dd HH:mm:ss".to_string(), tolerance: 5000 };
+    let expected = "2023-01-01 10:00:00";
+    expect!(rule.match_value(expected, "2023-01-01 10:00:00", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value(expected, "2023-01-01 10:00:05", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value(expected, "2023-01-01 09:59:57", RoundingMode::default())).to(be_ok());
+    expect!(rule.match_value(expected, "2023-01-01 10:00:07", RoundingMode::default())).to(be_err().value(
+      "Expected '2023-01-01 10:00:07' to be within 5s of '2023-01-01 10:00:00', but it was 7s after".to_string()));
+    expect!(rule.match_value(expected, "2023-01-01 09:58:00", RoundingMode::default())).to(be_err().value(
+      "Expected '2023-01-01 09:58:00' to be within 5s of '2023-01-01 10:00:00', but it was 2m before".to_string()));
+    expect!(rule.match_value(expected, "01/01/2023", RoundingMode::default())).to(be_err());
+  }
+
+  #[test]
+  fn parse_duration_test() {
+    expect!(parse_duration("500ms").unwrap()).to(be_equal_to(500));
+    expect!(parse_duration("5s").unwrap()).to(be_equal_to(5000));
+    expect!(parse_duration("2m").unwrap()).to(be_equal_to(120_000));
+    expect!(parse_duration("1h").unwrap()).to(be_equal_to(3_600_000));
+    expect!(parse_duration("5")).to(be_err());
+    expect!(parse_duration("s")).to(be_err());
+    expect!(parse_duration("5d")).to(be_err());
+  }
+}
@@ -0,0 +1,837 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::anyhow;
+use pact_matching::matchers::Matches;
+use pact_models::json_utils::json_to_num;
+use pact_models::matchingrules::MatchingRule;
+use serde_json::{json, Value};
+
+use pact_plugin_driver::utils::proto_struct_to_map;
+
+use crate::categories::MismatchCategory;
+use crate::constraints::ColumnConstraint;
+use crate::faults::Fault;
+use crate::generators::{CsvGenerator, DefaultGenerator, GenerationSpec};
+use crate::limits::DEFAULT_MAX_FIELD_SIZE;
+use crate::matchers::{CaseFolding, CsvRule, RoundingMode};
+use crate::pages::DEFAULT_MAX_COLUMN_MISMATCHES;
+use crate::proto;
+use crate::rows::RowOverride;
+use crate::sniffer::sniff_delimiter;
+use crate::transforms::Transform;
+use crate::utils::{from_value, to_boolean};
+
+/// Layout of the records in the content
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvFormat {
+  /// Delimited fields (the default CSV format)
+  Delimited,
+  /// Fields are at fixed widths (in characters) in each line
+  FixedWidth(Vec<usize>),
+  /// Zip archive of delimited CSV members, which are compared by name
+  Zip
+}
+
+/// Options for a column, keyed by the column path (i.e. `column:1` or `column:Name`)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ColumnOptions {
+  /// Matching rules provided by this plugin
+  pub rules: Vec<CsvRule>,
+  /// Transforms applied to the actual values before they are matched
+  pub transforms: Vec<Transform>,
+  /// Generator provided by this plugin for the column values
+  pub generator: Option<CsvGenerator>,
+  /// The column can be missing from the end of a row, but is matched if it is present
+  pub optional: bool,
+  /// The values are always compared as their literal text, and are never normalized as numbers
+  pub text: bool,
+  /// The values of the column across all the rows are compared as a multiset, ignoring the order
+  /// of the rows
+  pub multiset: bool,
+  /// All the values of the column must be the same inferred type (i.e. all integers or all dates)
+  pub homogeneous: bool,
+  /// The rules of the column are applied to every actual row, including the rows after the last
+  /// expected row
+  pub each_value: bool,
+  /// Constraints between the values of the column and the values of other columns
+  pub constraints: Vec<ColumnConstraint>,
+  /// Pact matching rules of the column, which are only stored to check the generated values when
+  /// the generation is retried
+  pub matching_rules: Vec<MatchingRule>
+}
+
+impl ColumnOptions {
+  pub(crate) fn from_json(json: &Value) -> anyhow::Result<Self> {
+    let rules = match json.get("rules") {
+      Some(Value::Array(rules)) => rules.iter().map(CsvRule::from_json).collect::<anyhow::Result<Vec<_>>>()?,
+      _ => vec![]
+    };
+    let transforms = match json.get("transforms") {
+      Some(Value::Array(transforms)) => transforms.iter().map(Transform::from_json).collect::<anyhow::Result<Vec<_>>>()?,
+      _ => vec![]
+    };
+    let generator = json.get("generator").map(CsvGenerator::from_json).transpose()?;
+    let optional = json.get("optional").and_then(Value::as_bool).unwrap_or(false);
+    let text = json.get("text").and_then(Value::as_bool).unwrap_or(false);
+    let multiset = json.get("multiset").and_then(Value::as_bool).unwrap_or(false);
+    let homogeneous = json.get("homogeneous").and_then(Value::as_bool).unwrap_or(false);
+    let each_value = json.get("eachValue").and_then(Value::as_bool).unwrap_or(false);
+    let constraints = match json.get("constraints") {
+      Some(Value::Array(constraints)) => constraints.iter().map(ColumnConstraint::from_json).collect::<anyhow::Result<Vec<_>>>()?,
+      _ => vec![]
+    };
+    let matching_rules = match json.get("matchingRules") {
+      Some(Value::Array(rules)) => rules.iter().map(MatchingRule::from_json).collect::<anyhow::Result<Vec<_>>>()?,
+      _ => vec![]
+    };
+    Ok(ColumnOptions { rules, transforms, generator, optional, text, multiset, homogeneous, each_value, constraints,
+      matching_rules })
+  }
+
+  pub(crate) fn to_json(&self) -> Value {
+    let mut json = json!({});
+    if !self.rules.is_empty() {
+      json["rules"] = Value::Array(self.rules.iter().map(|rule| rule.to_json()).collect());
+    }
+    if !self.transforms.is_empty() {
+      json["transforms"] = Value::Array(self.transforms.iter().map(|transform| transform.to_json()).collect());
+    }
+    if let Some(generator) = &self.generator {
+      json["generator"] = generator.to_json();
+    }
+    if self.optional {
+      json["optional"] = json!(true);
+    }
+    if self.text {
+      json["text"] = json!(true);
+    }
+    if self.multiset {
+      json["multiset"] = json!(true);
+    }
+    if self.homogeneous {
+      json["homogeneous"] = json!(true);
+    }
+    if self.each_value {
+      json["eachValue"] = json!(true);
+    }
+    if !self.constraints.is_empty() {
+      json["constraints"] = Value::Array(self.constraints.iter().map(|constraint| constraint.to_json()).collect());
+    }
+    if !self.matching_rules.is_empty() {
+      json["matchingRules"] = Value::Array(self.matching_rules.iter().map(|rule| rule.to_json()).collect());
+    }
+    json
+  }
+
+  /// Checks a generated value against the rules of the column, with the template value as the
+  /// expected value
+  pub fn check_generated(&self, expected: &str, value: &str, rounding_mode: RoundingMode) -> Result<(), String> {
+    for rule in &self.matching_rules {
+      expected.matches_with(value, rule, false).map_err(|err| err.to_string())?;
+    }
+    for rule in &self.rules {
+      rule.match_value(expected, value, rounding_mode)?;
+    }
+    Ok(())
+  }
+
+  fn is_empty(&self) -> bool {
+    self.rules.is_empty() && self.transforms.is_empty() && self.generator.is_none() && !self.optional && !self.text && !self.multiset
+      && !self.homogeneous && !self.each_value && self.constraints.is_empty() && self.matching_rules.is_empty()
+  }
+}
+
+/// Number of times a cell is generated when the generation is retried, if maxGenerationAttempts is
+/// not set
+pub const DEFAULT_MAX_GENERATION_ATTEMPTS: usize = 10;
+
+/// Content type for tab-separated values. These are handled the same as CSV, with a tab as the delimiter
+pub const TSV_CONTENT_TYPE: &str = "text/tab-separated-values";
+
+/// Media types handled by the plugin, which are registered in the catalogue entries
+pub const CONTENT_TYPES: [&str; 3] = ["text/csv", "application/csv", TSV_CONTENT_TYPE];
+
+/// Delimiter between the fields of a record
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delimiter {
+  /// The given character
+  Char(u8),
+  /// Detected from the content when it is compared
+  Auto
+}
+
+/// How the contents are compared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareMode {
+  /// Parse the contents and compare the records using the matching rules
+  Parsed,
+  /// Compare the raw bytes of the contents
+  Bytes
+}
+
+/// Which values are quoted when the configured and generated contents are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuoteStyle {
+  /// Every value is quoted
+  Always,
+  /// Only the values that have a delimiter, quote or line break in them are quoted
+  Necessary,
+  /// Every value that is not a number is quoted
+  NonNumeric,
+  /// No values are quoted, even if the contents can then not be read back the same
+  Never
+}
+
+impl QuoteStyle {
+  /// The quote style for the CSV writer
+  pub fn writer_style(&self) -> csv::QuoteStyle {
+    match self {
+      QuoteStyle::Always => csv::QuoteStyle::Always,
+      QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+      QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+      QuoteStyle::Never => csv::QuoteStyle::Never
+    }
+  }
+}
+
+/// Compression of the generated contents. Compressed contents that are compared are always
+/// detected and decompressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+  /// The contents are not compressed
+  None,
+  /// The contents are compressed with gzip
+  Gzip
+}
+
+/// How the order of the header row is checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeaderOrder {
+  /// The expected columns must be in the same order in the actual header row
+  Strict,
+  /// The expected columns can be in any order, as long as they all exist
+  Any,
+  /// The actual header row must have exactly the expected columns, in any order, even if unexpected
+  /// keys are allowed
+  SchemaMatch
+}
+
+/// Options that control how the CSV content is handled. These are provided in the contents config
+/// when the interaction is configured, and are then stored in the interaction plugin configuration
+/// so they are available when the contents are compared or generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+  /// If the content has a header row
+  pub has_headers: bool,
+  /// Layout of the records
+  pub format: CsvFormat,
+  /// Delimiter between the fields of the delimited format
+  pub delimiter: Delimiter,
+  /// Quote character used in the actual and generated contents. The expected contents are always
+  /// written with double quotes.
+  pub quote: u8,
+  /// Which values are quoted in the configured and generated contents
+  pub quote_style: QuoteStyle,
+  /// Compression of the generated contents
+  pub compression: Compression,
+  /// How the contents are compared
+  pub compare_mode: CompareMode,
+  /// Convert all line endings to LF before comparing the raw bytes
+  pub normalize_line_endings: bool,
+  /// Minimum number of columns each actual row must have
+  pub min_columns: Option<usize>,
+  /// Exact number of columns each actual row must have
+  pub exact_columns: Option<usize>,
+  /// Maximum size of a field of the contents in bytes, checked before the contents are read
+  pub max_field_size: usize,
+  /// How the order of the header row is checked
+  pub header_order: HeaderOrder,
+  /// The content must have exactly one record
+  pub single_record: bool,
+  /// Remove trailing zeros from the fraction of numbers in numeric columns before comparing them
+  pub normalize_numbers: bool,
+  /// Characters that are removed as thousands separators from numbers in numeric columns before
+  /// comparing them
+  pub thousands_separators: Vec<char>,
+  /// Log the rules applied to each cell and their outcome when comparing
+  pub explain: bool,
+  /// Align the expected columns with the actual columns using the column definitions, for
+  /// content without headers
+  pub align_columns: bool,
+  /// Fail the comparison if the actual contents start or end with blank lines
+  pub no_blank_lines: bool,
+  /// Replace invalid UTF-8 sequences with the replacement character instead of failing
+  pub lossy_utf8: bool,
+  /// Options for the columns
+  pub columns: BTreeMap<String, ColumnOptions>,
+  /// Column definitions that apply to selected rows
+  pub rows: Vec<RowOverride>,
+  /// Spec to generate the contents from when there is no template
+  pub generator_spec: Option<GenerationSpec>,
+  /// Generator for the columns of the template that do not have a generator of their own
+  pub default_generator: Option<DefaultGenerator>,
+  /// Maximum number of mismatches to return from a comparison. The remaining mismatches are kept
+  /// and can be fetched with the continuation token.
+  pub page_size: Option<usize>,
+  /// Maximum number of mismatches to return for each column, the rest are replaced with a summary
+  /// mismatch for the column. Zero returns all of them.
+  pub max_column_mismatches: usize,
+  /// How numbers are rounded by the numeric matching rules
+  pub rounding_mode: RoundingMode,
+  /// Compare the values ignoring case, with equality matching or when there are no rules
+  pub ignore_case: Option<CaseFolding>,
+  /// Golden file (relative to the golden file directory) to load the expected contents from
+  pub golden_file: Option<String>,
+  /// File (relative to the directory of the mismatch log environment variable) to write the result
+  /// of each comparison to as JSON, with `{timestamp}` and `{pid}` placeholders
+  pub mismatch_log: Option<String>,
+  /// Prefix each mismatch description with its category, i.e. `[value] Expected ...`
+  pub mismatch_categories: bool,
+  /// Faults to inject into the generated contents, for negative testing
+  pub fault_injection: Vec<Fault>,
+  /// Generate the value of a cell again if it does not match the rules of its column, up to
+  /// max_generation_attempts times
+  pub retry_generation: bool,
+  pub max_generation_attempts: usize,
+  /// Seed for the random values from the plugin generators, so the generated contents can be
+  /// repeated
+  pub seed: Option<u64>,
+  /// Number of data rows to generate from the rows of the template
+  pub row_count: Option<usize>,
+  /// Token for the next page of mismatches from a previous comparison. This is only provided with
+  /// the compare request and is never stored with the interaction.
+  pub continuation_token: Option<String>
+}
+
+impl Default for CsvOptions {
+  fn default() -> Self {
+    CsvOptions {
+      has_headers: true,
+      format: CsvFormat::Delimited,
+      delimiter: Delimiter::Char(b','),
+      quote: b'"',
+      quote_style: QuoteStyle::Necessary,
+      compression: Compression::None,
+      compare_mode: CompareMode::Parsed,
+      normalize_line_endings: false,
+      min_columns: None,
+      exact_columns: None,
+      max_field_size: DEFAULT_MAX_FIELD_SIZE,
+      header_order: HeaderOrder::Strict,
+      single_record: false,
+      normalize_numbers: false,
+      thousands_separators: vec![],
+      explain: false,
+      align_columns: false,
+      no_blank_lines: false,
+      lossy_utf8: false,
+      columns: BTreeMap::default(),
+      rows: vec![],
+      generator_spec: None,
+      default_generator: None,
+      page_size: None,
+      max_column_mismatches: DEFAULT_MAX_COLUMN_MISMATCHES,
+      rounding_mode: RoundingMode::default(),
+      ignore_case: None,
+      golden_file: None,
+      mismatch_log: None,
+      mismatch_categories: false,
+      fault_injection: vec![],
+      retry_generation: false,
+      max_generation_attempts: DEFAULT_MAX_GENERATION_ATTEMPTS,
+      seed: None,
+      row_count: None,
+      continuation_token: None
+    }
+  }
+}
+
+impl CsvOptions {
+  /// Reads the options from the contents config supplied with a configure interaction request
+  pub fn from_contents_config(fields: &BTreeMap<String, prost_types::Value>) -> anyhow::Result<Self> {
+    let config = fields.iter()
+      .filter(|(key, _)| !key.starts_with("column:") && !key.starts_with("row:"))
+      .map(|(key, value)| (key.clone(), from_value(value)))
+      .collect();
+    let mut options = Self::from_map(&config)?;
+    options.has_headers = fields.get("csvHeaders").map(to_boolean).unwrap_or(true);
+    if options.header_order == HeaderOrder::SchemaMatch && !options.has_headers {
+      return Err(anyhow!("The schemaMatch header order compares the columns by name, so csvHeaders can not be false"));
+    }
+    Ok(options)
+  }
+
+  /// Options for the column with the given index (starting at 1) and header
+  pub fn column(&self, index: usize, header: &str) -> Option<&ColumnOptions> {
+    self.columns.get(&format!("column:{}", index))
+      .or_else(|| self.columns.get(&format!("column:{}", header)))
+  }
+
+  /// Delimiter to use to read the decoded content with the given quote character. If the
+  /// delimiter is set to auto, it is detected from the data.
+  pub fn reader_delimiter(&self, data: &[u8], quote: u8) -> u8 {
+    match (&self.format, self.delimiter) {
+      (CsvFormat::FixedWidth(_), _) => b',',
+      (_, Delimiter::Char(delimiter)) => delimiter,
+      (_, Delimiter::Auto) => sniff_delimiter(data, quote)
+    }
+  }
+
+  /// Quote character to use to read or write the actual content. Fixed-width content is always
+  /// converted to CSV with double quotes.
+  pub fn actual_quote(&self) -> u8 {
+    match self.format {
+      CsvFormat::FixedWidth(_) => b'"',
+      CsvFormat::Delimited | CsvFormat::Zip => self.quote
+    }
+  }
+
+  /// Delimiter to use to write generated content. The delimiter is never detected when
+  /// generating content, so this is an error if it is set to auto.
+  pub fn writer_delimiter(&self) -> anyhow::Result<u8> {
+    match (&self.format, self.delimiter) {
+      (CsvFormat::FixedWidth(_), _) => Ok(b','),
+      (_, Delimiter::Char(delimiter)) => Ok(delimiter),
+      (_, Delimiter::Auto) => Err(anyhow!("The delimiter must be set explicitly to generate content, it can not be auto"))
+    }
+  }
+
+  /// If the column with the given index (starting at 1) and header is optional
+  pub fn is_optional(&self, index: usize, header: &str) -> bool {
+    self.column(index, header).map(|column| column.optional).unwrap_or(false)
+  }
+
+  /// Tags the mismatch with its category, if the `mismatchCategories` option is set
+  pub fn categorise(&self, category: MismatchCategory, mismatch: proto::ContentMismatch) -> proto::ContentMismatch {
+    if self.mismatch_categories {
+      category.tag(mismatch)
+    } else {
+      mismatch
+    }
+  }
+
+  /// Applies the `delimiter` parameter of the content type (i.e. `text/csv;delimiter=;`), or a tab for
+  /// tab-separated values. A delimiter set in the config takes precedence, so the content type is only
+  /// used if the delimiter is still the default comma. The generated contents are compressed if the
+  /// content type has an `encoding=gzip` parameter.
+  pub fn with_content_type(mut self, content_type: &str) -> anyhow::Result<Self> {
+    if self.delimiter == Delimiter::Char(b',') {
+      match content_type_parameter(content_type, "delimiter") {
+        Some(delimiter) => self.delimiter = parse_delimiter(&delimiter)
+          .map_err(|err| anyhow!("Content type '{}' is not valid: {}", content_type, err))?,
+        None if is_tsv(content_type) => self.delimiter = Delimiter::Char(b'\t'),
+        None => {}
+      }
+    }
+    if content_type_parameter(content_type, "encoding").map(|encoding| encoding.eq_ignore_ascii_case("gzip")).unwrap_or(false) {
+      self.compression = Compression::Gzip;
+    }
+    Ok(self)
+  }
+
+  /// Reads the options from the plugin configuration stored with the interaction
+  pub fn from_plugin_config(plugin_config: &Option<proto::PluginConfiguration>) -> anyhow::Result<Self> {
+    match plugin_config.as_ref().and_then(|config| config.interaction_configuration.as_ref()) {
+      Some(config) => Self::from_map(&proto_struct_to_map(config)),
+      None => Ok(CsvOptions::default())
+    }
+  }
+
+  fn from_map(config: &HashMap<String, Value>) -> anyhow::Result<Self> {
+    let has_headers = match config.get("csvHeaders") {
+      Some(Value::Bool(b)) => *b,
+      _ => true
+    };
+
+    let format = match config.get("format") {
+      Some(Value::String(format)) => match format.as_str() {
+        "csv" | "delimited" => CsvFormat::Delimited,
+        "fixedWidth" => match config.get("columnWidths") {
+          Some(Value::Array(widths)) => {
+            let widths = widths.iter()
+              .map(|width| json_to_num(Some(width.clone()))
+                .filter(|width| *width > 0)
+                .ok_or_else(|| anyhow!("'{}' is not a valid column width, expected an integer >= 1", width)))
+              .collect::<anyhow::Result<Vec<usize>>>()?;
+            CsvFormat::FixedWidth(widths)
+          }
+          _ => return Err(anyhow!("The fixedWidth format requires a list of columnWidths"))
+        },
+        "zip" => CsvFormat::Zip,
+        _ => return Err(anyhow!("'{}' is not a supported format, expected csv, fixedWidth or zip", format))
+      },
+      Some(format) => return Err(anyhow!("'{}' is not a valid format, expected a string", format)),
+      None => CsvFormat::Delimited
+    };
+
+    let delimiter = match config.get("delimiter") {
+      Some(Value::String(delimiter)) => parse_delimiter(delimiter)?,
+      Some(delimiter) => return Err(anyhow!("'{}' is not a valid delimiter, expected a string", delimiter)),
+      None => Delimiter::Char(b',')
+    };
+
+    let quote = match config.get("quote") {
+      Some(Value::String(quote)) if quote.len() == 1 && quote.is_ascii() => quote.as_bytes()[0],
+      Some(quote) => return Err(anyhow!("'{}' is not a valid quote character, expected a single character", quote)),
+      None => b'"'
+    };
+
+    let quote_style = match config.get("quoteStyle") {
+      Some(Value::String(style)) => match style.as_str() {
+        "always" => QuoteStyle::Always,
+        "necessary" => QuoteStyle::Necessary,
+        "nonNumeric" => QuoteStyle::NonNumeric,
+        "never" => QuoteStyle::Never,
+        _ => return Err(anyhow!("'{}' is not a supported quote style, expected always, necessary, nonNumeric or never", style))
+      },
+      Some(style) => return Err(anyhow!("'{}' is not a valid quote style, expected a string", style)),
+      None => QuoteStyle::Necessary
+    };
+
+    let compression = match config.get("compression") {
+      Some(Value::String(compression)) => match compression.as_str() {
+        "none" => Compression::None,
+        "gzip" => Compression::Gzip,
+        _ => return Err(anyhow!("'{}' is not a supported compression, expected none or gzip", compression))
+      },
+      Some(compression) => return Err(anyhow!("'{}' is not a valid compression, expected a string", compression)),
+      None => Compression::None
+    };
+
+    let compare_mode = match config.get("compareMode") {
+      Some(Value::String(mode)) => match mode.as_str() {
+        "parsed" => CompareMode::Parsed,
+        "bytes" => CompareMode::Bytes,
+        _ => return Err(anyhow!("'{}' is not a supported compare mode, expected parsed or bytes", mode))
+      },
+      Some(mode) => return Err(anyhow!("'{}' is not a valid compare mode, expected a string", mode)),
+      None => CompareMode::Parsed
+    };
+
+    let header_order = match config.get("headerOrder") {
+      Some(Value::String(order)) => match order.as_str() {
+        "strict" => HeaderOrder::Strict,
+        "any" => HeaderOrder::Any,
+        "schemaMatch" => HeaderOrder::SchemaMatch,
+        _ => return Err(anyhow!("'{}' is not a supported header order, expected strict, any or schemaMatch", order))
+      },
+      Some(order) => return Err(anyhow!("'{}' is not a valid header order, expected a string", order)),
+      None => HeaderOrder::Strict
+    };
+
+    let columns = match config.get("columns") {
+      Some(Value::Object(columns)) => columns.iter()
+        .map(|(path, column)| ColumnOptions::from_json(column).map(|column| (path.clone(), column)))
+        .collect::<anyhow::Result<BTreeMap<_, _>>>()?,
+      _ => BTreeMap::default()
+    };
+
+    let rows = match config.get("rows") {
+      Some(Value::Array(rows)) => rows.iter().map(RowOverride::from_json).collect::<anyhow::Result<Vec<_>>>()?,
+      _ => vec![]
+    };
+
+    let exact_columns = usize_option(config, "exactColumns")?;
+    if exact_columns == Some(0) {
+      return Err(anyhow!("exactColumns must be greater than zero"));
+    }
+    let max_field_size = usize_option(config, "maxFieldSize")?.unwrap_or(DEFAULT_MAX_FIELD_SIZE);
+    if max_field_size == 0 {
+      return Err(anyhow!("maxFieldSize must be greater than zero"));
+    }
+    let page_size = usize_option(config, "pageSize")?;
+    if page_size == Some(0) {
+      return Err(anyhow!("pageSize must be greater than zero"));
+    }
+    let max_column_mismatches = usize_option(config, "maxColumnMismatches")?.unwrap_or(DEFAULT_MAX_COLUMN_MISMATCHES);
+    let thousands_separators = match config.get("thousandsSeparators") {
+      Some(Value::String(separators)) if separators.contains(['.', '-', '+']) || separators.chars().any(|ch| ch.is_ascii_digit()) =>
+        return Err(anyhow!("'{}' is not valid for thousandsSeparators, the separators can not be digits, signs or the decimal point", separators)),
+      Some(Value::String(separators)) => separators.chars().collect(),
+      Some(value) => return Err(anyhow!("'{}' is not valid for thousandsSeparators, expected a string of the separator characters", value)),
+      None => vec![]
+    };
+    let rounding_mode = match config.get("roundingMode") {
+      Some(Value::String(mode)) => RoundingMode::parse(mode)?,
+      Some(value) => return Err(anyhow!("'{}' is not a valid rounding mode, expected halfEven, halfUp or truncate", value)),
+      None => RoundingMode::default()
+    };
+    let ignore_case = match (bool_option(config, "ignoreCase", false)?, config.get("ignoreCaseLocale")) {
+      (true, Some(Value::String(locale))) => Some(CaseFolding::Locale(locale.clone())),
+      (true, Some(value)) => return Err(anyhow!("'{}' is not a valid value for ignoreCaseLocale, expected a locale (i.e. tr-TR)", value)),
+      (true, None) => Some(CaseFolding::Simple),
+      (false, _) => None
+    };
+    let golden_file = match config.get("goldenFile") {
+      Some(Value::String(path)) => Some(path.clone()),
+      Some(value) => return Err(anyhow!("'{}' is not a valid golden file, expected a path", value)),
+      None => None
+    };
+    let max_generation_attempts = match usize_option(config, "maxGenerationAttempts")? {
+      Some(0) => return Err(anyhow!("maxGenerationAttempts must be at least 1")),
+      Some(attempts) => attempts,
+      None => DEFAULT_MAX_GENERATION_ATTEMPTS
+    };
+    let mismatch_log = match config.get("mismatchLog") {
+      Some(Value::String(path)) => Some(path.clone()),
+      Some(value) => return Err(anyhow!("'{}' is not a valid mismatch log, expected a file name", value)),
+      None => None
+    };
+    let fault_injection = match config.get("faultInjection") {
+      Some(Value::Array(faults)) => faults.iter().map(Fault::from_json).collect::<anyhow::Result<Vec<_>>>()?,
+      Some(value) => return Err(anyhow!("'{}' is not a valid value for faultInjection, expected a list of faults", value)),
+      None => vec![]
+    };
+    let seed = match config.get("seed") {
+      Some(value) => Some(value.as_u64()
+        .ok_or_else(|| anyhow!("'{}' is not a valid value for seed, expected a positive integer", value))?),
+      None => None
+    };
+    let continuation_token = match config.get("continuationToken") {
+      Some(Value::String(token)) => Some(token.clone()),
+      Some(value) => return Err(anyhow!("'{}' is not a valid continuation token, expected a string", value)),
+      None => None
+    };
+
+    Ok(CsvOptions {
+      has_headers,
+      format,
+      delimiter,
+      quote,
+      quote_style,
+      compression,
+      compare_mode,
+      normalize_line_endings: bool_option(config, "normalizeLineEndings", false)?,
+      min_columns: usize_option(config, "minColumns")?,
+      exact_columns,
+      max_field_size,
+      header_order,
+      single_record: bool_option(config, "singleRecord", false)?,
+      normalize_numbers: bool_option(config, "normalizeNumbers", false)?,
+      thousands_separators,
+      explain: bool_option(config, "explain", false)?,
+      align_columns: bool_option(config, "alignColumns", false)?,
+      no_blank_lines: bool_option(config, "noBlankLines", false)?,
+      lossy_utf8: bool_option(config, "lossyUtf8", false)?,
+      mismatch_categories: bool_option(config, "mismatchCategories", false)?,
+      columns,
+      rows,
+      generator_spec: config.get("generatorSpec").map(GenerationSpec::from_json).transpose()?,
+      default_generator: config.get("defaultGenerator").map(DefaultGenerator::from_json).transpose()?,
+      page_size,
+      max_column_mismatches,
+      rounding_mode,
+      ignore_case,
+      golden_file,
+      mismatch_log,
+      fault_injection,
+      retry_generation: bool_option(config, "retryGeneration", false)?,
+      max_generation_attempts,
+      seed,
+      row_count: usize_option(config, "rowCount")?,
+      continuation_token
+    })
+  }
+
+  /// Converts the options into the values stored in the interaction plugin configuration
+  pub fn to_config(&self) -> HashMap<String, Value> {
+    let mut config = HashMap::new();
+    config.insert("csvHeaders".to_string(), json!(self.has_headers));
+    match &self.format {
+      CsvFormat::Delimited => {}
+      CsvFormat::FixedWidth(widths) => {
+        config.insert("format".to_string(), json!("fixedWidth"));
+        config.insert("columnWidths".to_string(), json!(widths));
+      }
+      CsvFormat::Zip => { config.insert("format".to_string(), json!("zip")); }
+    }
+    match self.delimiter {
+      Delimiter::Auto => { config.insert("delimiter".to_string(), json!("auto")); }
+      Delimiter::Char(b',') => {}
+      Delimiter::Char(delimiter) => { config.insert("delimiter".to_string(), json!((delimiter as char).to_string())); }
+    }
+    if self.quote != b'"' {
+      config.insert("quote".to_string(), json!((self.quote as char).to_string()));
+    }
+    match self.quote_style {
+      QuoteStyle::Always => { config.insert("quoteStyle".to_string(), json!("always")); }
+      QuoteStyle::Necessary => {}
+      QuoteStyle::NonNumeric => { config.insert("quoteStyle".to_string(), json!("nonNumeric")); }
+      QuoteStyle::Never => { config.insert("quoteStyle".to_string(), json!("never")); }
+    }
+    if self.compression == Compression::Gzip {
+      config.insert("compression".to_string(), json!("gzip"));
+    }
+    if self.compare_mode == CompareMode::Bytes {
+      config.insert("compareMode".to_string(), json!("bytes"));
+    }
+    if self.normalize_line_endings {
+      config.insert("normalizeLineEndings".to_string(), json!(true));
+    }
+    if let Some(min_columns) = self.min_columns {
+      config.insert("minColumns".to_string(), json!(min_columns));
+    }
+    if let Some(exact_columns) = self.exact_columns {
+      config.insert("exactColumns".to_string(), json!(exact_columns));
+    }
+    if self.max_field_size != DEFAULT_MAX_FIELD_SIZE {
+      config.insert("maxFieldSize".to_string(), json!(self.max_field_size));
+    }
+    match self.header_order {
+      HeaderOrder::Strict => {}
+      HeaderOrder::Any => { config.insert("headerOrder".to_string(), json!("any")); }
+      HeaderOrder::SchemaMatch => { config.insert("headerOrder".to_string(), json!("schemaMatch")); }
+    }
+    if self.single_record {
+      config.insert("singleRecord".to_string(), json!(true));
+    }
+    if self.normalize_numbers {
+      config.insert("normalizeNumbers".to_string(), json!(true));
+    }
+    if !self.thousands_separators.is_empty() {
+      config.insert("thousandsSeparators".to_string(), json!(self.thousands_separators.iter().collect::<String>()));
+    }
+    if self.explain {
+      config.insert("explain".to_string(), json!(true));
+    }
+    if self.align_columns {
+      config.insert("alignColumns".to_string(), json!(true));
+    }
+    if self.no_blank_lines {
+      config.insert("noBlankLines".to_string(), json!(true));
+    }
+    if self.lossy_utf8 {
+      config.insert("lossyUtf8".to_string(), json!(true));
+    }
+    if self.mismatch_categories {
+      config.insert("mismatchCategories".to_string(), json!(true));
+    }
+    let columns = self.columns.iter()
+      .filter(|(_, column)| !column.is_empty())
+      .map(|(path, column)| (path.clone(), column.to_json()))
+      .collect::<serde_json::Map<_, _>>();
+    if !columns.is_empty() {
+      config.insert("columns".to_string(), Value::Object(columns));
+    }
+    if !self.rows.is_empty() {
+      config.insert("rows".to_string(), Value::Array(self.rows.iter().map(|row| row.to_json()).collect()));
+    }
+    if let Some(spec) = &self.generator_spec {
+      config.insert("generatorSpec".to_string(), spec.to_json());
+    }
+    if let Some(default_generator) = &self.default_generator {
+      config.insert("defaultGenerator".to_string(), default_generator.to_json());
+    }
+    if let Some(page_size) = self.page_size {
+      config.insert("pageSize".to_string(), json!(page_size));
+    }
+    if self.max_column_mismatches != DEFAULT_MAX_COLUMN_MISMATCHES {
+      config.insert("maxColumnMismatches".to_string(), json!(self.max_column_mismatches));
+    }
+    if self.rounding_mode != RoundingMode::default() {
+      config.insert("roundingMode".to_string(), json!(self.rounding_mode.to_string()));
+    }
+    match &self.ignore_case {
+      Some(CaseFolding::Simple) => { config.insert("ignoreCase".to_string(), json!(true)); }
+      Some(CaseFolding::Locale(locale)) => {
+        config.insert("ignoreCase".to_string(), json!(true));
+        config.insert("ignoreCaseLocale".to_string(), json!(locale));
+      }
+      None => {}
+    }
+    if let Some(golden_file) = &self.golden_file {
+      config.insert("goldenFile".to_string(), json!(golden_file));
+    }
+    if self.retry_generation {
+      config.insert("retryGeneration".to_string(), json!(true));
+    }
+    if self.max_generation_attempts != DEFAULT_MAX_GENERATION_ATTEMPTS {
+      config.insert("maxGenerationAttempts".to_string(), json!(self.max_generation_attempts));
+    }
+    if let Some(mismatch_log) = &self.mismatch_log {
+      config.insert("mismatchLog".to_string(), json!(mismatch_log));
+    }
+    if !self.fault_injection.is_empty() {
+      config.insert("faultInjection".to_string(), Value::Array(self.fault_injection.iter().map(Fault::to_json).collect()));
+    }
+    if let Some(seed) = self.seed {
+      config.insert("seed".to_string(), json!(seed));
+    }
+    if let Some(row_count) = self.row_count {
+      config.insert("rowCount".to_string(), json!(row_count));
+    }
+    config
+  }
+}
+
+/// Media type of the content type in lower case, without any parameters (i.e. `text/csv` for
+/// `TEXT/CSV; charset=utf-8`). All the content types received by the plugin are compared by this.
+pub fn media_type(content_type: &str) -> String {
+  content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase()
+}
+
+/// If the content type is for tab-separated values, ignoring any parameters
+pub fn is_tsv(content_type: &str) -> bool {
+  media_type(content_type) == TSV_CONTENT_TYPE
+}
+
+/// If the content type is one of the CSV or TSV media types handled by the plugin, ignoring any
+/// parameters (i.e. `text/csv; charset=UTF-8`). This includes media types with a `+csv` suffix.
+pub fn is_csv_content_type(content_type: &str) -> bool {
+  let media_type = media_type(content_type);
+  CONTENT_TYPES.contains(&media_type.as_str()) || media_type.ends_with("+csv")
+}
+
+/// Content type of the contents returned by the plugin, which is TSV if the request was for TSV.
+/// Any other content type (i.e. `application/csv`) is normalised to `text/csv`.
+pub fn body_content_type(content_type: &str) -> &'static str {
+  if is_tsv(content_type) { "text/tab-separated-values;charset=UTF-8" } else { "text/csv;charset=UTF-8" }
+}
+
+fn parse_delimiter(delimiter: &str) -> anyhow::Result<Delimiter> {
+  match delimiter {
+    "auto" => Ok(Delimiter::Auto),
+    "tab" | "\t" => Ok(Delimiter::Char(b'\t')),
+    _ if delimiter.len() == 1 && delimiter.is_ascii() => Ok(Delimiter::Char(delimiter.as_bytes()[0])),
+    _ => Err(anyhow!("'{}' is not a valid delimiter, expected a single character, tab or auto", delimiter))
+  }
+}
+
+/// Value of a parameter of a content type. The value can be quoted, and as a semicolon also
+/// separates the parameters, an unquoted `delimiter=;` is read as a semicolon.
+fn content_type_parameter(content_type: &str, parameter: &str) -> Option<String> {
+  let mut rest = content_type.split_once(';')?.1;
+  loop {
+    let (name, value) = rest.trim_start().split_once('=')?;
+    let (value, remainder) = if let Some(quoted) = value.strip_prefix('"') {
+      let (value, remainder) = quoted.split_once('"').unwrap_or((quoted, ""));
+      (value, remainder.split_once(';').map(|(_, remainder)| remainder).unwrap_or(""))
+    } else if let Some(remainder) = value.strip_prefix(';') {
+      (";", remainder.split_once(';').map(|(_, remainder)| remainder).unwrap_or(""))
+    } else {
+      let (value, remainder) = value.split_once(';').unwrap_or((value, ""));
+      (value.trim(), remainder)
+    };
+    if name.trim().eq_ignore_ascii_case(parameter) {
+      return Some(value.to_string());
+    }
+    rest = remainder;
+  }
+}
+
+fn bool_option(config: &HashMap<String, Value>, key: &str, default: bool) -> anyhow::Result<bool> {
+  match config.get(key) {
+    Some(Value::Bool(b)) => Ok(*b),
+    Some(value) => Err(anyhow!("'{}' is not a valid value for {}, expected a boolean", value, key)),
+    None => Ok(default)
+  }
+}
+
+fn usize_option(config: &HashMap<String, Value>, key: &str) -> anyhow::Result<Option<usize>> {
+  match config.get(key) {
+    Some(value) => json_to_num(Some(value.clone()))
+      .map(Some)
+      .ok_or_else(|| anyhow!("'{}' is not a valid value for {}, expected a positive integer", value, key)),
+    None => Ok(None)
+  }
+}
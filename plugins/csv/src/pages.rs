@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use log::debug;
+use uuid::Uuid;
+
+use crate::proto;
+
+/// Time the remaining mismatches of a paged comparison are kept for
+pub const PAGE_TTL: Duration = Duration::from_secs(300);
+
+/// Key of the results of the compare contents response with the continuation token, when there are
+/// more mismatches than the page size. It is kept separate from the mismatches so that it is not
+/// counted as one.
+pub const CONTINUATION_TOKEN_KEY: &str = "continuationToken";
+
+/// Number of mismatches returned for each column if the `maxColumnMismatches` option is not set
+pub const DEFAULT_MAX_COLUMN_MISMATCHES: usize = 10;
+
+/// Mismatches from a comparison that have not been returned yet
+#[derive(Debug)]
+struct PendingMismatches {
+  page_size: usize,
+  mismatches: Vec<proto::ContentMismatch>,
+  expires: Instant
+}
+
+/// A page of the mismatches of a comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct MismatchPage {
+  pub mismatches: Vec<proto::ContentMismatch>,
+  /// Continuation token for the next page and the number of mismatches that are left, if there are more
+  pub continuation: Option<(String, usize)>
+}
+
+impl MismatchPage {
+  /// The results for the compare contents response, with the continuation token under
+  /// `CONTINUATION_TOKEN_KEY`. The actual value of its entry is the token.
+  pub fn into_results(self) -> HashMap<String, proto::ContentMismatches> {
+    let mut results = HashMap::new();
+    results.insert(String::default(), proto::ContentMismatches { mismatches: self.mismatches });
+    if let Some((token, remaining)) = self.continuation {
+      results.insert(CONTINUATION_TOKEN_KEY.to_string(), proto::ContentMismatches {
+        mismatches: vec![proto::ContentMismatch {
+          expected: None,
+          actual: Some(token.as_bytes().to_vec()),
+          mismatch: format!("There are {} more mismatches, repeat the comparison with continuationToken '{}' to get the next page",
+            remaining, token),
+          path: String::default(),
+          diff: String::default()
+        }]
+      });
+    }
+    results
+  }
+}
+
+/// Keeps the remaining mismatches of paged comparisons, keyed by the continuation token returned
+/// with each page. Entries are removed once the last page has been fetched, or when they expire.
+#[derive(Debug)]
+pub struct MismatchPages {
+  pages: Mutex<HashMap<String, PendingMismatches>>,
+  ttl: Duration
+}
+
+impl Default for MismatchPages {
+  fn default() -> Self {
+    MismatchPages::new(PAGE_TTL)
+  }
+}
+
+impl MismatchPages {
+  /// Create a new cursor store where the entries expire after the given time
+  pub fn new(ttl: Duration) -> Self {
+    MismatchPages {
+      pages: Mutex::new(HashMap::new()),
+      ttl
+    }
+  }
+
+  /// Returns the first page of the mismatches. If there are more mismatches than the page size,
+  /// the rest are kept and the page has the continuation token for them.
+  pub fn first_page(
+    &self,
+    mismatches: Vec<proto::ContentMismatch>,
+    page_size: usize
+  ) -> anyhow::Result<MismatchPage> {
+    self.paginate(Uuid::new_v4().to_string(), mismatches, page_size)
+  }
+
+  /// Returns the next page of mismatches for the continuation token
+  pub fn next_page(&self, token: &str) -> anyhow::Result<MismatchPage> {
+    let pending = {
+      let mut pages = self.pages.lock().map_err(|_| anyhow!("Mismatch page lock is poisoned"))?;
+      remove_expired(&mut pages);
+      pages.remove(token)
+    };
+    match pending {
+      Some(pending) => self.paginate(token.to_string(), pending.mismatches, pending.page_size),
+      None => Err(anyhow!("Continuation token '{}' is not known, or has expired", token))
+    }
+  }
+
+  fn paginate(
+    &self,
+    token: String,
+    mut mismatches: Vec<proto::ContentMismatch>,
+    page_size: usize
+  ) -> anyhow::Result<MismatchPage> {
+    if mismatches.len() <= page_size {
+      return Ok(MismatchPage { mismatches, continuation: None });
+    }
+
+    let remaining = mismatches.split_off(page_size);
+    debug!("Keeping {} mismatches for continuation token '{}'", remaining.len(), token);
+    let continuation = Some((token.clone(), remaining.len()));
+
+    // Expired entries are also removed here, as the later pages may never be fetched
+    let mut pages = self.pages.lock().map_err(|_| anyhow!("Mismatch page lock is poisoned"))?;
+    remove_expired(&mut pages);
+    pages.insert(token, PendingMismatches {
+      page_size,
+      mismatches: remaining,
+      expires: Instant::now() + self.ttl
+    });
+    Ok(MismatchPage { mismatches, continuation })
+  }
+}
+
+fn remove_expired(pages: &mut HashMap<String, PendingMismatches>) {
+  let now = Instant::now();
+  pages.retain(|_, pending| pending.expires > now);
+}
+
+/// Keeps the first `max` mismatches of each column, and replaces the rest with a single summary
+/// mismatch for the column at the end, i.e. `and 412 more mismatches in column:3`. Mismatches that
+/// are not for a column (i.e. the row count) are always kept. A maximum of zero keeps them all.
+pub fn limit_column_mismatches(mismatches: Vec<proto::ContentMismatch>, max: usize) -> Vec<proto::ContentMismatch> {
+  if max == 0 {
+    return mismatches;
+  }
+
+  let mut counts: Vec<(String, usize)> = vec![];
+  let mut result = vec![];
+  for mismatch in mismatches {
+    match column_path(&mismatch.path) {
+      Some(column) => {
+        let count = match counts.iter().position(|(path, _)| *path == column) {
+          Some(index) => &mut counts[index].1,
+          None => {
+            counts.push((column, 0));
+            &mut counts.last_mut().unwrap().1
+          }
+        };
+        *count += 1;
+        if *count <= max {
+          result.push(mismatch);
+        }
+      }
+      None => result.push(mismatch)
+    }
+  }
+
+  for (column, count) in counts.iter().filter(|(_, count)| *count > max) {
+    debug!("Omitting {} mismatches for {}", count - max, column);
+    result.push(proto::ContentMismatch {
+      expected: None,
+      actual: None,
+      mismatch: format!("and {} more mismatches in {}", count - max, column),
+      path: column.clone(),
+      diff: String::default()
+    });
+  }
+  result
+}
+
+// The column that a mismatch path is for, without the row and the padding of the numbers, i.e.
+// `column:3` for `row:   12, column: 3`. Archive member prefixes are kept, so that the columns of
+// each member are counted separately.
+fn column_path(path: &str) -> Option<String> {
+  let parts = path.split(", ")
+    .filter(|part| !part.starts_with("row:"))
+    .map(|part| match part.split_once(':') {
+      Some((name, value)) => format!("{}:{}", name, value.trim()),
+      None => part.to_string()
+    })
+    .collect::<Vec<_>>();
+  if parts.iter().any(|part| part.starts_with("column:")) {
+    Some(parts.join(", "))
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use expectest::prelude::*;
+
+  use crate::proto;
+
+  use super::{CONTINUATION_TOKEN_KEY, limit_column_mismatches, MismatchPages};
+
+  fn mismatches(count: usize) -> Vec<proto::ContentMismatch> {
+    (1..=count).map(|i| proto::ContentMismatch {
+      mismatch: format!("mismatch {}", i),
+      .. proto::ContentMismatch::default()
+    }).collect()
+  }
+
+  fn messages(mismatches: &[proto::ContentMismatch]) -> Vec<&str> {
+    mismatches.iter().map(|mismatch| mismatch.mismatch.as_str()).collect()
+  }
+
+  #[test]
+  fn pages_through_the_mismatches() {
+    let pages = MismatchPages::default();
+    let all = pages.first_page(mismatches(2), 2).unwrap();
+    expect!(all.mismatches.len()).to(be_equal_to(2));
+    expect!(all.continuation).to(be_none());
+
+    let first = pages.first_page(mismatches(5), 2).unwrap();
+    expect!(messages(&first.mismatches)).to(be_equal_to(vec!["mismatch 1", "mismatch 2"]));
+    let (token, remaining) = first.continuation.clone().unwrap();
+    expect!(remaining).to(be_equal_to(3));
+
+    let second = pages.next_page(&token).unwrap();
+    expect!(messages(&second.mismatches)).to(be_equal_to(vec!["mismatch 3", "mismatch 4"]));
+    expect!(second.continuation).to(be_some().value((token.clone(), 1)));
+
+    let last = pages.next_page(&token).unwrap();
+    expect!(messages(&last.mismatches)).to(be_equal_to(vec!["mismatch 5"]));
+    expect!(last.continuation).to(be_none());
+    expect!(pages.next_page(&token)).to(be_err());
+  }
+
+  #[test]
+  fn the_continuation_token_is_not_one_of_the_mismatches() {
+    let pages = MismatchPages::default();
+    let results = pages.first_page(mismatches(3), 2).unwrap().into_results();
+    expect!(results[""].mismatches.len()).to(be_equal_to(2));
+    let continuation = &results[CONTINUATION_TOKEN_KEY].mismatches;
+    expect!(continuation.len()).to(be_equal_to(1));
+    let token = String::from_utf8(continuation[0].actual.clone().unwrap()).unwrap();
+    expect!(continuation[0].mismatch.clone()).to(be_equal_to(format!(
+      "There are 1 more mismatches, repeat the comparison with continuationToken '{}' to get the next page", token)));
+
+    let results = pages.next_page(&token).unwrap().into_results();
+    expect!(results.keys().collect::<Vec<_>>()).to(be_equal_to(vec![""]));
+  }
+
+  #[test]
+  fn expired_pages_are_removed() {
+    let pages = MismatchPages::new(Duration::from_millis(0));
+    let (token, _) = pages.first_page(mismatches(3), 1).unwrap().continuation.unwrap();
+    expect!(pages.next_page(&token)).to(be_err());
+  }
+
+  #[test]
+  fn expired_pages_are_removed_when_a_new_comparison_is_paged() {
+    let pages = MismatchPages::new(Duration::from_millis(0));
+    for _ in 0..3 {
+      pages.first_page(mismatches(3), 1).unwrap();
+    }
+    expect!(pages.pages.lock().unwrap().len()).to(be_equal_to(1));
+  }
+
+  #[test]
+  fn limit_column_mismatches_summarises_the_rest_of_each_column() {
+    let mismatch = |message: &str, path: &str| proto::ContentMismatch {
+      mismatch: message.to_string(),
+      path: path.to_string(),
+      .. proto::ContentMismatch::default()
+    };
+    let mismatches = vec![
+      mismatch("header", "header"),
+      mismatch("a1", "row:    1, column: 1"),
+      mismatch("b1", "row:    1, column: 2"),
+      mismatch("a2", "row:    2, column: 1"),
+      mismatch("a3", "row:    3, column: 1"),
+      mismatch("count", ""),
+      mismatch("a4", "row:    4, column: 1"),
+      mismatch("b2", "row:    2, column: 2"),
+      mismatch("b3", "row:    3, column: 2")
+    ];
+
+    let limited = limit_column_mismatches(mismatches.clone(), 2);
+    expect!(messages(&limited)).to(be_equal_to(vec!["header", "a1", "b1", "a2", "count", "b2",
+      "and 2 more mismatches in column:1", "and 1 more mismatches in column:2"]));
+    expect!(limited[6].path.as_str()).to(be_equal_to("column:1"));
+    expect!(limit_column_mismatches(mismatches.clone(), 4)).to(be_equal_to(mismatches.clone()));
+    expect!(limit_column_mismatches(mismatches.clone(), 0)).to(be_equal_to(mismatches));
+  }
+
+  #[test]
+  fn limit_column_mismatches_counts_the_columns_of_each_archive_member() {
+    let mismatches = ["a.csv", "b.csv", "a.csv"].iter().enumerate()
+      .map(|(row, member)| proto::ContentMismatch {
+        mismatch: member.to_string(),
+        path: format!("member:{}, row:{:5}, column:{:2}", member, row + 1, 0),
+        .. proto::ContentMismatch::default()
+      })
+      .collect();
+    expect!(messages(&limit_column_mismatches(mismatches, 1))).to(be_equal_to(vec!["a.csv", "b.csv",
+      "and 1 more mismatches in member:a.csv, column:0"]));
+  }
+}
@@ -1,10 +1,20 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
 use anyhow::anyhow;
 use logos::Logos;
-use pact_models::matchingrules::expressions::{MatchingRuleDefinition, parse_matcher_def};
+use pact_models::matchingrules::expressions::{MatchingRuleDefinition, parse_matcher_def, ValueType};
+use pact_models::time_utils::generate_string;
 use prost_types::value::Kind;
 use either::Either;
 use either::Either::{Left, Right};
 
+use crate::constraints::ColumnConstraint;
+use crate::generators::CsvGenerator;
+use crate::matchers::{CsvRule, parse_duration, RoundingMode};
+use crate::rows::RowOverride;
+use crate::transforms::Transform;
+
 #[derive(Logos, Debug, PartialEq)]
 enum FieldToken {
   #[token("column")]
@@ -13,35 +23,29 @@ enum FieldToken {
   #[token(":")]
   Colon,
 
-  #[regex("[a-zA-Z]+")]
-  Text,
-
-  #[regex("[0-9]+", |lex| lex.slice().parse())]
-  Int(i64),
-
   #[error]
   #[regex(r"[ \t\n\f]+", logos::skip)]
   Error,
 }
 
-// field -> "column" : int | text
+// field -> "column" : int | name, where the name is the rest of the field so it can be any header
+// (i.e. `column:total_amount` or `column:First Name`)
 pub(crate) fn parse_field(s: &str) -> anyhow::Result<Either<usize, String>> {
   let mut lex = FieldToken::lexer(s);
   let first = lex.next();
   if first == Some(FieldToken::Column) {
     let second = lex.next();
     if second == Some(FieldToken::Colon) {
-      let third = lex.next();
-      if let Some(FieldToken::Int(i)) = third {
-        if i < 1 {
-          Err(anyhow!("'{}' is not a valid field definition, expected an integer >= 1, got {}", s, i))
-        } else {
-          Ok(Left(i as usize))
+      let name = lex.remainder().trim();
+      if name.is_empty() {
+        Err(anyhow!("'{}' is not a valid field definition, expected an integer or a column name", s))
+      } else if name.chars().all(|ch| ch.is_ascii_digit()) {
+        match name.parse::<usize>() {
+          Ok(i) if i >= 1 => Ok(Left(i)),
+          _ => Err(anyhow!("'{}' is not a valid field definition, expected an integer >= 1, got {}", s, name))
         }
-      } else if let Some(FieldToken::Text) = third {
-        Ok(Right(lex.slice().to_string()))
       } else {
-        Err(anyhow!("'{}' is not a valid field definition, expected an integer, got '{}'", s, lex.remainder()))
+        Ok(Right(name.to_string()))
       }
     } else {
       Err(anyhow!("'{}' is not a valid field definition, expected ':', got '{}'", s, lex.remainder()))
@@ -51,10 +55,35 @@ pub(crate) fn parse_field(s: &str) -> anyhow::Result<Either<usize, String>> {
   }
 }
 
-pub(crate) fn parse_value(v: &prost_types::Value) -> anyhow::Result<MatchingRuleDefinition> {
+/// Parsed definition of a column
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ColumnDefinition {
+  /// Example value, Pact matching rules and generator for the column
+  pub definition: MatchingRuleDefinition,
+  /// Matching rules provided by this plugin
+  pub rules: Vec<CsvRule>,
+  /// Transforms to apply to the actual values before matching
+  pub transforms: Vec<Transform>,
+  /// Generator provided by this plugin for the column
+  pub generator: Option<CsvGenerator>,
+  /// The column can be missing from the end of a row
+  pub optional: bool,
+  /// The values are always compared as text, and are never normalized as numbers
+  pub text: bool,
+  /// The values of the column across all the rows are compared as a multiset
+  pub multiset: bool,
+  /// All the values of the column must be the same inferred type
+  pub homogeneous: bool,
+  /// The rules are applied to every actual row, including the rows after the last expected row
+  pub each_value: bool,
+  /// Constraints between the values of the column and the values of other columns
+  pub constraints: Vec<ColumnConstraint>
+}
+
+pub(crate) fn parse_value(v: &prost_types::Value) -> anyhow::Result<ColumnDefinition> {
   if let Some(kind) = &v.kind {
     match kind {
-      Kind::StringValue(s) => parse_matcher_def(&s),
+      Kind::StringValue(s) => parse_column_definition(s),
       Kind::NullValue(_) => Err(anyhow!("Null is not a valid value definition value")),
       Kind::NumberValue(_) => Err(anyhow!("Number is not a valid value definition value")),
       Kind::BoolValue(_) => Err(anyhow!("Bool is not a valid value definition value")),
@@ -65,3 +94,885 @@ pub(crate) fn parse_value(v: &prost_types::Value) -> anyhow::Result<MatchingRule
     Err(anyhow!("Not a valid value definition (missing value)"))
   }
 }
+
+/// Error found when validating a config map, with the key of the entry that is not valid
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConfigError {
+  /// Key of the config entry
+  pub key: String,
+  /// Description of the error
+  pub message: String
+}
+
+impl Display for ConfigError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: {}", self.key, self.message)
+  }
+}
+
+/// Validates the column (`column:<column>`) and row (`row:<selector>:column:<column>`) definitions
+/// in the config map, and returns all the errors found, sorted by key. No contents are built from
+/// the definitions. Entries with other keys are the content options, and are not checked here.
+pub(crate) fn validate_config(fields: &HashMap<String, String>) -> Vec<ConfigError> {
+  let mut errors = fields.iter()
+    .filter_map(|(key, value)| {
+      let result = if key.starts_with("row:") {
+        // The rules of a row definition are stored with the options, so they can not be references
+        RowOverride::parse_key(key)
+          .and_then(|_| parse_column_definition(value))
+          .and_then(|column| match column.definition.rules.iter().find_map(|rule| rule.as_ref().right()) {
+            Some(reference) => Err(anyhow!("Expected a matching rule definition, but got an un-resolved reference {:?}", reference)),
+            None => Ok(())
+          })
+      } else if key.starts_with("column:") {
+        parse_field(key)
+          .and_then(|_| parse_column_definition(value))
+          .map(|_| ())
+      } else {
+        Ok(())
+      };
+      result.err().map(|err| ConfigError { key: key.clone(), message: err.to_string() })
+    })
+    .collect::<Vec<_>>();
+  errors.sort_by(|a, b| a.key.cmp(&b.key));
+  errors
+}
+
+// Column definitions are Pact matching rule definitions, with additional expressions supported
+// by this plugin. The plugin expressions are extracted, and the remaining expressions are passed
+// to the Pact matching rule definition parser.
+fn parse_column_definition(s: &str) -> anyhow::Result<ColumnDefinition> {
+  let expressions = match parse_expressions(s) {
+    Ok(expressions) => expressions,
+    Err(_) => return parse_matcher_def(s).map(|definition| ColumnDefinition {
+      definition,
+      rules: vec![],
+      transforms: vec![],
+      generator: None,
+      optional: false,
+      text: false,
+      multiset: false,
+      homogeneous: false,
+      each_value: false,
+      constraints: vec![]
+    })
+  };
+
+  let mut column = ColumnDefinition {
+    definition: MatchingRuleDefinition {
+      value: String::default(),
+      value_type: ValueType::Unknown,
+      rules: vec![],
+      generator: None
+    },
+    rules: vec![],
+    transforms: vec![],
+    generator: None,
+    optional: false,
+    text: false,
+    multiset: false,
+    homogeneous: false,
+    each_value: false,
+    constraints: vec![]
+  };
+  let mut pact_expressions = vec![];
+  for expression in &expressions {
+    if !apply_plugin_expression(expression, &mut column)? {
+      pact_expressions.push(expression.source.as_str());
+    }
+  }
+
+  if !pact_expressions.is_empty() {
+    let definition = parse_matcher_def(pact_expressions.join(", ").as_str())?;
+    column.definition = definition.merge(&column.definition);
+  }
+
+  Ok(column)
+}
+
+// The check digit rule for `matching(luhn)` and `matching(iban)`
+fn checksum_rule(expression: &Expression) -> Option<CsvRule> {
+  match expression.args.first() {
+    Some(Arg::Positional(ArgValue::Identifier(rule))) if expression.name == "matching" => match rule.as_str() {
+      "luhn" => Some(CsvRule::Luhn),
+      "iban" => Some(CsvRule::Iban),
+      _ => None
+    },
+    _ => None
+  }
+}
+
+// The rule for `matching(notEmpty, 'example')` and `matching(empty)`
+fn emptiness_rule(expression: &Expression) -> Option<CsvRule> {
+  match expression.args.first() {
+    Some(Arg::Positional(ArgValue::Identifier(rule))) if expression.name == "matching" => match rule.as_str() {
+      "notEmpty" => Some(CsvRule::NotEmpty),
+      "empty" => Some(CsvRule::Empty),
+      _ => None
+    },
+    _ => None
+  }
+}
+
+// Applies the expression if it is one provided by this plugin, returning false if it is a Pact
+// expression
+fn apply_plugin_expression(expression: &Expression, column: &mut ColumnDefinition) -> anyhow::Result<bool> {
+  if expression.name == "matching" && expression.has_named_args() {
+    match expression.args.first() {
+      Some(Arg::Positional(ArgValue::Identifier(rule))) if rule == "decimal" => {
+        let precision = expression.named_usize("precision")?;
+        let scale = expression.named_usize("scale")?;
+        if precision.is_none() && scale.is_none() {
+          return Err(anyhow!("'{}' is not valid, the decimal matcher requires a precision and/or scale", expression.source));
+        }
+        let example = expression.example().unwrap_or_else(|| match scale {
+          Some(scale) if scale > 0 => format!("0.{}", "0".repeat(scale)),
+          _ => "0".to_string()
+        });
+        column.set_example(example, ValueType::Decimal);
+        column.rules.push(CsvRule::DecimalPrecision { precision, scale });
+        Ok(true)
+      }
+      Some(Arg::Positional(ArgValue::Identifier(rule))) if rule == "datetime" => {
+        let mut strings = expression.args.iter().skip(1).filter_map(|arg| match arg {
+          Arg::Positional(ArgValue::String(s)) => Some(s.clone()),
+          _ => None
+        });
+        let format = strings.next()
+          .ok_or_else(|| anyhow!("'{}' is not valid, the datetime matcher requires a format", expression.source))?;
+        let tolerance = match expression.named("tolerance") {
+          Some(ArgValue::String(tolerance)) => parse_duration(tolerance)
+            .map_err(|err| anyhow!("'{}' is not valid, {}", expression.source, err))?,
+          _ => return Err(anyhow!("'{}' is not valid, the datetime matcher requires a tolerance (i.e. tolerance: '5s')", expression.source))
+        };
+        let example = match strings.next() {
+          Some(example) => example,
+          None => generate_string(&format)
+            .map_err(|err| anyhow!("'{}' is not valid, {}", expression.source, err))?
+        };
+        column.set_example(example, ValueType::String);
+        column.rules.push(CsvRule::DateTimeTolerance { format, tolerance });
+        Ok(true)
+      }
+      Some(Arg::Positional(ArgValue::Identifier(rule))) if rule == "rounded" => {
+        let scale = expression.named_usize("scale")?
+          .ok_or_else(|| anyhow!("'{}' is not valid, the rounded matcher requires a scale", expression.source))?;
+        let example = expression.example().unwrap_or_else(|| match scale {
+          0 => "0".to_string(),
+          _ => format!("0.{}", "0".repeat(scale))
+        });
+        column.set_example(example, ValueType::Decimal);
+        column.rules.push(CsvRule::Rounded { scale });
+        Ok(true)
+      }
+      _ => Err(anyhow!("'{}' is not a valid matching definition, named parameters are not supported for this matcher", expression.source))
+    }
+  } else if let Some(rule) = checksum_rule(expression) {
+    let default_example = if rule == CsvRule::Iban { "GB82WEST12345698765432" } else { "79927398713" };
+    let example = match expression.args.as_slice() {
+      [_] => default_example.to_string(),
+      [_, Arg::Positional(ArgValue::String(example))] => example.clone(),
+      _ => return Err(anyhow!("'{}' is not valid, expected an optional example (i.e. matching({}, '{}'))",
+        expression.source, rule.name(), default_example))
+    };
+    rule.match_value(&example, &example, RoundingMode::default())
+      .map_err(|err| anyhow!("'{}' is not valid, the example does not match - {}", expression.source, err))?;
+    column.set_example(example, ValueType::String);
+    column.rules.push(rule);
+    Ok(true)
+  } else if let Some(rule) = emptiness_rule(expression) {
+    let example = match (&rule, expression.args.as_slice()) {
+      (CsvRule::NotEmpty, [_, Arg::Positional(ArgValue::String(example))]) => example.clone(),
+      (CsvRule::NotEmpty, _) => return Err(anyhow!("'{}' is not valid, expected an example (i.e. matching(notEmpty, 'Alice'))",
+        expression.source)),
+      (_, [_]) => String::default(),
+      _ => return Err(anyhow!("'{}' is not valid, the empty matcher does not take any parameters", expression.source))
+    };
+    rule.match_value(&example, &example, RoundingMode::default())
+      .map_err(|err| anyhow!("'{}' is not valid, the example does not match - {}", expression.source, err))?;
+    column.set_example(example, ValueType::String);
+    column.rules.push(rule);
+    Ok(true)
+  } else if expression.name == "decimal" {
+    let (places, example) = match expression.args.as_slice() {
+      [Arg::Positional(ArgValue::Number(places))] => (places, None),
+      [Arg::Positional(ArgValue::Number(places)), Arg::Positional(ArgValue::String(example))] => (places, Some(example.clone())),
+      _ => return Err(anyhow!("'{}' is not valid, expected the number of decimal places and an optional example \
+        (i.e. decimal(2, '12.50'))", expression.source))
+    };
+    let places = places.parse::<usize>()
+      .map_err(|_| anyhow!("'{}' is not valid, the decimal places must be a positive integer, got {}", expression.source, places))?;
+    let rule = CsvRule::DecimalPlaces { places };
+    let example = example.unwrap_or_else(|| match places {
+      0 => "0".to_string(),
+      _ => format!("0.{}", "0".repeat(places))
+    });
+    rule.match_value(&example, &example, RoundingMode::default())
+      .map_err(|err| anyhow!("'{}' is not valid, the example does not match - {}", expression.source, err))?;
+    column.set_example(example, ValueType::Decimal);
+    column.rules.push(rule);
+    Ok(true)
+  } else if expression.name == "oneOf" {
+    let values = expression.args.iter()
+      .map(|arg| match arg {
+        Arg::Positional(ArgValue::String(value)) => Some(value.clone()),
+        _ => None
+      })
+      .collect::<Option<Vec<_>>>()
+      .filter(|values| !values.is_empty())
+      .ok_or_else(|| anyhow!("'{}' is not valid, expected the allowed values (i.e. oneOf('OPEN', 'CLOSED'))", expression.source))?;
+    column.set_example(values[0].clone(), ValueType::String);
+    column.rules.push(CsvRule::OneOf { values });
+    Ok(true)
+  } else if expression.name == "transform" {
+    let transform = match expression.args.as_slice() {
+      [Arg::Positional(ArgValue::Identifier(name))] => Transform::create(name, None),
+      [Arg::Positional(ArgValue::Identifier(name)), Arg::Positional(ArgValue::String(value))] =>
+        Transform::create(name, Some(value)),
+      _ => Err(anyhow!("expected a transform name and optional string value"))
+    }.map_err(|err| anyhow!("'{}' is not a valid transform definition, {}", expression.source, err))?;
+    column.transforms.push(transform);
+    Ok(true)
+  } else if expression.name == "optional" {
+    if !expression.args.is_empty() {
+      return Err(anyhow!("'{}' is not valid, optional does not take any parameters", expression.source));
+    }
+    column.optional = true;
+    Ok(true)
+  } else if expression.name == "list" {
+    let delimiter = match expression.named("subDelimiter") {
+      Some(ArgValue::String(delimiter)) if !delimiter.is_empty() => delimiter.clone(),
+      _ => return Err(anyhow!("'{}' is not valid, the list requires a subDelimiter (i.e. subDelimiter: ';')", expression.source))
+    };
+    let count = expression.named_usize("count")?;
+    let (each, element_example) = match expression.named("each") {
+      Some(ArgValue::Expression(each)) => {
+        let definition = parse_matcher_def(&each.source)
+          .map_err(|err| anyhow!("'{}' is not valid, {}", expression.source, err))?;
+        let rules = definition.rules.iter()
+          .map(|rule| match rule {
+            Either::Left(rule) => Ok(rule.clone()),
+            Either::Right(reference) => Err(anyhow!("'{}' is not valid, references are not supported for list elements, got {:?}", expression.source, reference))
+          })
+          .collect::<anyhow::Result<Vec<_>>>()?;
+        (rules, definition.value)
+      }
+      Some(value) => return Err(anyhow!("'{}' is not valid, each must be a matching definition, got {:?}", expression.source, value)),
+      None => (vec![], String::default())
+    };
+    let example = expression.args.iter().find_map(|arg| match arg {
+      Arg::Positional(ArgValue::String(s)) => Some(s.clone()),
+      _ => None
+    }).unwrap_or_else(|| vec![element_example; count.unwrap_or(1)].join(&delimiter));
+    column.set_example(example, ValueType::String);
+    column.rules.push(CsvRule::List { delimiter, each, count });
+    Ok(true)
+  } else if expression.name == "text" {
+    if !expression.args.is_empty() {
+      return Err(anyhow!("'{}' is not valid, text does not take any parameters", expression.source));
+    }
+    column.text = true;
+    Ok(true)
+  } else if expression.name == "multiset" {
+    if !expression.args.is_empty() {
+      return Err(anyhow!("'{}' is not valid, multiset does not take any parameters", expression.source));
+    }
+    column.multiset = true;
+    Ok(true)
+  } else if expression.name == "homogeneous" {
+    if !expression.args.is_empty() {
+      return Err(anyhow!("'{}' is not valid, homogeneous does not take any parameters", expression.source));
+    }
+    column.homogeneous = true;
+    Ok(true)
+  } else if expression.name == "eachValue" {
+    // The wrapped definition is parsed as a column definition, so it can use the plugin matchers
+    let each = match expression.args.as_slice() {
+      [Arg::Positional(ArgValue::Expression(each))] => parse_column_definition(&each.source)
+        .map_err(|err| anyhow!("'{}' is not valid, {}", expression.source, err))?,
+      _ => return Err(anyhow!("'{}' is not valid, expected a matching definition (i.e. eachValue(matching(integer, 100)))",
+        expression.source))
+    };
+    if each.definition.rules.is_empty() && each.rules.is_empty() {
+      return Err(anyhow!("'{}' is not valid, the definition does not have any matching rules", expression.source));
+    }
+    if each.generator.is_some() && column.generator.is_some() {
+      return Err(anyhow!("'{}' is not valid, only one generator can be defined for a column", expression.source));
+    }
+    column.definition = column.definition.merge(&each.definition);
+    column.rules.extend(each.rules);
+    column.transforms.extend(each.transforms);
+    column.generator = column.generator.take().or(each.generator);
+    column.optional = column.optional || each.optional;
+    column.text = column.text || each.text;
+    column.multiset = column.multiset || each.multiset;
+    column.homogeneous = column.homogeneous || each.homogeneous;
+    column.constraints.extend(each.constraints);
+    column.each_value = true;
+    Ok(true)
+  } else if expression.name == "distinctFrom" || expression.name == "disjointFrom" {
+    let other = match expression.args.as_slice() {
+      [Arg::Positional(ArgValue::String(other))] => parse_field(other)
+        .map_err(|err| anyhow!("'{}' is not valid, {}", expression.source, err))?,
+      _ => return Err(anyhow!("'{}' is not valid, expected the other column (i.e. {}('column:name'))",
+        expression.source, expression.name))
+    };
+    column.constraints.push(if expression.name == "distinctFrom" {
+      ColumnConstraint::DistinctFrom(other)
+    } else {
+      ColumnConstraint::DisjointFrom(other)
+    });
+    Ok(true)
+  } else if expression.name == "generate" {
+    match expression.args.first() {
+      Some(Arg::Positional(ArgValue::Identifier(generator))) if generator == "sequence" => {
+        let start = expression.named_i64("start")?.unwrap_or(1);
+        let step = expression.named_i64("step")?.unwrap_or(1);
+        if column.generator.is_some() {
+          return Err(anyhow!("'{}' is not valid, only one generator can be defined for a column", expression.source));
+        }
+        column.set_example(start.to_string(), ValueType::Integer);
+        column.generator = Some(CsvGenerator::Sequence { start, step });
+        Ok(true)
+      }
+      Some(Arg::Positional(ArgValue::Identifier(generator))) if generator == "rowIndex" => {
+        let base = expression.named_i64("base")?.unwrap_or(1);
+        if column.generator.is_some() {
+          return Err(anyhow!("'{}' is not valid, only one generator can be defined for a column", expression.source));
+        }
+        let generator = CsvGenerator::row_index(base)
+          .map_err(|err| anyhow!("'{}' is not valid, {}", expression.source, err))?;
+        column.set_example(base.to_string(), ValueType::Integer);
+        column.generator = Some(generator);
+        Ok(true)
+      }
+      Some(Arg::Positional(ArgValue::Identifier(generator))) if generator == "weighted" => {
+        let choices = expression.args.iter().skip(1).map(|arg| match arg {
+          Arg::Named(value, ArgValue::Number(weight)) => weight.parse::<u32>()
+            .map(|weight| (value.clone(), weight))
+            .map_err(|_| anyhow!("'{}' is not valid, the weight for '{}' must be a non-negative integer, got '{}'",
+              expression.source, value, weight)),
+          _ => Err(anyhow!("'{}' is not valid, expected each weighted value to be in the form 'value': weight",
+            expression.source))
+        }).collect::<anyhow::Result<Vec<_>>>()?;
+        if column.generator.is_some() {
+          return Err(anyhow!("'{}' is not valid, only one generator can be defined for a column", expression.source));
+        }
+        let generator = CsvGenerator::weighted(choices)
+          .map_err(|err| anyhow!("'{}' is not valid, {}", expression.source, err))?;
+        if let CsvGenerator::Weighted { choices } = &generator {
+          column.set_example(choices[0].0.clone(), ValueType::String);
+        }
+        column.generator = Some(generator);
+        Ok(true)
+      }
+      _ => Err(anyhow!("'{}' is not a valid generator definition, expected generate(sequence, start: 1, step: 1), generate(rowIndex, base: 0) or generate(weighted, 'value': weight, ...)", expression.source))
+    }
+  } else {
+    Ok(false)
+  }
+}
+
+impl ColumnDefinition {
+  fn set_example(&mut self, example: String, value_type: ValueType) {
+    if self.definition.value.is_empty() {
+      self.definition.value = example;
+      self.definition.value_type = value_type;
+    }
+  }
+}
+
+/// Expression in the form `name(arg, name: arg, ...)`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Expression {
+  pub name: String,
+  pub args: Vec<Arg>,
+  /// Source text of the expression
+  pub source: String
+}
+
+impl Expression {
+  fn has_named_args(&self) -> bool {
+    self.args.iter().any(|arg| matches!(arg, Arg::Named(_, _)))
+  }
+
+  fn named(&self, name: &str) -> Option<&ArgValue> {
+    self.args.iter().find_map(|arg| match arg {
+      Arg::Named(key, value) if key == name => Some(value),
+      _ => None
+    })
+  }
+
+  fn named_usize(&self, name: &str) -> anyhow::Result<Option<usize>> {
+    match self.named(name) {
+      Some(ArgValue::Number(n)) => n.parse::<usize>().map(Some)
+        .map_err(|_| anyhow!("'{}' is not valid, {} must be a positive integer, got {}", self.source, name, n)),
+      Some(value) => Err(anyhow!("'{}' is not valid, {} must be a positive integer, got {:?}", self.source, name, value)),
+      None => Ok(None)
+    }
+  }
+
+  fn named_i64(&self, name: &str) -> anyhow::Result<Option<i64>> {
+    match self.named(name) {
+      Some(ArgValue::Number(n)) => n.parse::<i64>().map(Some)
+        .map_err(|_| anyhow!("'{}' is not valid, {} must be an integer, got {}", self.source, name, n)),
+      Some(value) => Err(anyhow!("'{}' is not valid, {} must be an integer, got {:?}", self.source, name, value)),
+      None => Ok(None)
+    }
+  }
+
+  /// The example value, which is a positional string or number value after the first argument
+  fn example(&self) -> Option<String> {
+    self.args.iter().skip(1).find_map(|arg| match arg {
+      Arg::Positional(ArgValue::String(s)) => Some(s.clone()),
+      Arg::Positional(ArgValue::Number(n)) => Some(n.clone()),
+      _ => None
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Arg {
+  Positional(ArgValue),
+  Named(String, ArgValue)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ArgValue {
+  String(String),
+  Number(String),
+  Identifier(String),
+  Reference(String),
+  Expression(Expression)
+}
+
+// expressions -> expression ( "," expression )*
+pub(crate) fn parse_expressions(s: &str) -> anyhow::Result<Vec<Expression>> {
+  let mut parser = ExpressionParser { input: s, pos: 0 };
+  let mut expressions = vec![parser.expression()?];
+  while parser.consume(',') {
+    expressions.push(parser.expression()?);
+  }
+  parser.skip_whitespace();
+  if parser.pos < s.len() {
+    Err(anyhow!("'{}' is not a valid expression, unexpected '{}'", s, &s[parser.pos..]))
+  } else {
+    Ok(expressions)
+  }
+}
+
+struct ExpressionParser<'a> {
+  input: &'a str,
+  pos: usize
+}
+
+impl<'a> ExpressionParser<'a> {
+  fn skip_whitespace(&mut self) {
+    let remainder = &self.input[self.pos..];
+    self.pos += remainder.len() - remainder.trim_start().len();
+  }
+
+  fn peek(&mut self) -> Option<char> {
+    self.skip_whitespace();
+    self.input[self.pos..].chars().next()
+  }
+
+  fn consume(&mut self, ch: char) -> bool {
+    if self.peek() == Some(ch) {
+      self.pos += ch.len_utf8();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, ch: char) -> anyhow::Result<()> {
+    if self.consume(ch) {
+      Ok(())
+    } else {
+      Err(anyhow!("'{}' is not a valid expression, expected '{}' at position {}", self.input, ch, self.pos))
+    }
+  }
+
+  fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+    let start = self.pos;
+    let remainder = &self.input[start..];
+    let len = remainder.find(|ch: char| !f(ch)).unwrap_or(remainder.len());
+    self.pos += len;
+    &self.input[start..start + len]
+  }
+
+  // expression -> identifier "(" ( arg ( "," arg )* )? ")"
+  fn expression(&mut self) -> anyhow::Result<Expression> {
+    self.skip_whitespace();
+    let start = self.pos;
+    let name = self.identifier()?;
+    self.expect('(')?;
+    let mut args = vec![];
+    if !self.consume(')') {
+      args.push(self.arg()?);
+      while self.consume(',') {
+        args.push(self.arg()?);
+      }
+      self.expect(')')?;
+    }
+    Ok(Expression {
+      name,
+      args,
+      source: self.input[start..self.pos].to_string()
+    })
+  }
+
+  fn identifier(&mut self) -> anyhow::Result<String> {
+    self.skip_whitespace();
+    let identifier = self.take_while(|ch| ch.is_alphanumeric() || ch == '_');
+    if identifier.is_empty() {
+      Err(anyhow!("'{}' is not a valid expression, expected a name at position {}", self.input, self.pos))
+    } else {
+      Ok(identifier.to_string())
+    }
+  }
+
+  // arg -> ( ( identifier | string ) ":" )? value
+  fn arg(&mut self) -> anyhow::Result<Arg> {
+    let value = self.value()?;
+    if self.consume(':') {
+      let key = match value {
+        ArgValue::Identifier(key) | ArgValue::String(key) => key,
+        _ => return Err(anyhow!("'{}' is not a valid expression, expected a name before ':'", self.input))
+      };
+      Ok(Arg::Named(key, self.value()?))
+    } else {
+      Ok(Arg::Positional(value))
+    }
+  }
+
+  // value -> string | number | "$" string | identifier | expression
+  fn value(&mut self) -> anyhow::Result<ArgValue> {
+    match self.peek() {
+      Some('\'') => self.string().map(ArgValue::String),
+      Some('$') => {
+        self.pos += 1;
+        self.string().map(ArgValue::Reference)
+      }
+      Some(ch) if ch.is_ascii_digit() || ch == '-' || ch == '.' => {
+        let number = self.take_while(|ch| ch.is_ascii_digit() || ch == '-' || ch == '.');
+        Ok(ArgValue::Number(number.to_string()))
+      }
+      Some(_) => {
+        let start = self.pos;
+        let identifier = self.identifier()?;
+        if self.peek() == Some('(') {
+          self.pos = start;
+          self.expression().map(ArgValue::Expression)
+        } else {
+          Ok(ArgValue::Identifier(identifier))
+        }
+      }
+      None => Err(anyhow!("'{}' is not a valid expression, expected a value but got the end of the expression", self.input))
+    }
+  }
+
+  // string -> "'" [^']* "'"
+  fn string(&mut self) -> anyhow::Result<String> {
+    self.expect('\'')?;
+    let value = self.take_while(|ch| ch != '\'').to_string();
+    self.expect('\'')?;
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use maplit::hashmap;
+  use pact_models::matchingrules::MatchingRule;
+  use pact_models::matchingrules::expressions::ValueType;
+
+  use crate::constraints::ColumnConstraint;
+  use crate::generators::CsvGenerator;
+  use crate::matchers::CsvRule;
+  use crate::transforms::Transform;
+
+  use super::*;
+
+  #[test]
+  fn parse_expressions_test() {
+    let expressions = parse_expressions("matching(regex, '\\w+', 'Fred'), atLeast(1)").unwrap();
+    expect!(expressions.len()).to(be_equal_to(2));
+    expect!(expressions[0].name.as_str()).to(be_equal_to("matching"));
+    expect!(expressions[0].source.as_str()).to(be_equal_to("matching(regex, '\\w+', 'Fred')"));
+    expect!(expressions[0].args.clone()).to(be_equal_to(vec![
+      Arg::Positional(ArgValue::Identifier("regex".to_string())),
+      Arg::Positional(ArgValue::String("\\w+".to_string())),
+      Arg::Positional(ArgValue::String("Fred".to_string()))
+    ]));
+    expect!(expressions[1].args.clone()).to(be_equal_to(vec![Arg::Positional(ArgValue::Number("1".to_string()))]));
+
+    let expressions = parse_expressions("matching(decimal, precision: 10, 'scale': 2)").unwrap();
+    expect!(expressions[0].args.clone()).to(be_equal_to(vec![
+      Arg::Positional(ArgValue::Identifier("decimal".to_string())),
+      Arg::Named("precision".to_string(), ArgValue::Number("10".to_string())),
+      Arg::Named("scale".to_string(), ArgValue::Number("2".to_string()))
+    ]));
+
+    expect!(parse_expressions("matching(type, 'Name'")).to(be_err());
+    expect!(parse_expressions("matching(type, 'Name') extra")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_pact_expressions() {
+    let column = parse_column_definition("matching(number, 100)").unwrap();
+    expect!(column.definition.value).to(be_equal_to("100"));
+    expect!(column.definition.rules).to(be_equal_to(vec![Either::Left(MatchingRule::Number)]));
+    expect!(column.rules).to(be_equal_to(vec![]));
+  }
+
+  #[test]
+  fn parse_column_definition_with_include() {
+    let column = parse_column_definition("matching(include, 'SPRING23')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("SPRING23"));
+    expect!(column.definition.rules).to(be_equal_to(vec![Either::Left(MatchingRule::Include("SPRING23".to_string()))]));
+    expect!(column.rules).to(be_equal_to(vec![]));
+  }
+
+  #[test]
+  fn parse_column_definition_with_decimal_precision() {
+    let column = parse_column_definition("matching(decimal, precision: 10, scale: 2)").unwrap();
+    expect!(column.definition.value).to(be_equal_to("0.00"));
+    expect!(column.definition.value_type).to(be_equal_to(ValueType::Decimal));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::DecimalPrecision { precision: Some(10), scale: Some(2) }]));
+
+    let column = parse_column_definition("matching(decimal, scale: 2, '12.50')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("12.50"));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::DecimalPrecision { precision: None, scale: Some(2) }]));
+
+    expect!(parse_column_definition("matching(decimal, precision: 'ten')")).to(be_err());
+    expect!(parse_column_definition("matching(type, precision: 10)")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_transforms() {
+    let column = parse_column_definition("transform(stripPrefix, 'ID-'), transform(upper), matching(type, 'ABC')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("ABC"));
+    expect!(column.transforms).to(be_equal_to(vec![Transform::StripPrefix("ID-".to_string()), Transform::Upper]));
+
+    expect!(parse_column_definition("transform(stripPrefix), matching(type, 'ABC')")).to(be_err());
+    expect!(parse_column_definition("transform(reverse), matching(type, 'ABC')")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_sequence_generator() {
+    let column = parse_column_definition("generate(sequence, start: 10, step: 5)").unwrap();
+    expect!(column.definition.value).to(be_equal_to("10"));
+    expect!(column.generator).to(be_some().value(CsvGenerator::Sequence { start: 10, step: 5 }));
+
+    let column = parse_column_definition("matching(integer, 100), generate(sequence)").unwrap();
+    expect!(column.definition.value).to(be_equal_to("100"));
+    expect!(column.generator).to(be_some().value(CsvGenerator::Sequence { start: 1, step: 1 }));
+
+    let column = parse_column_definition("generate(rowIndex, base: 0)").unwrap();
+    expect!(column.definition.value).to(be_equal_to("0"));
+    expect!(column.generator).to(be_some().value(CsvGenerator::Sequence { start: 0, step: 1 }));
+    expect!(parse_column_definition("generate(rowIndex)").unwrap().generator)
+      .to(be_some().value(CsvGenerator::Sequence { start: 1, step: 1 }));
+    expect!(parse_column_definition("generate(rowIndex, base: 2)")).to(be_err());
+
+    expect!(parse_column_definition("generate(sequence, start: 'a')")).to(be_err());
+    expect!(parse_column_definition("generate(random)")).to(be_err());
+  }
+
+  #[test]
+  fn validate_config_returns_all_the_errors() {
+    let fields = hashmap! {
+      "column:1".to_string() => "matching(integer, 100)".to_string(),
+      "column:name".to_string() => "matching(type, 'Fred')".to_string(),
+      "column:0".to_string() => "matching(type, 'x')".to_string(),
+      "column:id".to_string() => "generate(random)".to_string(),
+      "row:2:column:1".to_string() => "matching(integer, 1)".to_string(),
+      "row:x:column:1".to_string() => "matching(integer, 1)".to_string(),
+      "csvHeaders".to_string() => "false".to_string()
+    };
+    let errors = validate_config(&fields);
+    expect!(errors.iter().map(|err| err.key.as_str()).collect::<Vec<_>>())
+      .to(be_equal_to(vec!["column:0", "column:id", "row:x:column:1"]));
+    expect!(errors[1].to_string()).to(be_equal_to("column:id: 'generate(random)' is not a valid generator definition, \
+      expected generate(sequence, start: 1, step: 1), generate(rowIndex, base: 0) or generate(weighted, 'value': weight, ...)"));
+
+    expect!(validate_config(&hashmap! {
+      "column:1".to_string() => "matching(integer, 100)".to_string()
+    })).to(be_equal_to(vec![]));
+    let errors = validate_config(&hashmap! {
+      "column:1".to_string() => "matching($'items')".to_string(),
+      "row:1:column:1".to_string() => "matching($'items')".to_string()
+    });
+    expect!(errors.iter().map(|err| err.key.as_str()).collect::<Vec<_>>()).to(be_equal_to(vec!["row:1:column:1"]));
+  }
+
+  #[test]
+  fn parse_weighted_generator() {
+    let column = parse_column_definition("generate(weighted, 'A': 70, 'B': 30)").unwrap();
+    expect!(column.generator).to(be_some().value(CsvGenerator::Weighted {
+      choices: vec![("A".to_string(), 70), ("B".to_string(), 30)]
+    }));
+    expect!(column.definition.value).to(be_equal_to("A"));
+
+    expect!(parse_column_definition("generate(weighted)")).to(be_err());
+    expect!(parse_column_definition("generate(weighted, 'A': 0)")).to(be_err());
+    expect!(parse_column_definition("generate(weighted, 'A': -1)")).to(be_err());
+    expect!(parse_column_definition("generate(weighted, 'A': 0.5)")).to(be_err());
+    expect!(parse_column_definition("generate(weighted, 'A': 'x')")).to(be_err());
+    expect!(parse_column_definition("generate(weighted, 'A')")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_datetime_tolerance() {
+    let column = parse_column_definition("matching(datetime, 'yyyy-MM-dd HH:mm:ss', '2023-01-01 10:00:00', tolerance: '5s')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("2023-01-01 10:00:00"));
+    expect!(column.rules).to(be_equal_to(vec![
+      CsvRule::DateTimeTolerance { format: "yyyy-MM-dd HH:mm:ss".to_string(), tolerance: 5000 }
+    ]));
+
+    expect!(parse_column_definition("matching(datetime, 'yyyy-MM-dd', tolerance: '5x')")).to(be_err());
+    expect!(parse_column_definition("matching(datetime, tolerance: '5s')")).to(be_err());
+  }
+
+  #[test]
+  fn parse_field_test() {
+    expect!(parse_field("column:1").unwrap()).to(be_equal_to(Left(1)));
+    expect!(parse_field("column:email").unwrap()).to(be_equal_to(Right("email".to_string())));
+    expect!(parse_field("column:total_amount").unwrap()).to(be_equal_to(Right("total_amount".to_string())));
+    expect!(parse_field("column: First Name").unwrap()).to(be_equal_to(Right("First Name".to_string())));
+    expect!(parse_field("column:2nd").unwrap()).to(be_equal_to(Right("2nd".to_string())));
+    expect!(parse_field("column:0")).to(be_err());
+    expect!(parse_field("column:")).to(be_err());
+    expect!(parse_field("row:1")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_constraints() {
+    let column = parse_column_definition("distinctFrom('column:sourceId'), disjointFrom('column:2'), matching(type, 'x')").unwrap();
+    expect!(column.constraints).to(be_equal_to(vec![
+      ColumnConstraint::DistinctFrom(Either::Right("sourceId".to_string())),
+      ColumnConstraint::DisjointFrom(Either::Left(2))
+    ]));
+    expect!(column.definition.value).to(be_equal_to("x"));
+
+    expect!(parse_column_definition("distinctFrom()")).to(be_err());
+    expect!(parse_column_definition("disjointFrom('other')")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_optional() {
+    let column = parse_column_definition("optional(), matching(type, 'x')").unwrap();
+    expect!(column.optional).to(be_true());
+    expect!(column.definition.value).to(be_equal_to("x"));
+    expect!(parse_column_definition("matching(type, 'x')").unwrap().optional).to(be_false());
+    expect!(parse_column_definition("optional(true)")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_text() {
+    let column = parse_column_definition("text(), matching(regex, '\\d+', '00123')").unwrap();
+    expect!(column.text).to(be_true());
+    expect!(column.definition.value).to(be_equal_to("00123"));
+    expect!(parse_column_definition("matching(type, '00123')").unwrap().text).to(be_false());
+    expect!(parse_column_definition("text(zip)")).to(be_err());
+    expect!(parse_column_definition("multiset(), matching(type, 'a')").unwrap().multiset).to(be_true());
+    expect!(parse_column_definition("homogeneous(), matching(type, 'a')").unwrap().homogeneous).to(be_true());
+    expect!(parse_column_definition("homogeneous(integer)")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_list() {
+    let column = parse_column_definition("list(subDelimiter: ';', each: matching(regex, '[a-z]+', 'a'), count: 3)").unwrap();
+    expect!(column.definition.value).to(be_equal_to("a;a;a"));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::List {
+      delimiter: ";".to_string(),
+      each: vec![MatchingRule::Regex("[a-z]+".to_string())],
+      count: Some(3)
+    }]));
+
+    let column = parse_column_definition("list(subDelimiter: '|', each: matching(integer, 1), 'x|y')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("x|y"));
+
+    expect!(parse_column_definition("list(each: matching(integer, 1))")).to(be_err());
+    expect!(parse_column_definition("list(subDelimiter: ';', each: 'a')")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_rounded() {
+    let column = parse_column_definition("matching(rounded, scale: 2, '2.35')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("2.35"));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::Rounded { scale: 2 }]));
+    expect!(parse_column_definition("matching(rounded, scale: 1)").unwrap().definition.value).to(be_equal_to("0.0"));
+    expect!(parse_column_definition("matching(rounded, precision: 1)")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_check_digits() {
+    let column = parse_column_definition("matching(luhn)").unwrap();
+    expect!(column.definition.value).to(be_equal_to("79927398713"));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::Luhn]));
+    let column = parse_column_definition("matching(iban, 'DE89 3704 0044 0532 0130 00')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("DE89 3704 0044 0532 0130 00"));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::Iban]));
+    expect!(parse_column_definition("matching(luhn, '79927398710')")).to(be_err());
+    expect!(parse_column_definition("matching(iban, 'GB82', 'x')")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_decimal_places() {
+    let column = parse_column_definition("decimal(2)").unwrap();
+    expect!(column.definition.value).to(be_equal_to("0.00"));
+    expect!(column.definition.value_type).to(be_equal_to(ValueType::Decimal));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::DecimalPlaces { places: 2 }]));
+
+    let column = parse_column_definition("decimal(2, '12.50')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("12.50"));
+    expect!(parse_column_definition("decimal(0)").unwrap().definition.value).to(be_equal_to("0"));
+
+    expect!(parse_column_definition("decimal(2, '12.5')")).to(be_err());
+    expect!(parse_column_definition("decimal(-1)")).to(be_err());
+    expect!(parse_column_definition("decimal()")).to(be_err());
+    expect!(parse_column_definition("decimal('2')")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_not_empty_and_empty() {
+    let column = parse_column_definition("matching(notEmpty, 'Alice')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("Alice"));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::NotEmpty]));
+    expect!(parse_column_definition("matching(notEmpty)")).to(be_err());
+    expect!(parse_column_definition("matching(notEmpty, ' ')")).to(be_err());
+
+    let column = parse_column_definition("matching(empty)").unwrap();
+    expect!(column.definition.value).to(be_equal_to(""));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::Empty]));
+    expect!(parse_column_definition("matching(empty, 'x')")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_one_of() {
+    let column = parse_column_definition("oneOf('OPEN', 'CLOSED', 'PENDING')").unwrap();
+    expect!(column.definition.value).to(be_equal_to("OPEN"));
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::OneOf {
+      values: vec!["OPEN".to_string(), "CLOSED".to_string(), "PENDING".to_string()]
+    }]));
+    expect!(parse_column_definition("oneOf()")).to(be_err());
+    expect!(parse_column_definition("oneOf('OPEN', count: 1)")).to(be_err());
+  }
+
+  #[test]
+  fn parse_column_definition_with_each_value() {
+    let column = parse_column_definition("eachValue(matching(regex, '^[A-Z]{3}$', 'ABC'))").unwrap();
+    expect!(column.each_value).to(be_true());
+    expect!(column.definition.value).to(be_equal_to("ABC"));
+    expect!(column.definition.rules).to(be_equal_to(vec![Either::Left(MatchingRule::Regex("^[A-Z]{3}$".to_string()))]));
+    let column = parse_column_definition("eachValue(matching(luhn)), transform(trim)").unwrap();
+    expect!(column.rules).to(be_equal_to(vec![CsvRule::Luhn]));
+    expect!(column.transforms).to(be_equal_to(vec![Transform::Trim]));
+    expect!(parse_column_definition("eachValue('ABC')")).to(be_err());
+    expect!(parse_column_definition("eachValue(optional())")).to(be_err());
+  }
+}
@@ -0,0 +1,111 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context};
+use log::debug;
+use serde_json::Value;
+
+use crate::utils::to_value;
+
+/// Environment variable with the directory to load the configuration profiles from
+pub const PROFILE_DIR_ENV: &str = "PACT_CSV_PROFILE_DIR";
+
+/// Key in the contents config with the name of the profile to use
+pub const PROFILE_KEY: &str = "profile";
+
+/// Loads named configuration profiles from a directory. A profile is a JSON file (`<name>.json`)
+/// containing an object with the same entries as the contents config (column definitions and
+/// options). Profiles are cached once they have been loaded.
+#[derive(Debug, Default)]
+pub struct ProfileLoader {
+  profiles: Mutex<HashMap<PathBuf, BTreeMap<String, prost_types::Value>>>
+}
+
+impl ProfileLoader {
+  /// If the contents config has a `profile` entry, returns the entries from the profile merged
+  /// with the contents config. The entries in the contents config override the ones in the
+  /// profile.
+  pub fn apply_profile(
+    &self,
+    fields: &BTreeMap<String, prost_types::Value>
+  ) -> anyhow::Result<BTreeMap<String, prost_types::Value>> {
+    match fields.get(PROFILE_KEY).and_then(|value| value.kind.as_ref()) {
+      Some(prost_types::value::Kind::StringValue(name)) => {
+        let dir = env::var(PROFILE_DIR_ENV)
+          .map_err(|_| anyhow!("Profile '{}' was requested, but the {} environment variable is not set", name, PROFILE_DIR_ENV))?;
+        let mut merged = self.load_profile(Path::new(&dir), name)?;
+        merged.extend(fields.iter()
+          .filter(|(key, _)| key.as_str() != PROFILE_KEY)
+          .map(|(key, value)| (key.clone(), value.clone())));
+        Ok(merged)
+      }
+      Some(_) => Err(anyhow!("The profile must be the name of a profile in the {} directory", PROFILE_DIR_ENV)),
+      None => Ok(fields.clone())
+    }
+  }
+
+  fn load_profile(&self, dir: &Path, name: &str) -> anyhow::Result<BTreeMap<String, prost_types::Value>> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+      return Err(anyhow!("'{}' is not a valid profile name", name));
+    }
+
+    let path = dir.join(format!("{}.json", name));
+    let mut profiles = self.profiles.lock().map_err(|_| anyhow!("Profile cache lock is poisoned"))?;
+    if let Some(profile) = profiles.get(&path) {
+      return Ok(profile.clone());
+    }
+
+    if !path.exists() {
+      return Err(anyhow!("Unknown profile '{}', there is no {} file in {}", name, path.display(), dir.display()));
+    }
+    debug!("Loading profile '{}' from {}", name, path.display());
+    let contents = fs::read_to_string(&path)
+      .with_context(|| format!("Failed to read profile '{}' from {}", name, path.display()))?;
+    let profile = match serde_json::from_str::<Value>(&contents)
+      .with_context(|| format!("Profile '{}' is not valid JSON", name))? {
+      Value::Object(entries) => entries.iter()
+        .map(|(key, value)| (key.clone(), to_value(value)))
+        .collect::<BTreeMap<_, _>>(),
+      _ => return Err(anyhow!("Profile '{}' must be a JSON object", name))
+    };
+    profiles.insert(path, profile.clone());
+    Ok(profile)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use expectest::prelude::*;
+  use maplit::btreemap;
+
+  use crate::utils::to_value;
+
+  use super::ProfileLoader;
+
+  #[test]
+  fn load_profile_test() {
+    let dir = std::env::temp_dir().join(format!("pact-csv-profiles-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("orders.json"), r#"{"csvHeaders": false, "column:1": "matching(type, 'a')"}"#).unwrap();
+
+    let loader = ProfileLoader::default();
+    let profile = loader.load_profile(&dir, "orders").unwrap();
+    expect!(profile).to(be_equal_to(btreemap! {
+      "csvHeaders".to_string() => to_value(&serde_json::json!(false)),
+      "column:1".to_string() => to_value(&serde_json::json!("matching(type, 'a')"))
+    }));
+
+    // Loaded profiles are cached
+    fs::remove_file(dir.join("orders.json")).unwrap();
+    expect!(loader.load_profile(&dir, "orders")).to(be_ok());
+
+    expect!(loader.load_profile(&dir, "other")).to(be_err());
+    expect!(loader.load_profile(&dir, "../orders")).to(be_err());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -1,3 +1,5 @@
+use anyhow::anyhow;
+use pact_models::matchingrules::{MatchingRule as PactMatchingRule, RuleList, RuleLogic};
 use prost_types::value::Kind;
 use serde_json::{json, Value};
 
@@ -22,3 +24,67 @@ pub fn to_value(v: &prost_types::Value) -> Value {
     None => Value::Null
   }
 }
+
+/// Converts the matching rules received from the driver into a Pact rule list, where all the rules
+/// must match. Returns an error listing all the rules that can not be constructed (i.e. an unknown
+/// type).
+pub fn to_rule_list(rules: &MatchingRules) -> anyhow::Result<RuleList> {
+  let mut list = RuleList::empty(RuleLogic::And);
+  let mut errors = vec![];
+  for rule in &rules.rule {
+    let mut map = match rule.values.as_ref().map(to_object) {
+      Some(Value::Object(map)) => map,
+      _ => serde_json::Map::new()
+    };
+    map.insert("match".to_string(), Value::String(rule.r#type.clone()));
+    match PactMatchingRule::from_json(&Value::Object(map)) {
+      Ok(matching_rule) => list.add_rule(&matching_rule),
+      Err(err) => errors.push(format!("'{}' is not a valid matching rule: {}", rule.r#type, err))
+    }
+  }
+  if errors.is_empty() {
+    Ok(list)
+  } else {
+    Err(anyhow!(errors.join("; ")))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use pact_models::matchingrules::MatchingRule as PactMatchingRule;
+  use serde_json::json;
+
+  use crate::utils::to_value;
+
+  use super::{MatchingRule, MatchingRules, to_rule_list};
+
+  fn rule(r#type: &str, values: serde_json::Value) -> MatchingRule {
+    MatchingRule {
+      r#type: r#type.to_string(),
+      values: Some(prost_types::Struct {
+        fields: values.as_object().unwrap().iter().map(|(key, value)| (key.clone(), to_value(value))).collect()
+      })
+    }
+  }
+
+  #[test]
+  fn to_rule_list_test() {
+    let rules = MatchingRules {
+      rule: vec![rule("regex", json!({ "regex": "\\d+" })), rule("integer", json!({}))]
+    };
+    expect!(to_rule_list(&rules).unwrap().rules).to(be_equal_to(vec![
+      PactMatchingRule::Regex("\\d+".to_string()),
+      PactMatchingRule::Integer
+    ]));
+    expect!(to_rule_list(&MatchingRules { rule: vec![MatchingRule { r#type: "type".to_string(), values: None }] })
+      .unwrap().rules).to(be_equal_to(vec![PactMatchingRule::Type]));
+
+    expect!(to_rule_list(&MatchingRules { rule: vec![rule("unknown", json!({}))] })).to(be_err());
+    expect!(to_rule_list(&MatchingRules { rule: vec![rule("regex", json!({}))] })).to(be_err());
+    let err = to_rule_list(&MatchingRules { rule: vec![rule("unknown", json!({})), rule("integer", json!({})), rule("other", json!({}))] })
+      .unwrap_err().to_string();
+    expect!(err.starts_with("'unknown' is not a valid matching rule")).to(be_true());
+    expect!(err.contains("; 'other' is not a valid matching rule")).to(be_true());
+  }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use crate::categories::MismatchCategory;
+use crate::proto;
+
+/// Renders the compare contents response as a multi-line report suitable for printing to a log,
+/// with the mismatches grouped by column.
+pub fn render_report(response: &proto::CompareContentsResponse) -> String {
+  let mut report = String::new();
+
+  if !response.error.is_empty() {
+    report.push_str(format!("Comparison failed with an error: {}\n", response.error).as_str());
+  }
+
+  if let Some(type_mismatch) = &response.type_mismatch {
+    report.push_str(format!("Expected content type '{}', but got '{}'\n", type_mismatch.expected,
+      type_mismatch.actual).as_str());
+  }
+
+  let mut groups: BTreeMap<String, Vec<&proto::ContentMismatch>> = BTreeMap::new();
+  for mismatch in response.results.values().flat_map(|mismatches| mismatches.mismatches.iter()) {
+    groups.entry(mismatch_group(&mismatch.path)).or_default().push(mismatch);
+  }
+
+  let total: usize = groups.values().map(|mismatches| mismatches.len()).sum();
+  if total == 0 {
+    if report.is_empty() {
+      report.push_str("CSV contents matched\n");
+    }
+    return report;
+  }
+
+  report.push_str(format!("CSV contents had {} in {}\n", plural(total, "mismatch"),
+    plural(groups.len(), "group")).as_str());
+  for (group, mismatches) in &groups {
+    report.push_str(format!("\n{} ({}):\n", group, plural(mismatches.len(), "mismatch")).as_str());
+    for mismatch in mismatches {
+      if mismatch.path.is_empty() {
+        report.push_str(format!("  {}\n", mismatch.mismatch).as_str());
+      } else {
+        report.push_str(format!("  [{}] {}\n", mismatch.path.split_whitespace().join(" "), mismatch.mismatch).as_str());
+      }
+      for line in mismatch.diff.lines() {
+        report.push_str(format!("      {}\n", line).as_str());
+      }
+    }
+  }
+
+  report
+}
+
+/// Diff for a cell of the actual content, in the form `- expected` and `+ actual`. Line endings in
+/// the values (from quoted fields) are escaped, so each value stays on a single line of the diff.
+pub fn cell_diff(expected: &str, actual: &str) -> String {
+  format!("- {}\n+ {}", escape_diff_value(expected), escape_diff_value(actual))
+}
+
+/// Diff for contents with a different number of rows, with the header lines of a unified diff
+pub fn row_count_diff(expected: usize, actual: usize) -> String {
+  format!("--- expected ({})\n+++ actual ({})", plural(expected, "row"), plural(actual, "row"))
+}
+
+fn escape_diff_value(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for ch in value.chars() {
+    match ch {
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      '\r' => escaped.push_str("\\r"),
+      _ => escaped.push(ch)
+    }
+  }
+  escaped
+}
+
+/// Outcome of checking a value against a rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleOutcome {
+  /// Description of the rule
+  pub rule: String,
+  /// Mismatch message if the value did not match
+  pub result: Result<(), String>,
+  /// Category of the mismatch if the value did not match
+  pub category: MismatchCategory
+}
+
+/// Rule that was applied to a cell of the actual content when explaining a comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellDecision {
+  /// Line of the row in the actual content
+  pub row: u64,
+  /// Index of the column (starting at 0)
+  pub column: usize,
+  /// Expected value the rule was applied with
+  pub expected: String,
+  /// Actual value (after any transforms or normalization)
+  pub actual: String,
+  /// Description of the rule
+  pub rule: String,
+  /// Mismatch message if the value did not match
+  pub result: Result<(), String>
+}
+
+/// Renders the rules that were applied to each cell and their outcome, one line per rule
+pub fn render_explanation(decisions: &[CellDecision]) -> String {
+  let mut explanation = String::new();
+  for decision in decisions {
+    let outcome = match &decision.result {
+      Ok(()) => "matched".to_string(),
+      Err(mismatch) => format!("failed - {}", mismatch)
+    };
+    explanation.push_str(format!("  [row: {}, column: {}] '{}' (expected '{}') using {}: {}\n", decision.row,
+      decision.column, decision.actual, decision.expected, decision.rule, outcome).as_str());
+  }
+  if explanation.is_empty() {
+    explanation.push_str("  No cells were compared\n");
+  }
+  explanation
+}
+
+// Mismatches are grouped by the column in their path, otherwise they apply to the whole content
+fn mismatch_group(path: &str) -> String {
+  path.split(',')
+    .map(|part| part.trim())
+    .find_map(|part| part.strip_prefix("column:"))
+    .map(|column| format!("column {}", column.trim()))
+    .unwrap_or_else(|| "content".to_string())
+}
+
+fn plural(count: usize, noun: &str) -> String {
+  if count == 1 {
+    format!("{} {}", count, noun)
+  } else if noun.ends_with("ch") {
+    format!("{} {}es", count, noun)
+  } else {
+    format!("{} {}s", count, noun)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+  use maplit::hashmap;
+
+  use crate::proto;
+
+  use super::{CellDecision, cell_diff, render_explanation, render_report, row_count_diff};
+
+  fn mismatch(path: &str, message: &str) -> proto::ContentMismatch {
+    proto::ContentMismatch {
+      expected: None,
+      actual: None,
+      mismatch: message.to_string(),
+      path: path.to_string(),
+      diff: String::default()
+    }
+  }
+
+  #[test]
+  fn render_report_test() {
+    let response = proto::CompareContentsResponse {
+      error: String::default(),
+      type_mismatch: None,
+      results: hashmap! {
+        String::default() => proto::ContentMismatches {
+          mismatches: vec![
+            mismatch("", "Expected 3 columns, but got 2"),
+            mismatch("row:    2, column: 1", "Expected 'a' to equal 'b'"),
+            mismatch("row:    3, column: 1", "Expected 'c' to equal 'b'")
+          ]
+        }
+      }
+    };
+    expect!(render_report(&response)).to(be_equal_to(
+      "CSV contents had 3 mismatches in 2 groups\n\
+      \n\
+      column 1 (2 mismatches):\n  \
+        [row: 2, column: 1] Expected 'a' to equal 'b'\n  \
+        [row: 3, column: 1] Expected 'c' to equal 'b'\n\
+      \n\
+      content (1 mismatch):\n  \
+        Expected 3 columns, but got 2\n"));
+  }
+
+  #[test]
+  fn diff_test() {
+    expect!(cell_diff("1", "2")).to(be_equal_to("- 1\n+ 2"));
+    expect!(cell_diff("Zürich", "line 1\r\nline 2")).to(be_equal_to("- Zürich\n+ line 1\\r\\nline 2"));
+    expect!(cell_diff("a\\nb", "")).to(be_equal_to("- a\\\\nb\n+ "));
+    expect!(row_count_diff(3, 1)).to(be_equal_to("--- expected (3 rows)\n+++ actual (1 row)"));
+  }
+
+  #[test]
+  fn render_report_with_no_mismatches() {
+    expect!(render_report(&proto::CompareContentsResponse::default())).to(be_equal_to("CSV contents matched\n"));
+  }
+
+  #[test]
+  fn render_explanation_test() {
+    let decisions = vec![
+      CellDecision {
+        row: 2,
+        column: 0,
+        expected: "a".to_string(),
+        actual: "b".to_string(),
+        rule: "{\"match\":\"type\"}".to_string(),
+        result: Ok(())
+      },
+      CellDecision {
+        row: 2,
+        column: 1,
+        expected: "1".to_string(),
+        actual: "2".to_string(),
+        rule: "equality (the column has no matching rules)".to_string(),
+        result: Err("Expected column 1 value to equal '1', but got '2'".to_string())
+      }
+    ];
+    expect!(render_explanation(&decisions)).to(be_equal_to(
+      "  [row: 2, column: 0] 'b' (expected 'a') using {\"match\":\"type\"}: matched\n  \
+        [row: 2, column: 1] '2' (expected '1') using equality (the column has no matching rules): \
+        failed - Expected column 1 value to equal '1', but got '2'\n"));
+  }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use anyhow::anyhow;
+use csv::StringRecord;
+use either::Either;
+use pact_models::matchingrules::MatchingRule;
+use serde_json::{json, Value};
+
+use crate::options::ColumnOptions;
+use crate::parser::parse_field;
+
+/// Selects the actual rows a row override applies to
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowSelector {
+  /// The last row of the content
+  Last,
+  /// The data row with the given number (starting at 1)
+  Index(usize),
+  /// Rows where the column has the given value
+  Where(Either<usize, String>, String)
+}
+
+impl RowSelector {
+  /// Parses a row selector (`last`, a row number or `where(column:1='value')`)
+  pub fn parse(s: &str) -> anyhow::Result<RowSelector> {
+    let s = s.trim();
+    if s == "last" {
+      Ok(RowSelector::Last)
+    } else if let Ok(index) = s.parse::<usize>() {
+      if index < 1 {
+        Err(anyhow!("'{}' is not a valid row selector, row numbers start at 1", s))
+      } else {
+        Ok(RowSelector::Index(index))
+      }
+    } else if let Some(condition) = s.strip_prefix("where(").and_then(|condition| condition.strip_suffix(')')) {
+      let (column, value) = condition.split_once('=')
+        .ok_or_else(|| anyhow!("'{}' is not a valid row selector, expected where(column:1='value')", s))?;
+      let value = value.trim().strip_prefix('\'').and_then(|value| value.strip_suffix('\''))
+        .ok_or_else(|| anyhow!("'{}' is not a valid row selector, the value must be quoted", s))?;
+      Ok(RowSelector::Where(parse_field(column.trim())?, value.to_string()))
+    } else {
+      Err(anyhow!("'{}' is not a valid row selector, expected last, a row number or where(column:1='value')", s))
+    }
+  }
+
+  /// If the actual row with the given data row number (starting at 1) is selected
+  pub fn matches(
+    &self,
+    row: &StringRecord,
+    row_number: usize,
+    is_last: bool,
+    headers: &HashMap<&str, usize>
+  ) -> bool {
+    match self {
+      RowSelector::Last => is_last,
+      RowSelector::Index(index) => *index == row_number,
+      RowSelector::Where(column, value) => {
+        let actual = match column {
+          Either::Left(index) => row.get(index - 1),
+          Either::Right(header) => headers.get(header.as_str()).and_then(|index| row.get(*index))
+        };
+        actual == Some(value.as_str())
+      }
+    }
+  }
+}
+
+impl Display for RowSelector {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RowSelector::Last => write!(f, "last"),
+      RowSelector::Index(index) => write!(f, "{}", index),
+      RowSelector::Where(column, value) => match column {
+        Either::Left(index) => write!(f, "where(column:{}='{}')", index, value),
+        Either::Right(header) => write!(f, "where(column:{}='{}')", header, value)
+      }
+    }
+  }
+}
+
+/// Column definition that replaces the definition for the column in the selected rows. These are
+/// provided with keys in the form `row:<selector>:column:<column>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowOverride {
+  /// Rows the override applies to
+  pub selector: RowSelector,
+  /// Column path (i.e. `column:1` or `column:Name`)
+  pub column: String,
+  /// Expected value for the column in the selected rows
+  pub value: String,
+  /// Pact matching rules for the column in the selected rows
+  pub matching_rules: Vec<MatchingRule>,
+  /// Plugin matching rules and transforms for the column in the selected rows
+  pub options: ColumnOptions
+}
+
+impl RowOverride {
+  /// Splits a `row:<selector>:column:<column>` key into the row selector and column path
+  pub fn parse_key(key: &str) -> anyhow::Result<(RowSelector, String)> {
+    let selector_and_column = key.strip_prefix("row:")
+      .ok_or_else(|| anyhow!("'{}' is not a valid row key, expected row:<selector>:column:<column>", key))?;
+    let index = selector_and_column.rfind(":column:")
+      .ok_or_else(|| anyhow!("'{}' is not a valid row key, expected row:<selector>:column:<column>", key))?;
+    let selector = RowSelector::parse(&selector_and_column[..index])?;
+    let column = &selector_and_column[index + 1..];
+    let column = match parse_field(column)? {
+      Either::Left(index) => format!("column:{}", index),
+      Either::Right(header) => format!("column:{}", header)
+    };
+    Ok((selector, column))
+  }
+
+  /// If this override applies to the column with the given index (starting at 1) and header
+  pub fn applies_to(&self, index: usize, header: &str) -> bool {
+    self.column == format!("column:{}", index) || self.column == format!("column:{}", header)
+  }
+
+  /// Builds the override from its stored JSON form
+  pub fn from_json(json: &Value) -> anyhow::Result<RowOverride> {
+    let selector = json.get("selector").and_then(Value::as_str)
+      .ok_or_else(|| anyhow!("Row override JSON {} is missing the 'selector' attribute", json))?;
+    let column = json.get("column").and_then(Value::as_str)
+      .ok_or_else(|| anyhow!("Row override JSON {} is missing the 'column' attribute", json))?;
+    let matching_rules = match json.get("matchingRules") {
+      Some(Value::Array(rules)) => rules.iter().map(MatchingRule::from_json).collect::<anyhow::Result<Vec<_>>>()?,
+      _ => vec![]
+    };
+    Ok(RowOverride {
+      selector: RowSelector::parse(selector)?,
+      column: column.to_string(),
+      value: json.get("value").and_then(Value::as_str).unwrap_or_default().to_string(),
+      matching_rules,
+      options: ColumnOptions::from_json(json)?
+    })
+  }
+
+  /// Converts the override to the JSON form stored in the plugin configuration
+  pub fn to_json(&self) -> Value {
+    let mut json = self.options.to_json();
+    json["selector"] = json!(self.selector.to_string());
+    json["column"] = json!(self.column);
+    json["value"] = json!(self.value);
+    if !self.matching_rules.is_empty() {
+      json["matchingRules"] = Value::Array(self.matching_rules.iter().map(|rule| rule.to_json()).collect());
+    }
+    json
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use csv::StringRecord;
+  use either::Either;
+  use expectest::prelude::*;
+  use maplit::hashmap;
+
+  use super::{RowOverride, RowSelector};
+
+  #[test]
+  fn parse_key_test() {
+    expect!(RowOverride::parse_key("row:last:column:3").unwrap())
+      .to(be_equal_to((RowSelector::Last, "column:3".to_string())));
+    expect!(RowOverride::parse_key("row:2:column:Name").unwrap())
+      .to(be_equal_to((RowSelector::Index(2), "column:Name".to_string())));
+    expect!(RowOverride::parse_key("row:where(column:1='TOTAL'):column:3").unwrap())
+      .to(be_equal_to((RowSelector::Where(Either::Left(1), "TOTAL".to_string()), "column:3".to_string())));
+    expect!(RowOverride::parse_key("row:first:column:3")).to(be_err());
+    expect!(RowOverride::parse_key("row:last")).to(be_err());
+    expect!(RowOverride::parse_key("row:where(column:1=TOTAL):column:3")).to(be_err());
+  }
+
+  #[test]
+  fn row_selector_matches_test() {
+    let row = StringRecord::from(vec!["TOTAL", "", "100"]);
+    let headers = hashmap! { "type" => 0, "name" => 1, "amount" => 2 };
+    expect!(RowSelector::Last.matches(&row, 3, true, &headers)).to(be_true());
+    expect!(RowSelector::Last.matches(&row, 3, false, &headers)).to(be_false());
+    expect!(RowSelector::Index(3).matches(&row, 3, false, &headers)).to(be_true());
+    expect!(RowSelector::Where(Either::Left(1), "TOTAL".to_string()).matches(&row, 1, false, &headers)).to(be_true());
+    expect!(RowSelector::Where(Either::Right("type".to_string()), "TOTAL".to_string()).matches(&row, 1, false, &headers)).to(be_true());
+    expect!(RowSelector::Where(Either::Left(2), "TOTAL".to_string()).matches(&row, 1, false, &headers)).to(be_false());
+  }
+}
@@ -0,0 +1,71 @@
+use std::cmp::Reverse;
+
+use log::debug;
+
+/// Delimiters that are considered when detecting the delimiter
+const CANDIDATES: [u8; 3] = [b',', b';', b'\t'];
+
+/// Number of lines sampled when detecting the delimiter
+const SAMPLE_LINES: usize = 10;
+
+/// Detects the delimiter of the CSV data (quoted with the given quote character) by counting the candidate delimiters (comma, semicolon
+/// and tab) in each of the first few lines. The delimiter that occurs the same number of times on
+/// every sampled line is chosen, preferring the one that occurs most often. Falls back to a comma
+/// if no delimiter is consistent, or if the detection is ambiguous.
+pub fn sniff_delimiter(data: &[u8], quote: u8) -> u8 {
+  let lines = data.split(|b| *b == b'\n')
+    .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+    .filter(|line| !line.is_empty())
+    .take(SAMPLE_LINES)
+    .collect::<Vec<_>>();
+
+  let mut consistent = CANDIDATES.iter()
+    .filter_map(|candidate| {
+      let counts = lines.iter().map(|line| count_unquoted(line, *candidate, quote)).collect::<Vec<_>>();
+      match counts.first() {
+        Some(first) if *first > 0 && counts.iter().all(|count| count == first) => Some((*candidate, *first)),
+        _ => None
+      }
+    })
+    .collect::<Vec<_>>();
+  consistent.sort_by_key(|(_, count)| Reverse(*count));
+
+  let delimiter = match consistent.as_slice() {
+    [] => b',',
+    [(delimiter, _)] => *delimiter,
+    [(first, first_count), (_, second_count), ..] => if first_count > second_count { *first } else { b',' }
+  };
+  debug!("Detected delimiter {:?} from {} sampled lines", delimiter as char, lines.len());
+  delimiter
+}
+
+// Counts the occurrences of the delimiter that are not inside quoted fields
+fn count_unquoted(line: &[u8], delimiter: u8, quote: u8) -> usize {
+  let mut quoted = false;
+  let mut count = 0;
+  for b in line {
+    if *b == quote {
+      quoted = !quoted;
+    } else if *b == delimiter && !quoted {
+      count += 1;
+    }
+  }
+  count
+}
+
+#[cfg(test)]
+mod tests {
+  use expectest::prelude::*;
+
+  use super::sniff_delimiter;
+
+  #[test]
+  fn sniff_delimiter_test() {
+    expect!(sniff_delimiter(b"a,b,c\n1,2,3\n", b'"')).to(be_equal_to(b','));
+    expect!(sniff_delimiter(b"a;b;c\r\n1;2,5;3\r\n", b'"')).to(be_equal_to(b';'));
+    expect!(sniff_delimiter(b"a\tb\n1\t\"x;y\"\n", b'"')).to(be_equal_to(b'\t'));
+    expect!(sniff_delimiter(b"a;b,c\n1;2,3\n", b'"')).to(be_equal_to(b','));
+    expect!(sniff_delimiter(b"abc\n123\n", b'"')).to(be_equal_to(b','));
+    expect!(sniff_delimiter(b"", b'"')).to(be_equal_to(b','));
+  }
+}
@@ -0,0 +1,68 @@
+use anyhow::anyhow;
+use serde_json::{json, Value};
+
+/// Transformation applied to the actual value of a column before it is matched
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transform {
+  /// Convert to upper case
+  Upper,
+  /// Convert to lower case
+  Lower,
+  /// Remove leading and trailing whitespace
+  Trim,
+  /// Remove the prefix if the value starts with it
+  StripPrefix(String),
+  /// Remove the suffix if the value ends with it
+  StripSuffix(String)
+}
+
+impl Transform {
+  /// Creates the transform from its name and optional parameter
+  pub fn create(name: &str, value: Option<&str>) -> anyhow::Result<Transform> {
+    match (name, value) {
+      ("upper", None) => Ok(Transform::Upper),
+      ("lower", None) => Ok(Transform::Lower),
+      ("trim", None) => Ok(Transform::Trim),
+      ("stripPrefix", Some(prefix)) => Ok(Transform::StripPrefix(prefix.to_string())),
+      ("stripSuffix", Some(suffix)) => Ok(Transform::StripSuffix(suffix.to_string())),
+      ("stripPrefix", None) | ("stripSuffix", None) => Err(anyhow!("The {} transform requires a value", name)),
+      ("upper", Some(_)) | ("lower", Some(_)) | ("trim", Some(_)) => Err(anyhow!("The {} transform does not take a value", name)),
+      _ => Err(anyhow!("'{}' is not a known transform, expected one of upper, lower, trim, stripPrefix or stripSuffix", name))
+    }
+  }
+
+  /// Builds the transform from its stored JSON form
+  pub fn from_json(json: &Value) -> anyhow::Result<Transform> {
+    match json.get("transform").and_then(Value::as_str) {
+      Some(name) => Transform::create(name, json.get("value").and_then(Value::as_str)),
+      None => Err(anyhow!("Transform JSON {} is missing the 'transform' attribute", json))
+    }
+  }
+
+  /// Converts the transform to the JSON form stored in the plugin configuration
+  pub fn to_json(&self) -> Value {
+    match self {
+      Transform::Upper => json!({ "transform": "upper" }),
+      Transform::Lower => json!({ "transform": "lower" }),
+      Transform::Trim => json!({ "transform": "trim" }),
+      Transform::StripPrefix(prefix) => json!({ "transform": "stripPrefix", "value": prefix }),
+      Transform::StripSuffix(suffix) => json!({ "transform": "stripSuffix", "value": suffix })
+    }
+  }
+
+  /// Applies the transform to the value
+  pub fn apply(&self, value: &str) -> String {
+    match self {
+      Transform::Upper => value.to_uppercase(),
+      Transform::Lower => value.to_lowercase(),
+      Transform::Trim => value.trim().to_string(),
+      Transform::StripPrefix(prefix) => value.strip_prefix(prefix.as_str()).unwrap_or(value).to_string(),
+      Transform::StripSuffix(suffix) => value.strip_suffix(suffix.as_str()).unwrap_or(value).to_string()
+    }
+  }
+}
+
+/// Applies all the transforms in order to the value
+pub fn apply_transforms(transforms: &[Transform], value: &str) -> String {
+  transforms.iter().fold(value.to_string(), |value, transform| transform.apply(&value))
+}
@@ -23,7 +23,11 @@ pub fn to_value(value: &Value) -> prost_types::Value {
 }
 
 pub fn from_value(value: &prost_types::Value) -> Value {
-  match value.kind.as_ref().unwrap() {
+  let kind = match value.kind.as_ref() {
+    Some(kind) => kind,
+    None => return Value::Null
+  };
+  match kind {
     Kind::NullValue(_) => Value::Null,
     Kind::NumberValue(n) => json!(*n),
     Kind::StringValue(s) => Value::String(s.clone()),
@@ -36,7 +40,11 @@ pub fn from_value(value: &prost_types::Value) -> Value {
 }
 
 pub fn to_boolean(value: &prost_types::Value) -> bool {
-  match value.kind.as_ref().unwrap() {
+  let kind = match value.kind.as_ref() {
+    Some(kind) => kind,
+    None => return false
+  };
+  match kind {
     Kind::NullValue(_) => false,
     Kind::NumberValue(n) => *n == 0.0,
     Kind::StringValue(s) => !s.is_empty(),